@@ -0,0 +1,40 @@
+//! Flutter WebRTC signaling and TURN server as a library.
+//!
+//! Embed the signaling endpoints into an existing axum app:
+//!
+//! ```no_run
+//! use flutter_webrtc_server_rust::{signaling_router, Config, Signaler, TurnServer};
+//! use std::net::SocketAddr;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let config = Config::builder().public_ip("203.0.113.7").build()?;
+//! let signaler = Signaler::builder().config(config).build()?;
+//!
+//! let mut turn = TurnServer::builder(signaler.clone()).build();
+//! turn.start().await?;
+//!
+//! let app: axum::Router = axum::Router::new().merge(signaling_router(signaler, "/rtc"));
+//! let listener = tokio::net::TcpListener::bind("0.0.0.0:8086").await?;
+//! axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod modules;
+
+pub use modules::bots::{BotBehavior, SignalingBot};
+pub use modules::cluster::{Cluster, MemoryBus, MemoryDirectory, MessageBus, PeerDirectory, RemotePeer};
+pub use modules::cluster_mesh::MeshBackend;
+pub use modules::cluster_redis::RedisBackend;
+pub use modules::config::{Config, ConfigBuilder, ConfigHandle};
+pub use modules::echo::EchoBot;
+pub use modules::hooks::{ConnectContext, HookContext, HookDecision, SignalingHooks};
+pub use modules::http::{metrics_router, router as signaling_router};
+pub use modules::metrics::Metrics;
+pub use modules::server::{serve, ServeMode};
+pub use modules::sfu::SfuPeer;
+pub use modules::signaling::{Signaler, SignalerBuilder};
+pub use modules::store::{MemoryStore, SqliteStore, StateStore};
+pub use modules::turn_server::{TurnServer, TurnServerBuilder};
+pub use modules::webhooks::{WebhookEvent, Webhooks};
+pub use modules::webtransport::WebTransportServer;
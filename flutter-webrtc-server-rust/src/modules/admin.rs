@@ -0,0 +1,297 @@
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get, post},
+    Router,
+};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::modules::cdr::{CdrQuery, ExportFormat};
+use crate::modules::cluster::RemotePeer;
+use crate::modules::protocol::Feature;
+use crate::modules::reload::ConfigReloader;
+use crate::modules::signaling::{Peer, PeerInfo, Signaler};
+use crate::modules::turn_server::{record_gauges, AllocationMap, TurnAllocation};
+use crate::modules::util::constant_time_eq;
+
+#[derive(Clone)]
+pub struct AdminState {
+    pub signaler: Arc<Signaler>,
+    pub allocations: AllocationMap,
+    pub reloader: Arc<ConfigReloader>,
+}
+
+#[derive(Debug, Serialize)]
+struct PeerSummary {
+    #[serde(flatten)]
+    info: PeerInfo,
+    connected_at: DateTime<Utc>,
+    remote_addr: Option<SocketAddr>,
+    protocol_version: u32,
+    features: Vec<Feature>,
+}
+
+impl From<&Peer> for PeerSummary {
+    fn from(peer: &Peer) -> Self {
+        Self {
+            info: peer.info.clone(),
+            connected_at: peer.connected_at,
+            remote_addr: peer.remote_addr,
+            protocol_version: peer.capabilities.version,
+            features: peer.capabilities.features.iter().copied().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct DisconnectQuery {
+    reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FormatQuery {
+    #[serde(default)]
+    format: ExportFormat,
+}
+
+#[derive(Debug, Serialize)]
+struct ClusterSummary {
+    node_id: String,
+    local_peers: usize,
+    remote_peers: Vec<RemotePeer>,
+}
+
+#[derive(Debug, Serialize)]
+struct RevokeResponse {
+    revoked: Vec<String>,
+    allocations_removed: usize,
+}
+
+/// Routes of the admin API, to be nested under `/admin`. Every route requires
+/// `Authorization: Bearer <token>`.
+pub fn router<S>(state: AdminState) -> Router<S> {
+    Router::new()
+        .route("/peers", get(list_peers))
+        .route("/peers/:id", get(get_peer).delete(disconnect_peer))
+        .route("/sessions", get(list_sessions))
+        .route("/sessions/:id", get(get_session).delete(end_session))
+        .route("/cdr", get(export_cdr))
+        .route("/cluster", get(cluster_status))
+        .route("/turn/allocations", get(list_allocations))
+        .route("/turn/credentials/:username", delete(revoke_credentials))
+        .route("/config/reload", post(reload_config))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state)
+}
+
+async fn require_token(State(state): State<AdminState>, request: Request, next: Next) -> Response {
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    // Read per request so a reloaded token takes effect immediately
+    let config = state.signaler.config.load();
+    let expected = config.admin.token.as_deref().unwrap_or_default();
+
+    match provided {
+        Some(token) if !expected.is_empty() && constant_time_eq(token.as_bytes(), expected.as_bytes()) => {
+            next.run(request).await
+        }
+        _ => {
+            warn!("Rejected unauthenticated admin request to {}", request.uri().path());
+            (StatusCode::UNAUTHORIZED, "Invalid or missing admin token").into_response()
+        }
+    }
+}
+
+async fn list_peers(State(state): State<AdminState>) -> Json<Vec<PeerSummary>> {
+    let peers = state
+        .signaler
+        .peers
+        .iter()
+        .map(|entry| PeerSummary::from(entry.value()))
+        .collect();
+    Json(peers)
+}
+
+async fn get_peer(State(state): State<AdminState>, Path(id): Path<String>) -> Response {
+    match state.signaler.peers.get(&id) {
+        Some(peer) => Json(PeerSummary::from(peer.value())).into_response(),
+        None => (StatusCode::NOT_FOUND, "Peer not found").into_response(),
+    }
+}
+
+async fn disconnect_peer(
+    State(state): State<AdminState>,
+    Path(id): Path<String>,
+    Query(query): Query<DisconnectQuery>,
+) -> Response {
+    let reason = query.reason.unwrap_or_else(|| "Disconnected by administrator".to_string());
+    info!("Admin request to disconnect peer {}: {}", id, reason);
+
+    if state.signaler.disconnect_peer(&id, &reason).await {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        (StatusCode::NOT_FOUND, "Peer not found").into_response()
+    }
+}
+
+async fn list_sessions(State(state): State<AdminState>) -> Response {
+    match state.signaler.store.sessions().await {
+        Ok(sessions) => Json(sessions).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn get_session(State(state): State<AdminState>, Path(id): Path<String>) -> Response {
+    match state.signaler.store.session(&id).await {
+        Ok(Some(session)) => Json(session).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Session not found").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn end_session(State(state): State<AdminState>, Path(id): Path<String>) -> Response {
+    info!("Admin request to end session {}", id);
+
+    if state.signaler.end_session(&id).await {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        (StatusCode::NOT_FOUND, "Session not found").into_response()
+    }
+}
+
+async fn export_cdr(
+    State(state): State<AdminState>,
+    Query(query): Query<CdrQuery>,
+    Query(FormatQuery { format }): Query<FormatQuery>,
+) -> Response {
+    let Some(cdr) = state.signaler.cdr.clone() else {
+        return (StatusCode::NOT_FOUND, "Call history is disabled").into_response();
+    };
+
+    let rendered = tokio::task::spawn_blocking(move || format.render(&cdr.query(&query)?)).await;
+    match rendered {
+        Ok(Ok(body)) => ([(header::CONTENT_TYPE, format.content_type())], body).into_response(),
+        Ok(Err(e)) => {
+            warn!("Call history query failed: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn cluster_status(State(state): State<AdminState>) -> Response {
+    let Some(cluster) = &state.signaler.cluster else {
+        return (StatusCode::NOT_FOUND, "Cluster mode is disabled").into_response();
+    };
+    Json(ClusterSummary {
+        node_id: cluster.node_id.clone(),
+        local_peers: state.signaler.peers.len(),
+        remote_peers: cluster.remote_peers(),
+    })
+    .into_response()
+}
+
+async fn list_allocations(State(state): State<AdminState>) -> Json<Vec<TurnAllocation>> {
+    let allocations = state.allocations.lock().await;
+    Json(allocations.iter().map(|(client, allocation)| allocation.summary(*client)).collect())
+}
+
+pub(crate) async fn revoke_credentials(
+    State(state): State<AdminState>,
+    Path(username): Path<String>,
+) -> Response {
+    info!("Admin request to revoke TURN credentials for {}", username);
+    let revoked = match state.signaler.revoke_turn_credentials(&username).await {
+        Ok(revoked) => revoked,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+
+    let mut allocations = state.allocations.lock().await;
+    let before = allocations.len();
+    allocations.retain(|_, allocation| !revoked.iter().any(|username| username == allocation.username()));
+    let allocations_removed = before - allocations.len();
+    record_gauges(&state.signaler.metrics, &allocations);
+
+    Json(RevokeResponse {
+        revoked,
+        allocations_removed,
+    })
+    .into_response()
+}
+
+async fn reload_config(State(state): State<AdminState>) -> Response {
+    info!("Admin request to reload configuration");
+
+    match state.reloader.reload() {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => {
+            warn!("Configuration reload failed: {}", e);
+            (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::config::Config;
+
+    async fn serve(token: Option<&str>) -> (String, Arc<Signaler>) {
+        let mut builder = Config::builder();
+        if let Some(token) = token {
+            builder = builder.set("admin", "token", token);
+        }
+        let signaler = Signaler::builder().config(builder.build().unwrap()).build().unwrap();
+        let reloader = Arc::new(ConfigReloader::new(signaler.config.clone(), || Ok(Config::builder().build()?)));
+        let state = AdminState {
+            signaler: signaler.clone(),
+            allocations: Default::default(),
+            reloader,
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/peers", listener.local_addr().unwrap());
+        let app = router::<()>(state);
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, signaler)
+    }
+
+    async fn status(url: &str, authorization: Option<&str>) -> u16 {
+        let mut request = reqwest::Client::new().get(url);
+        if let Some(authorization) = authorization {
+            request = request.header(reqwest::header::AUTHORIZATION, authorization);
+        }
+        request.send().await.unwrap().status().as_u16()
+    }
+
+    #[tokio::test]
+    async fn requests_need_the_admin_token() {
+        let (url, signaler) = serve(Some("s3cret")).await;
+        assert_eq!(status(&url, None).await, 401);
+        assert_eq!(status(&url, Some("Bearer wrong")).await, 401);
+        assert_eq!(status(&url, Some("Bearer s3cre")).await, 401);
+        assert_eq!(status(&url, Some("s3cret")).await, 401);
+        assert_eq!(status(&url, Some("Bearer s3cret")).await, 200);
+
+        // A reloaded token replaces the old one at once
+        signaler.config.store(Arc::new(Config::builder().set("admin", "token", "rotated").build().unwrap()));
+        assert_eq!(status(&url, Some("Bearer s3cret")).await, 401);
+        assert_eq!(status(&url, Some("Bearer rotated")).await, 200);
+    }
+
+    #[tokio::test]
+    async fn the_api_is_closed_without_a_token() {
+        let (url, _signaler) = serve(None).await;
+        assert_eq!(status(&url, None).await, 401);
+        assert_eq!(status(&url, Some("Bearer ")).await, 401);
+    }
+}
@@ -0,0 +1,419 @@
+use anyhow::{Context, Result};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::time::Duration;
+
+use crate::modules::signaling::{
+    Byebye, CandidatePayload, Connection, ConnectionEvents, DescriptionPayload, IceCandidate, Method, PeerInfo,
+    SessionDescription, ShutdownPhase, Signaler,
+};
+
+/// How a signaling bot replies to offers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BotBehavior {
+    /// Answers with an SDP mirroring the offer and one host candidate
+    Answer,
+    /// Hangs up with `bye`
+    Reject,
+    /// Hangs up with `bye` and reason `busy`
+    Busy,
+    /// Never replies
+    Timeout,
+    /// Answers with an SDP and a candidate no WebRTC stack can parse
+    Malformed,
+    /// Answers with a frame missing its description, which client decoders
+    /// must reject
+    Undecodable,
+}
+
+impl BotBehavior {
+    const ALL: [BotBehavior; 6] = [
+        BotBehavior::Answer,
+        BotBehavior::Reject,
+        BotBehavior::Busy,
+        BotBehavior::Timeout,
+        BotBehavior::Malformed,
+        BotBehavior::Undecodable,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BotBehavior::Answer => "answer",
+            BotBehavior::Reject => "reject",
+            BotBehavior::Busy => "busy",
+            BotBehavior::Timeout => "timeout",
+            BotBehavior::Malformed => "malformed",
+            BotBehavior::Undecodable => "undecodable",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|behavior| behavior.as_str() == value)
+    }
+
+    // Whether the bot is in a call after replying
+    fn answers(self) -> bool {
+        matches!(self, BotBehavior::Answer | BotBehavior::Malformed)
+    }
+}
+
+/// A virtual peer that speaks only the signaling protocol and replies to every
+/// offer the same scripted way, so client test suites can exercise each call
+/// outcome against a real server. No media ever flows.
+pub struct SignalingBot {
+    state: Arc<BotState>,
+    events: ConnectionEvents,
+}
+
+struct BotState {
+    signaler: Arc<Signaler>,
+    peer_id: String,
+    behavior: BotBehavior,
+    connection: Connection,
+}
+
+impl SignalingBot {
+    /// Registers `peer_id` as a peer every client can call.
+    pub async fn register(signaler: Arc<Signaler>, peer_id: &str, behavior: BotBehavior) -> Result<Self> {
+        let (connection, events) = signaler.connect_server_peer();
+        let register = Method::New(PeerInfo {
+            id: peer_id.to_string(),
+            name: format!("Test bot ({})", behavior.as_str()),
+            user_agent: concat!("flutter-webrtc-server-rust/", env!("CARGO_PKG_VERSION")).to_string(),
+        });
+        signaler
+            .receive(&connection, register)
+            .await
+            .with_context(|| format!("Failed to register bot {}", peer_id))?;
+
+        let state = Arc::new(BotState {
+            signaler,
+            peer_id: peer_id.to_string(),
+            behavior,
+            connection,
+        });
+        Ok(Self { state, events })
+    }
+
+    /// Replies to offers until the signaler starts draining or the peer is
+    /// disconnected.
+    pub async fn run(mut self) {
+        let state = self.state;
+        let mut shutdown = state.signaler.watch_shutdown();
+        shutdown.mark_changed();
+        // Callers of the sessions the bot answered, to tell renegotiations
+        // from new calls
+        let mut calls = HashMap::new();
+
+        loop {
+            let message = tokio::select! {
+                message = self.events.messages.recv() => match message {
+                    Some(message) => message,
+                    None => break,
+                },
+                Some(frame) = self.events.close.recv() => {
+                    info!("🔌 Bot {} disconnected: {}", state.peer_id, frame.reason);
+                    break;
+                }
+                Ok(()) = shutdown.changed() => {
+                    if matches!(*shutdown.borrow_and_update(), ShutdownPhase::Running) {
+                        continue;
+                    }
+                    break;
+                }
+            };
+
+            match message {
+                Method::Offer(offer) => {
+                    info!("🤖 Bot {} got an offer from {}, replying with {}", state.peer_id, offer.from, state.behavior.as_str());
+                    let renegotiation = calls.contains_key(&offer.session_id);
+                    if state.behavior.answers() {
+                        calls.insert(offer.session_id.clone(), offer.from.clone());
+                    }
+                    tokio::spawn(state.clone().reply(offer, renegotiation));
+                }
+                Method::Bye(bye) => {
+                    calls.remove(&bye.session_id);
+                }
+                Method::Peers(peers) => {
+                    // Callers that went away without hanging up
+                    calls.retain(|_, caller| peers.iter().any(|peer| peer.id == *caller));
+                }
+                Method::Candidate(candidate) => {
+                    debug!("Bot {} ignoring candidate from {}", state.peer_id, candidate.from);
+                }
+                Method::Error(e) => warn!("⚠️ Signaling rejected {} from bot {}: {}", e.request, state.peer_id, e.reason),
+                _ => {}
+            }
+        }
+
+        state.signaler.disconnect(&state.connection).await;
+        info!("Bot {} stopped", state.peer_id);
+    }
+}
+
+impl BotState {
+    async fn reply(self: Arc<Self>, offer: DescriptionPayload, renegotiation: bool) {
+        let delay = Duration::from_millis(self.signaler.config.load().bots.reply_delay_ms);
+        // Renegotiations are answered right away, like a client in a call would
+        if !renegotiation {
+            tokio::time::sleep(delay).await;
+        }
+
+        let (sdp, candidate) = match self.behavior {
+            BotBehavior::Answer => (canned_answer(&offer.description.sdp), HOST_CANDIDATE.to_string()),
+            BotBehavior::Malformed => (MALFORMED_SDP.to_string(), MALFORMED_CANDIDATE.to_string()),
+            BotBehavior::Reject | BotBehavior::Busy => {
+                let reason = (self.behavior == BotBehavior::Busy).then(|| "busy".to_string());
+                self.send(Method::Bye(Byebye {
+                    session_id: offer.session_id,
+                    from: self.peer_id.clone(),
+                    reason,
+                }))
+                .await;
+                return;
+            }
+            BotBehavior::Undecodable => {
+                self.send_raw(
+                    &offer.from,
+                    json!({"type": "answer", "data": {
+                        "from": self.peer_id,
+                        "to": offer.from,
+                        "session_id": offer.session_id,
+                    }}),
+                );
+                return;
+            }
+            BotBehavior::Timeout => return,
+        };
+
+        self.send(Method::Answer(DescriptionPayload {
+            from: self.peer_id.clone(),
+            to: offer.from.clone(),
+            session_id: offer.session_id.clone(),
+            description: SessionDescription {
+                sdp,
+                sdp_type: "answer".to_string(),
+            },
+            extra: serde_json::Map::new(),
+        }))
+        .await;
+        self.send(Method::Candidate(CandidatePayload {
+            from: self.peer_id.clone(),
+            to: offer.from,
+            session_id: offer.session_id,
+            candidate: IceCandidate {
+                candidate,
+                sdp_mid: None,
+                sdp_mline_index: Some(0),
+            },
+            extra: serde_json::Map::new(),
+        }))
+        .await;
+    }
+
+    // Raw frames would fail the signaler's own decoding, so they go straight
+    // to the connection of a caller on this node
+    fn send_raw(&self, to: &str, frame: serde_json::Value) {
+        let delivered = match self.signaler.peers.get(to) {
+            Some(peer) => peer.sender.send(Method::Raw(frame)).is_ok(),
+            None => false,
+        };
+        if !delivered {
+            warn!("⚠️ Bot {} could not send a raw frame to {}", self.peer_id, to);
+        }
+    }
+
+    async fn send(&self, message: Method) {
+        let request = message.request_type();
+        if let Err(e) = self.signaler.receive(&self.connection, message).await {
+            error!("❌ Failed to send {} from bot {}: {}", request, self.peer_id, e);
+        }
+    }
+}
+
+/// Host candidate in TEST-NET-1, which never connects
+const HOST_CANDIDATE: &str = "candidate:1 1 udp 2130706431 192.0.2.1 9 typ host";
+
+/// Passes server validation, which only checks the `v=` line
+const MALFORMED_SDP: &str = "v=0\r\nthis is not a session description\r\n";
+
+const MALFORMED_CANDIDATE: &str = "candidate:not a candidate";
+
+// An answer accepting each m-section of the offer with its first format,
+// receive-only, with made-up ICE and DTLS parameters
+fn canned_answer(offer: &str) -> String {
+    let mut sections: Vec<Vec<String>> = Vec::new();
+    let mut mids = Vec::new();
+    for line in offer.lines() {
+        if let Some(media) = line.strip_prefix("m=") {
+            let fields: Vec<&str> = media.split_whitespace().collect();
+            let (kind, proto, format) = match fields.as_slice() {
+                [kind, _port, proto, format, ..] => (*kind, *proto, *format),
+                _ => continue,
+            };
+            let mut section = vec![format!("m={} 9 {} {}", kind, proto, format), "c=IN IP4 0.0.0.0".to_string()];
+            if kind == "application" {
+                section.push("a=sctp-port:5000".to_string());
+            } else {
+                section.push("a=recvonly".to_string());
+                section.push("a=rtcp-mux".to_string());
+            }
+            section.extend([
+                "a=ice-ufrag:bot0".to_string(),
+                "a=ice-pwd:botpasswordbotpassword00".to_string(),
+                format!("a=fingerprint:sha-256 {}", ["00"; 32].join(":")),
+                "a=setup:active".to_string(),
+            ]);
+            mids.push(sections.len().to_string());
+            sections.push(section);
+        } else if let Some(section) = sections.last_mut() {
+            let Some(format) = section[0].rsplit(' ').next().map(str::to_string) else {
+                continue;
+            };
+            if let Some(mid) = line.strip_prefix("a=mid:") {
+                if let Some(last) = mids.last_mut() {
+                    *last = mid.trim().to_string();
+                }
+                section.push(line.to_string());
+            } else if line.starts_with(&format!("a=rtpmap:{} ", format)) || line.starts_with(&format!("a=fmtp:{} ", format)) {
+                section.push(line.to_string());
+            }
+        }
+    }
+
+    let mut lines = vec![
+        "v=0".to_string(),
+        "o=- 0 1 IN IP4 127.0.0.1".to_string(),
+        "s=-".to_string(),
+        "t=0 0".to_string(),
+    ];
+    if offer.contains("a=group:BUNDLE") && !mids.is_empty() {
+        lines.push(format!("a=group:BUNDLE {}", mids.join(" ")));
+    }
+    lines.extend(sections.into_iter().flatten());
+    let mut sdp = lines.join("\r\n");
+    sdp.push_str("\r\n");
+    sdp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::config::Config;
+    use tokio::time::Instant;
+
+    const DELAY: Duration = Duration::from_millis(300);
+
+    async fn signaler_with(bots: &[(&str, BotBehavior)]) -> Arc<Signaler> {
+        let config = Config::builder()
+            .set("bots", "reply_delay_ms", DELAY.as_millis().to_string())
+            .build()
+            .unwrap();
+        let signaler = Signaler::builder().config(config).build().unwrap();
+        for &(peer_id, behavior) in bots {
+            let bot = SignalingBot::register(signaler.clone(), peer_id, behavior).await.unwrap();
+            tokio::spawn(bot.run());
+        }
+        signaler
+    }
+
+    async fn client(signaler: &Signaler, id: &str) -> (Connection, ConnectionEvents) {
+        let (connection, events) = signaler.connect(None);
+        let register = json!({"type": "new", "data": {"id": id, "name": id, "user_agent": "test"}});
+        signaler.receive(&connection, serde_json::from_value(register).unwrap()).await.unwrap();
+        (connection, events)
+    }
+
+    async fn offer(signaler: &Signaler, connection: &Connection, to: &str) {
+        let offer = json!({"type": "offer", "data": {
+            "from": "alice", "to": to, "session_id": format!("alice-{}", to),
+            "description": {"type": "offer", "sdp": "v=0\r\nm=audio 9 UDP/TLS/RTP/SAVPF 111\r\na=mid:0\r\n"},
+        }});
+        signaler.receive(connection, serde_json::from_value(offer).unwrap()).await.unwrap();
+    }
+
+    // Skips messages such as peer lists until one matches
+    async fn expect(events: &mut ConnectionEvents, matches: impl Fn(&Method) -> bool) -> Method {
+        tokio::time::timeout(Duration::from_secs(2), async {
+            loop {
+                let message = events.messages.recv().await.unwrap();
+                if matches(&message) {
+                    return message;
+                }
+            }
+        })
+        .await
+        .expect("expected message not received")
+    }
+
+    // How long the bot took to answer an offer
+    async fn answer_time(signaler: &Signaler, alice: &Connection, events: &mut ConnectionEvents) -> Duration {
+        let sent = Instant::now();
+        offer(signaler, alice, "bot").await;
+        expect(events, |message| matches!(message, Method::Answer(_))).await;
+        sent.elapsed()
+    }
+
+    #[tokio::test]
+    async fn answers_new_calls_after_the_delay_and_renegotiations_at_once() {
+        let signaler = signaler_with(&[("bot", BotBehavior::Answer)]).await;
+        let (alice, mut events) = client(&signaler, "alice").await;
+
+        assert!(answer_time(&signaler, &alice, &mut events).await >= DELAY);
+        match expect(&mut events, |message| matches!(message, Method::Candidate(_))).await {
+            Method::Candidate(candidate) => assert_eq!(candidate.candidate.candidate, HOST_CANDIDATE),
+            _ => unreachable!(),
+        }
+        assert!(answer_time(&signaler, &alice, &mut events).await < DELAY);
+    }
+
+    #[tokio::test]
+    async fn reject_and_busy_hang_up() {
+        let signaler = signaler_with(&[("rejecting", BotBehavior::Reject), ("busy", BotBehavior::Busy)]).await;
+        let (alice, mut events) = client(&signaler, "alice").await;
+
+        for (bot, reason) in [("rejecting", None), ("busy", Some("busy"))] {
+            offer(&signaler, &alice, bot).await;
+            match expect(&mut events, |message| matches!(message, Method::Bye(_))).await {
+                Method::Bye(bye) => {
+                    assert_eq!(bye.session_id, format!("alice-{}", bot));
+                    assert_eq!(bye.reason.as_deref(), reason);
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn undecodable_answers_lack_a_description() {
+        let signaler = signaler_with(&[("bot", BotBehavior::Undecodable)]).await;
+        let (alice, mut events) = client(&signaler, "alice").await;
+
+        offer(&signaler, &alice, "bot").await;
+        match expect(&mut events, |message| matches!(message, Method::Raw(_))).await {
+            Method::Raw(frame) => {
+                assert_eq!(frame["type"], "answer");
+                assert_eq!(frame["data"]["session_id"], "alice-bot");
+                assert!(frame["data"].get("description").is_none());
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn calls_of_callers_that_left_are_forgotten() {
+        let signaler = signaler_with(&[("bot", BotBehavior::Answer)]).await;
+        let (alice, mut events) = client(&signaler, "alice").await;
+        answer_time(&signaler, &alice, &mut events).await;
+
+        // Back with the same session id, which is a new call to the bot
+        signaler.disconnect(&alice).await;
+        let (alice, mut events) = client(&signaler, "alice").await;
+        assert!(answer_time(&signaler, &alice, &mut events).await >= DELAY);
+    }
+}
@@ -0,0 +1,326 @@
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::{error, info};
+use rusqlite::{params, Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{mpsc, Mutex};
+
+use crate::modules::signaling::{CallSession, EndReason};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS call_records (
+    session_id TEXT NOT NULL,
+    caller_id TEXT NOT NULL,
+    callee_id TEXT NOT NULL,
+    room TEXT,
+    started_at TEXT NOT NULL,
+    answered_at TEXT,
+    ended_at TEXT NOT NULL,
+    duration_seconds REAL,
+    end_reason TEXT NOT NULL,
+    turn_used INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS call_records_started_at ON call_records (started_at);
+";
+
+/// Call detail record of one ended call session.
+#[derive(Debug, Clone, Serialize)]
+pub struct CallRecord {
+    pub session_id: String,
+    pub caller_id: String,
+    pub callee_id: String,
+    pub room: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub answered_at: Option<DateTime<Utc>>,
+    pub ended_at: DateTime<Utc>,
+    /// Answer to end, `None` for calls that were never answered
+    pub duration_seconds: Option<f64>,
+    pub end_reason: EndReason,
+    pub turn_used: bool,
+}
+
+impl CallRecord {
+    /// Returns `None` for sessions that have not ended.
+    pub fn from_session(session: &CallSession) -> Option<Self> {
+        let ended_at = session.ended_at?;
+        Some(Self {
+            session_id: session.session_id.clone(),
+            caller_id: session.caller_id.clone(),
+            callee_id: session.callee_id.clone(),
+            room: session.room.clone(),
+            started_at: session.started_at,
+            answered_at: session.answered_at,
+            ended_at,
+            duration_seconds: session
+                .answered_at
+                .map(|answered_at| (ended_at - answered_at).num_milliseconds() as f64 / 1000.0),
+            end_reason: session.end_reason.unwrap_or(EndReason::Bye),
+            turn_used: session.turn_used,
+        })
+    }
+}
+
+/// Filters for `CdrStore::query`, also the query string of `GET /admin/cdr`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CdrQuery {
+    /// Calls started at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Calls started before this time
+    pub to: Option<DateTime<Utc>>,
+    /// Calls where this peer was caller or callee
+    pub peer: Option<String>,
+    pub room: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Csv => "text/csv",
+        }
+    }
+
+    pub fn render(&self, records: &[CallRecord]) -> Result<String> {
+        match self {
+            ExportFormat::Json => Ok(serde_json::to_string_pretty(records)? + "\n"),
+            ExportFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(Vec::new());
+                for record in records {
+                    writer.serialize(record)?;
+                }
+                Ok(String::from_utf8(writer.into_inner()?)?)
+            }
+        }
+    }
+}
+
+/// Call history in an SQLite database. Records are written by a background
+/// thread so ending a call never waits on the disk.
+pub struct CdrStore {
+    reader: Mutex<Connection>,
+    writer: Option<mpsc::Sender<CallRecord>>,
+}
+
+impl CdrStore {
+    /// Opens or creates the database at `path` for recording and querying.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let connection = Connection::open(path)
+            .map_err(|e| anyhow::anyhow!("Failed to open call history {}: {}", path.display(), e))?;
+        // WAL lets queries run while the writer thread inserts
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.execute_batch(SCHEMA)?;
+
+        let (writer, records) = mpsc::channel::<CallRecord>();
+        std::thread::Builder::new()
+            .name("cdr-writer".to_string())
+            .spawn(move || {
+                for record in records {
+                    if let Err(e) = insert(&connection, &record) {
+                        error!("❌ Failed to store call record for {}: {}", record.session_id, e);
+                    }
+                }
+            })?;
+
+        info!("Recording call history to {}", path.display());
+        Ok(Self {
+            reader: Mutex::new(Connection::open(path)?),
+            writer: Some(writer),
+        })
+    }
+
+    /// Opens an existing database for queries only, e.g. for exports while the
+    /// server is running.
+    pub fn open_read_only(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| anyhow::anyhow!("Failed to open call history {}: {}", path.display(), e))?;
+        Ok(Self {
+            reader: Mutex::new(connection),
+            writer: None,
+        })
+    }
+
+    /// Queues `record` for writing.
+    pub fn record(&self, record: CallRecord) {
+        if let Some(writer) = &self.writer {
+            if writer.send(record).is_err() {
+                error!("❌ Call history writer has stopped, dropping call record");
+            }
+        }
+    }
+
+    /// Matching records, newest first. Blocks on SQLite.
+    pub fn query(&self, query: &CdrQuery) -> Result<Vec<CallRecord>> {
+        let connection = self.reader.lock().unwrap_or_else(|e| e.into_inner());
+        let mut statement = connection.prepare(
+            "SELECT session_id, caller_id, callee_id, room, started_at, answered_at, ended_at,
+                    duration_seconds, end_reason, turn_used
+             FROM call_records
+             WHERE (?1 IS NULL OR started_at >= ?1)
+               AND (?2 IS NULL OR started_at < ?2)
+               AND (?3 IS NULL OR caller_id = ?3 OR callee_id = ?3)
+               AND (?4 IS NULL OR room = ?4)
+             ORDER BY started_at DESC
+             LIMIT ?5",
+        )?;
+
+        let limit = query.limit.map_or(-1, |limit| limit as i64);
+        let rows = statement.query_map(
+            params![
+                query.from.map(timestamp),
+                query.to.map(timestamp),
+                query.peer,
+                query.room,
+                limit
+            ],
+            |row| {
+                Ok(CallRecord {
+                    session_id: row.get(0)?,
+                    caller_id: row.get(1)?,
+                    callee_id: row.get(2)?,
+                    room: row.get(3)?,
+                    started_at: row.get(4)?,
+                    answered_at: row.get(5)?,
+                    ended_at: row.get(6)?,
+                    duration_seconds: row.get(7)?,
+                    end_reason: row.get::<_, String>(8)?.parse().unwrap_or(EndReason::Bye),
+                    turn_used: row.get(9)?,
+                })
+            },
+        )?;
+
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+}
+
+// Fixed-width UTC timestamps, so string comparison in SQL orders them correctly
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn insert(connection: &Connection, record: &CallRecord) -> Result<()> {
+    connection.execute(
+        "INSERT INTO call_records (session_id, caller_id, callee_id, room, started_at, answered_at,
+                                   ended_at, duration_seconds, end_reason, turn_used)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            record.session_id,
+            record.caller_id,
+            record.callee_id,
+            record.room,
+            timestamp(record.started_at),
+            record.answered_at.map(timestamp),
+            timestamp(record.ended_at),
+            record.duration_seconds,
+            record.end_reason.as_str(),
+            record.turn_used,
+        ],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+    }
+
+    fn call(session_id: &str, caller_id: &str, callee_id: &str, room: Option<&str>, hours: i64) -> CallRecord {
+        let started_at = start() + Duration::hours(hours);
+        CallRecord {
+            session_id: session_id.to_string(),
+            caller_id: caller_id.to_string(),
+            callee_id: callee_id.to_string(),
+            room: room.map(str::to_string),
+            started_at,
+            answered_at: Some(started_at + Duration::seconds(2)),
+            ended_at: started_at + Duration::seconds(62),
+            duration_seconds: Some(60.0),
+            end_reason: EndReason::Bye,
+            turn_used: false,
+        }
+    }
+
+    fn sessions(store: &CdrStore, query: CdrQuery) -> Vec<String> {
+        store.query(&query).unwrap().into_iter().map(|record| record.session_id).collect()
+    }
+
+    #[test]
+    fn queries_filter_by_time_peer_and_room() {
+        let dir = std::env::temp_dir().join(format!("cdr-{}", uuid::Uuid::new_v4().simple()));
+        let store = CdrStore::open(dir.join("cdr.db")).unwrap();
+        {
+            // Inserted directly, the writer thread would leave the timing to chance
+            let connection = store.reader.lock().unwrap();
+            insert(&connection, &call("alice-bob", "alice", "bob", None, 0)).unwrap();
+            insert(&connection, &call("bob-carol", "bob", "carol", Some("blue"), 1)).unwrap();
+            insert(&connection, &call("carol-alice", "carol", "alice", Some("blue"), 2)).unwrap();
+        }
+
+        assert_eq!(sessions(&store, CdrQuery::default()), ["carol-alice", "bob-carol", "alice-bob"]);
+        let from = Some(start() + Duration::hours(1));
+        assert_eq!(sessions(&store, CdrQuery { from, ..Default::default() }), ["carol-alice", "bob-carol"]);
+        let to = Some(start() + Duration::hours(1));
+        assert_eq!(sessions(&store, CdrQuery { to, ..Default::default() }), ["alice-bob"]);
+        let peer = Some("alice".to_string());
+        assert_eq!(sessions(&store, CdrQuery { peer, ..Default::default() }), ["carol-alice", "alice-bob"]);
+        let room = Some("blue".to_string());
+        assert_eq!(sessions(&store, CdrQuery { room, ..Default::default() }), ["carol-alice", "bob-carol"]);
+        assert_eq!(sessions(&store, CdrQuery { limit: Some(1), ..Default::default() }), ["carol-alice"]);
+
+        let combined = CdrQuery {
+            to: Some(start() + Duration::hours(2)),
+            peer: Some("carol".to_string()),
+            room: Some("blue".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(sessions(&store, combined), ["bob-carol"]);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn exports_render_json_and_quoted_csv() {
+        let mut unanswered = call("alice,bob \"2\"", "alice", "bob", Some("blue"), 0);
+        unanswered.answered_at = None;
+        unanswered.duration_seconds = None;
+        unanswered.end_reason = EndReason::Unreachable;
+        let records = [call("alice-bob", "alice", "bob", None, 1), unanswered];
+
+        let json: serde_json::Value = serde_json::from_str(&ExportFormat::Json.render(&records).unwrap()).unwrap();
+        assert_eq!(json[0]["session_id"], "alice-bob");
+        assert_eq!(json[0]["duration_seconds"], 60.0);
+        assert_eq!(json[1]["session_id"], "alice,bob \"2\"");
+        assert_eq!(json[1]["end_reason"], "unreachable");
+        assert!(json[1]["answered_at"].is_null());
+
+        let csv = ExportFormat::Csv.render(&records).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("session_id,caller_id,callee_id,room,started_at,answered_at,ended_at,duration_seconds,end_reason,turn_used")
+        );
+        assert!(lines.next().unwrap().starts_with("alice-bob,alice,bob,,2024-05-01T13:00:00Z,"));
+        let quoted = lines.next().unwrap();
+        assert!(quoted.starts_with("\"alice,bob \"\"2\"\"\",alice,bob,blue,"), "{}", quoted);
+        assert!(quoted.ends_with(",,unreachable,false"), "{}", quoted);
+        assert_eq!(lines.next(), None);
+    }
+}
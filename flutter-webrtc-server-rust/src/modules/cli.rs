@@ -0,0 +1,99 @@
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand};
+use log::info;
+use std::path::{Path, PathBuf};
+
+use crate::modules::cdr::ExportFormat;
+use crate::modules::config::Config;
+use crate::modules::config_source::Source;
+
+const DEFAULT_CONFIG: &str = "configs/config.ini";
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Flutter WebRTC signaling and TURN server")]
+pub struct Cli {
+    /// Config file, TOML if it ends in `.toml` and INI otherwise
+    /// [default: configs/config.ini, if present]
+    #[arg(short, long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Overrides `[general] bind`
+    #[arg(long, global = true)]
+    pub bind: Option<String>,
+
+    /// Overrides `[general] port`
+    #[arg(long, global = true)]
+    pub port: Option<u16>,
+
+    /// Overrides `[turn] public_ip`
+    #[arg(long, global = true)]
+    pub public_ip: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Run signaling and TURN (default)
+    Serve,
+    /// Run only the TURN server, with health, metrics and admin endpoints
+    TurnOnly,
+    /// Run only signaling and the HTTP API, without the TURN server
+    SignalingOnly,
+    /// Validate the configuration and print the effective settings
+    CheckConfig,
+    /// Issue TURN REST API credentials for a user into the `[store]` database
+    GenCredential {
+        /// User the credentials are issued for
+        user: String,
+    },
+    /// Export call detail records from the `[cdr]` database
+    Cdr {
+        /// Calls started at or after this RFC 3339 time
+        #[arg(long)]
+        from: Option<DateTime<Utc>>,
+        /// Calls started before this RFC 3339 time
+        #[arg(long)]
+        to: Option<DateTime<Utc>>,
+        /// Calls where this peer was caller or callee
+        #[arg(long)]
+        peer: Option<String>,
+        #[arg(long)]
+        room: Option<String>,
+        #[arg(long)]
+        limit: Option<usize>,
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+impl Cli {
+    /// Resolves the configuration from, in increasing precedence: built-in defaults,
+    /// the config file, `WEBRTC_<SECTION>__<KEY>` environment variables and flags.
+    pub fn load_config(&self) -> anyhow::Result<Config> {
+        let mut builder = Config::builder();
+
+        match &self.config {
+            Some(path) => builder = builder.file(path)?,
+            None if Path::new(DEFAULT_CONFIG).exists() => builder = builder.file(DEFAULT_CONFIG)?,
+            None => info!("No {} found, using defaults and environment", DEFAULT_CONFIG),
+        }
+        builder = builder.env();
+
+        if let Some(bind) = &self.bind {
+            builder = builder.set_from("general", "bind", bind, Source::Cli("--bind"));
+        }
+        if let Some(port) = self.port {
+            builder = builder.set_from("general", "port", port, Source::Cli("--port"));
+        }
+        if let Some(public_ip) = &self.public_ip {
+            builder = builder.set_from("turn", "public_ip", public_ip, Source::Cli("--public-ip"));
+        }
+
+        Ok(builder.build()?)
+    }
+}
@@ -0,0 +1,428 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use dashmap::DashMap;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::modules::signaling::{Method, PeerInfo};
+
+/// A peer and the node its WebSocket is connected to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemotePeer {
+    pub node_id: String,
+    #[serde(flatten)]
+    pub info: PeerInfo,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ClusterMessage {
+    PeerJoined { peer: RemotePeer },
+    PeerLeft { peer_id: String },
+    /// Signaling message for a peer connected to the receiving node
+    Deliver { to: String, message: Method },
+}
+
+/// A `ClusterMessage` with the node that sent it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    pub from: String,
+    #[serde(flatten)]
+    pub message: ClusterMessage,
+}
+
+/// Cluster-wide record of which node each peer is connected to.
+#[async_trait]
+pub trait PeerDirectory: Send + Sync {
+    async fn register(&self, peer: &RemotePeer) -> Result<()>;
+    /// Removes `peer_id`, unless it has since registered on another node.
+    async fn unregister(&self, node_id: &str, peer_id: &str) -> Result<()>;
+    /// Removes every peer of `node_id`, e.g. ones left behind by a crash.
+    async fn clear_node(&self, node_id: &str) -> Result<()>;
+    /// Marks `node_id` as alive. Directories that can expire nodes drop the
+    /// peers of nodes that stop calling this.
+    async fn heartbeat(&self, _node_id: &str) -> Result<()> {
+        Ok(())
+    }
+    /// Peers of every live node.
+    async fn list(&self) -> Result<Vec<RemotePeer>>;
+}
+
+/// Carries `Envelope`s between nodes.
+#[async_trait]
+pub trait MessageBus: Send + Sync {
+    /// Sends to `node_id`, or to every node when `None`. Nodes may receive their own broadcasts.
+    async fn publish(&self, node_id: Option<&str>, envelope: &Envelope) -> Result<()>;
+    /// Messages addressed to `node_id` or broadcast.
+    async fn subscribe(&self, node_id: &str) -> Result<mpsc::UnboundedReceiver<Envelope>>;
+}
+
+/// Directory for nodes in one process, e.g. a single node or tests.
+#[derive(Default)]
+pub struct MemoryDirectory {
+    peers: DashMap<String, RemotePeer>,
+}
+
+#[async_trait]
+impl PeerDirectory for MemoryDirectory {
+    async fn register(&self, peer: &RemotePeer) -> Result<()> {
+        self.peers.insert(peer.info.id.clone(), peer.clone());
+        Ok(())
+    }
+
+    async fn unregister(&self, node_id: &str, peer_id: &str) -> Result<()> {
+        self.peers.remove_if(peer_id, |_, peer| peer.node_id == node_id);
+        Ok(())
+    }
+
+    async fn clear_node(&self, node_id: &str) -> Result<()> {
+        self.peers.retain(|_, peer| peer.node_id != node_id);
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<RemotePeer>> {
+        Ok(self.peers.iter().map(|entry| entry.value().clone()).collect())
+    }
+}
+
+/// Bus for nodes in one process.
+#[derive(Default)]
+pub struct MemoryBus {
+    nodes: DashMap<String, mpsc::UnboundedSender<Envelope>>,
+}
+
+#[async_trait]
+impl MessageBus for MemoryBus {
+    async fn publish(&self, node_id: Option<&str>, envelope: &Envelope) -> Result<()> {
+        match node_id {
+            Some(node_id) => {
+                let node = self
+                    .nodes
+                    .get(node_id)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown node {}", node_id))?;
+                node.send(envelope.clone())?;
+            }
+            None => {
+                self.nodes.retain(|_, node| node.send(envelope.clone()).is_ok());
+            }
+        }
+        Ok(())
+    }
+
+    async fn subscribe(&self, node_id: &str) -> Result<mpsc::UnboundedReceiver<Envelope>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.nodes.insert(node_id.to_string(), tx);
+        Ok(rx)
+    }
+}
+
+/// What the cluster tells the local `Signaler`.
+#[derive(Debug)]
+pub enum ClusterEvent {
+    /// Peers on other nodes changed, local clients need a new `peers` list
+    PeersChanged,
+    /// A peer on another node went away
+    PeerLeft(String),
+    Deliver { to: String, message: Method },
+}
+
+enum Op {
+    Register(PeerInfo),
+    Unregister(String),
+    Send { node_id: String, message: ClusterMessage },
+}
+
+/// This node's membership in a cluster. Calls never block: directory updates
+/// and messages are queued to a background task, and lookups read a local
+/// cache of remote peers kept current from the bus and periodic directory syncs.
+pub struct Cluster {
+    pub node_id: String,
+    remote: DashMap<String, RemotePeer>,
+    ops: mpsc::UnboundedSender<Op>,
+}
+
+impl Cluster {
+    /// Joins the cluster as `node_id`. `sync_interval` is how often the node
+    /// heartbeats and reloads the directory. Must run inside a Tokio runtime.
+    pub fn start(
+        node_id: impl Into<String>,
+        directory: Arc<dyn PeerDirectory>,
+        bus: Arc<dyn MessageBus>,
+        sync_interval: Duration,
+    ) -> Result<(Arc<Self>, mpsc::UnboundedReceiver<ClusterEvent>)> {
+        let runtime = tokio::runtime::Handle::try_current()
+            .map_err(|_| anyhow::anyhow!("Cluster mode needs a Tokio runtime"))?;
+        let (ops_tx, ops_rx) = mpsc::unbounded_channel();
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+
+        let cluster = Arc::new(Self {
+            node_id: node_id.into(),
+            remote: DashMap::new(),
+            ops: ops_tx,
+        });
+        info!("🌐 Joining cluster as node {}", cluster.node_id);
+        runtime.spawn(run(cluster.clone(), directory, bus, sync_interval, ops_rx, events_tx));
+
+        Ok((cluster, events_rx))
+    }
+
+    /// Node a peer not connected here is registered on.
+    pub fn locate(&self, peer_id: &str) -> Option<String> {
+        self.remote.get(peer_id).map(|peer| peer.node_id.clone())
+    }
+
+    /// Peers registered on other nodes.
+    pub fn remote_peers(&self) -> Vec<RemotePeer> {
+        self.remote.iter().map(|entry| entry.value().clone()).collect()
+    }
+
+    pub fn register(&self, peer: &PeerInfo) {
+        let _ = self.ops.send(Op::Register(peer.clone()));
+    }
+
+    pub fn unregister(&self, peer_id: &str) {
+        let _ = self.ops.send(Op::Unregister(peer_id.to_string()));
+    }
+
+    /// Forwards `message` to `to` on `node_id`.
+    pub fn deliver(&self, node_id: &str, to: &str, message: Method) {
+        let _ = self.ops.send(Op::Send {
+            node_id: node_id.to_string(),
+            message: ClusterMessage::Deliver { to: to.to_string(), message },
+        });
+    }
+
+    // Replaces the cache with the directory's view. Returns the peers that went
+    // away and whether any appeared or moved.
+    fn sync(&self, peers: Vec<RemotePeer>) -> (Vec<String>, bool) {
+        let current: HashMap<String, RemotePeer> = peers
+            .into_iter()
+            .filter(|peer| peer.node_id != self.node_id)
+            .map(|peer| (peer.info.id.clone(), peer))
+            .collect();
+
+        let mut gone = Vec::new();
+        self.remote.retain(|id, _| {
+            let keep = current.contains_key(id);
+            if !keep {
+                gone.push(id.clone());
+            }
+            keep
+        });
+        let mut changed = false;
+        for (id, peer) in current {
+            if self.remote.insert(id, peer.clone()).as_ref() != Some(&peer) {
+                changed = true;
+            }
+        }
+        (gone, changed)
+    }
+}
+
+async fn run(
+    cluster: Arc<Cluster>,
+    directory: Arc<dyn PeerDirectory>,
+    bus: Arc<dyn MessageBus>,
+    sync_interval: Duration,
+    mut ops: mpsc::UnboundedReceiver<Op>,
+    events: mpsc::UnboundedSender<ClusterEvent>,
+) {
+    let node_id = cluster.node_id.clone();
+    let mut inbound = loop {
+        match bus.subscribe(&node_id).await {
+            Ok(inbound) => break inbound,
+            Err(e) => {
+                error!("❌ Cluster bus unavailable, retrying: {}", e);
+                tokio::time::sleep(sync_interval).await;
+            }
+        }
+    };
+    if let Err(e) = directory.clear_node(&node_id).await {
+        warn!("⚠️ Failed to clear stale peers of node {}: {}", node_id, e);
+    }
+    // Announce the node before registering peers, or other nodes may expire them
+    if let Err(e) = directory.heartbeat(&node_id).await {
+        warn!("⚠️ Cluster heartbeat failed: {}", e);
+    }
+
+    let mut ticker = tokio::time::interval(sync_interval);
+    loop {
+        tokio::select! {
+            op = ops.recv() => {
+                let Some(op) = op else { break };
+                if let Err(e) = apply(&node_id, op, directory.as_ref(), bus.as_ref()).await {
+                    error!("❌ Cluster update failed: {}", e);
+                }
+            }
+            envelope = inbound.recv() => {
+                let Some(envelope) = envelope else {
+                    error!("❌ Cluster bus subscription ended");
+                    break;
+                };
+                if envelope.from == node_id {
+                    continue;
+                }
+                debug!("Cluster message from {}: {:?}", envelope.from, envelope.message);
+                let event = match envelope.message {
+                    ClusterMessage::PeerJoined { peer } => {
+                        cluster.remote.insert(peer.info.id.clone(), peer);
+                        ClusterEvent::PeersChanged
+                    }
+                    ClusterMessage::PeerLeft { peer_id } => {
+                        if cluster.remote.remove_if(&peer_id, |_, peer| peer.node_id == envelope.from).is_none() {
+                            continue;
+                        }
+                        ClusterEvent::PeerLeft(peer_id)
+                    }
+                    ClusterMessage::Deliver { to, message } => ClusterEvent::Deliver { to, message },
+                };
+                if events.send(event).is_err() {
+                    break;
+                }
+            }
+            _ = ticker.tick() => {
+                if let Err(e) = directory.heartbeat(&node_id).await {
+                    warn!("⚠️ Cluster heartbeat failed: {}", e);
+                }
+                match directory.list().await {
+                    Ok(peers) => {
+                        // Catches up on missed messages and drops peers of expired nodes
+                        let (gone, changed) = cluster.sync(peers);
+                        if changed && gone.is_empty() {
+                            let _ = events.send(ClusterEvent::PeersChanged);
+                        }
+                        for peer_id in gone {
+                            let _ = events.send(ClusterEvent::PeerLeft(peer_id));
+                        }
+                    }
+                    Err(e) => warn!("⚠️ Cluster directory sync failed: {}", e),
+                }
+            }
+        }
+    }
+}
+
+async fn apply(node_id: &str, op: Op, directory: &dyn PeerDirectory, bus: &dyn MessageBus) -> Result<()> {
+    let envelope = |message| Envelope { from: node_id.to_string(), message };
+
+    match op {
+        Op::Register(info) => {
+            let peer = RemotePeer { node_id: node_id.to_string(), info };
+            directory.register(&peer).await?;
+            bus.publish(None, &envelope(ClusterMessage::PeerJoined { peer })).await
+        }
+        Op::Unregister(peer_id) => {
+            directory.unregister(node_id, &peer_id).await?;
+            bus.publish(None, &envelope(ClusterMessage::PeerLeft { peer_id })).await
+        }
+        Op::Send { node_id: target, message } => bus.publish(Some(&target), &envelope(message)).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    fn info(id: &str) -> PeerInfo {
+        PeerInfo { id: id.to_string(), name: id.to_string(), user_agent: "test".to_string() }
+    }
+
+    fn peer(node_id: &str, id: &str) -> RemotePeer {
+        RemotePeer { node_id: node_id.to_string(), info: info(id) }
+    }
+
+    fn left(peer_id: &str) -> Envelope {
+        Envelope { from: "a".to_string(), message: ClusterMessage::PeerLeft { peer_id: peer_id.to_string() } }
+    }
+
+    async fn next(events: &mut mpsc::UnboundedReceiver<ClusterEvent>) -> ClusterEvent {
+        tokio::time::timeout(TIMEOUT, events.recv()).await.expect("no cluster event").unwrap()
+    }
+
+    #[tokio::test]
+    async fn directory_only_unregisters_from_the_owning_node() {
+        let directory = MemoryDirectory::default();
+        directory.register(&peer("a", "alice")).await.unwrap();
+        directory.register(&peer("b", "bob")).await.unwrap();
+        // Alice reconnected to node b before node a noticed she left
+        directory.register(&peer("b", "alice")).await.unwrap();
+        directory.unregister("a", "alice").await.unwrap();
+        assert_eq!(directory.list().await.unwrap().len(), 2);
+
+        directory.clear_node("b").await.unwrap();
+        assert!(directory.list().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn bus_routes_to_one_node_or_all() {
+        let bus = MemoryBus::default();
+        let mut a = bus.subscribe("a").await.unwrap();
+        let mut b = bus.subscribe("b").await.unwrap();
+
+        bus.publish(Some("b"), &left("direct")).await.unwrap();
+        bus.publish(None, &left("broadcast")).await.unwrap();
+        assert!(bus.publish(Some("c"), &left("lost")).await.is_err());
+
+        let received = |envelope: Envelope| match envelope.message {
+            ClusterMessage::PeerLeft { peer_id } => peer_id,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(received(a.try_recv().unwrap()), "broadcast");
+        assert!(a.try_recv().is_err());
+        assert_eq!(received(b.try_recv().unwrap()), "direct");
+        assert_eq!(received(b.try_recv().unwrap()), "broadcast");
+
+        // Broadcasts forget nodes that stopped listening
+        drop(a);
+        bus.publish(None, &left("broadcast")).await.unwrap();
+        assert!(bus.publish(Some("a"), &left("lost")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn nodes_see_each_others_peers_and_deliver_messages() {
+        let directory: Arc<dyn PeerDirectory> = Arc::new(MemoryDirectory::default());
+        let bus: Arc<dyn MessageBus> = Arc::new(MemoryBus::default());
+        // Syncs cover the bus messages sent before the other node subscribed
+        let interval = Duration::from_millis(50);
+        let (a, mut a_events) = Cluster::start("a", directory.clone(), bus.clone(), interval).unwrap();
+        let (b, mut b_events) = Cluster::start("b", directory.clone(), bus.clone(), interval).unwrap();
+
+        a.register(&info("alice"));
+        assert!(matches!(next(&mut b_events).await, ClusterEvent::PeersChanged));
+        assert_eq!(b.locate("alice").as_deref(), Some("a"));
+        assert_eq!(a.locate("alice"), None, "a node does not list its own peers");
+
+        b.deliver("a", "alice", Method::Keepalive);
+        match next(&mut a_events).await {
+            ClusterEvent::Deliver { to, message: Method::Keepalive } => assert_eq!(to, "alice"),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        a.unregister("alice");
+        assert!(matches!(next(&mut b_events).await, ClusterEvent::PeerLeft(id) if id == "alice"));
+        assert_eq!(b.locate("alice"), None);
+        assert!(directory.list().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn directory_sync_catches_up_on_missed_messages() {
+        let directory: Arc<dyn PeerDirectory> = Arc::new(MemoryDirectory::default());
+        let bus: Arc<dyn MessageBus> = Arc::new(MemoryBus::default());
+        let (b, mut b_events) = Cluster::start("b", directory.clone(), bus, Duration::from_millis(50)).unwrap();
+
+        // Registered without a message on the bus, e.g. by a node that crashed after
+        directory.register(&peer("a", "alice")).await.unwrap();
+        assert!(matches!(next(&mut b_events).await, ClusterEvent::PeersChanged));
+        assert_eq!(b.locate("alice").as_deref(), Some("a"));
+
+        directory.clear_node("a").await.unwrap();
+        assert!(matches!(next(&mut b_events).await, ClusterEvent::PeerLeft(id) if id == "alice"));
+        assert_eq!(b.remote_peers(), Vec::new());
+    }
+}
@@ -0,0 +1,753 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use dashmap::DashMap;
+use futures_util::stream::{SplitSink, SplitStream};
+use futures_util::{SinkExt, StreamExt};
+use hmac::{Hmac, Mac};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use crate::modules::cluster::{ClusterMessage, Envelope, MessageBus, PeerDirectory, RemotePeer};
+use crate::modules::signaling::PeerInfo;
+
+// Plenty for a peer list or signaling message, and bounds what a broken node can send
+const MAX_FRAME_SIZE: usize = 1 << 20;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Labels keeping proofs and frame keys of the two ends of a link apart
+const DIALER_PROOF: &str = "mesh dialer proof";
+const ACCEPTOR_PROOF: &str = "mesh acceptor proof";
+const DIALER_FRAMES: &str = "mesh dialer frames";
+const ACCEPTOR_FRAMES: &str = "mesh acceptor frames";
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "frame", rename_all = "snake_case")]
+enum Frame {
+    /// First frame from both ends of a link, the dialer's first
+    Hello { node_id: String, nonce: String },
+    /// Hex HMAC-SHA256 of both node ids and nonces under the sender's role,
+    /// sent by the acceptor only once the dialer's proof checked out
+    Auth { proof: String },
+    /// The sender's local peers, sent when the link opens and on every heartbeat
+    Presence { peers: Vec<PeerInfo> },
+    Message { envelope: Envelope },
+}
+
+/// A frame of an open link with its sequence number and a MAC under the
+/// sender's link key, so a relay of the link can't inject, alter or replay frames.
+#[derive(Debug, Serialize, Deserialize)]
+struct Sealed {
+    seq: u64,
+    frame: String,
+    mac: String,
+}
+
+/// Frame keys of one link, derived from the secret and the handshake.
+struct LinkKeys {
+    send_key: Vec<u8>,
+    receive_key: Vec<u8>,
+    sent: u64,
+    received: u64,
+}
+
+impl LinkKeys {
+    fn new(secret: &str, transcript: &[&str; 4], dialed: bool) -> Self {
+        let key = |label| mac(secret.as_bytes(), label, transcript).finalize().into_bytes().to_vec();
+        let (dialer, acceptor) = (key(DIALER_FRAMES), key(ACCEPTOR_FRAMES));
+        let (send_key, receive_key) = if dialed { (dialer, acceptor) } else { (acceptor, dialer) };
+        Self {
+            send_key,
+            receive_key,
+            sent: 0,
+            received: 0,
+        }
+    }
+
+    fn seal(&mut self, frame: &Frame) -> Result<String> {
+        self.sent += 1;
+        let frame = serde_json::to_string(frame)?;
+        let mac = hex::encode(mac(&self.send_key, "", &[&self.sent.to_string(), &frame]).finalize().into_bytes());
+        Ok(serde_json::to_string(&Sealed { seq: self.sent, frame, mac })?)
+    }
+
+    // Returns the frame text if it is the next frame from the other end
+    fn open(&mut self, text: &str) -> Result<String> {
+        let sealed: Sealed = serde_json::from_str(text)?;
+        if sealed.seq != self.received + 1 {
+            anyhow::bail!("frame {} out of sequence, expected {}", sealed.seq, self.received + 1);
+        }
+        let proof = hex::decode(&sealed.mac).unwrap_or_default();
+        mac(&self.receive_key, "", &[&sealed.seq.to_string(), &sealed.frame])
+            .verify_slice(&proof)
+            .map_err(|_| anyhow::anyhow!("frame {} failed authentication", sealed.seq))?;
+        self.received = sealed.seq;
+        Ok(sealed.frame)
+    }
+}
+
+struct Link {
+    id: u64,
+    /// Node that opened the connection
+    dialer: String,
+    frames: mpsc::UnboundedSender<Frame>,
+}
+
+struct RemoteNode {
+    peers: HashMap<String, PeerInfo>,
+    last_seen: Instant,
+}
+
+/// Peer directory and message bus over direct links between nodes, without a broker.
+///
+/// Each node accepts links on its listen address and dials the addresses of the
+/// other nodes, which must all be listed since messages are not relayed. Links
+/// are WebSockets authenticated by an HMAC challenge on a shared secret, with
+/// every later frame MACed under keys derived from the challenge. Nodes
+/// send their peers over every link on each heartbeat, and a node not heard
+/// from for `node_timeout` is dropped together with its peers.
+pub struct MeshBackend {
+    node_id: String,
+    secret: String,
+    node_timeout: Duration,
+    local: DashMap<String, PeerInfo>,
+    nodes: DashMap<String, RemoteNode>,
+    links: DashMap<String, Link>,
+    next_link: AtomicU64,
+    subscriber: Mutex<Option<mpsc::UnboundedSender<Envelope>>>,
+}
+
+impl MeshBackend {
+    /// Listens for links on `listen`, if set, and keeps dialing each of `peers`
+    /// (`host:port` or a `ws://` URL), retrying every `retry` while a link is
+    /// down. Must run inside a Tokio runtime.
+    pub fn start(
+        node_id: &str,
+        listen: Option<&str>,
+        peers: &[String],
+        secret: &str,
+        retry: Duration,
+        node_timeout: Duration,
+    ) -> Result<Arc<Self>> {
+        let runtime = tokio::runtime::Handle::try_current()
+            .map_err(|_| anyhow::anyhow!("Mesh links need a Tokio runtime"))?;
+        if secret.is_empty() {
+            anyhow::bail!("Mesh links need a shared secret");
+        }
+
+        let mesh = Arc::new(Self::new(node_id, secret, node_timeout));
+
+        if let Some(listen) = listen {
+            let listener = std::net::TcpListener::bind(listen)
+                .map_err(|e| anyhow::anyhow!("Failed to listen for mesh links on {}: {}", listen, e))?;
+            listener.set_nonblocking(true)?;
+            let listener = TcpListener::from_std(listener)?;
+            info!("🌐 Accepting mesh links on {}", listen);
+            runtime.spawn(mesh.clone().accept(listener));
+        }
+        for address in peers {
+            runtime.spawn(mesh.clone().dial(address.clone(), retry));
+        }
+        Ok(mesh)
+    }
+
+    fn new(node_id: &str, secret: &str, node_timeout: Duration) -> Self {
+        Self {
+            node_id: node_id.to_string(),
+            secret: secret.to_string(),
+            node_timeout,
+            local: DashMap::new(),
+            nodes: DashMap::new(),
+            links: DashMap::new(),
+            next_link: AtomicU64::new(0),
+            subscriber: Mutex::new(None),
+        }
+    }
+
+    async fn accept(self: Arc<Self>, listener: TcpListener) {
+        loop {
+            let (stream, addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    error!("❌ Failed to accept mesh link: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+
+            let mesh = self.clone();
+            tokio::spawn(async move {
+                let linked = match tokio_tungstenite::accept_async_with_config(stream, Some(ws_config())).await {
+                    Ok(ws) => mesh.run_link(ws, false).await,
+                    Err(e) => Err(e.into()),
+                };
+                if let Err(e) = linked {
+                    warn!("⚠️ Mesh link from {} failed: {}", addr, e);
+                }
+            });
+        }
+    }
+
+    async fn dial(self: Arc<Self>, address: String, retry: Duration) {
+        let url = if address.contains("://") {
+            address.clone()
+        } else {
+            format!("ws://{}/", address)
+        };
+        // Node at `address`, once known, so an existing link dialed by that node is not duplicated
+        let mut remote: Option<String> = None;
+
+        loop {
+            if !remote.as_ref().is_some_and(|node_id| self.links.contains_key(node_id)) {
+                match tokio_tungstenite::connect_async_with_config(url.as_str(), Some(ws_config()), true).await {
+                    Ok((ws, _)) => match self.clone().run_link(ws, true).await {
+                        Ok(node_id) => remote = Some(node_id),
+                        Err(e) => warn!("⚠️ Mesh link to {} failed: {}", address, e),
+                    },
+                    Err(e) => debug!("Mesh node {} unreachable: {}", address, e),
+                }
+            }
+            tokio::time::sleep(retry).await;
+        }
+    }
+
+    // Authenticates a connection and serves it until it closes. Returns the
+    // remote node id, also when the link was dropped as a duplicate.
+    async fn run_link<S>(self: Arc<Self>, ws: WebSocketStream<S>, dialed: bool) -> Result<String>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let (mut sink, mut stream) = ws.split();
+        let (remote, mut keys) = tokio::time::timeout(HANDSHAKE_TIMEOUT, self.handshake(&mut sink, &mut stream, dialed))
+            .await
+            .map_err(|_| anyhow::anyhow!("handshake timed out"))??;
+
+        let id = self.next_link.fetch_add(1, Ordering::SeqCst);
+        let (frames, mut outbound) = mpsc::unbounded_channel();
+        let link = Link {
+            id,
+            dialer: if dialed { self.node_id.clone() } else { remote.clone() },
+            frames: frames.clone(),
+        };
+        if !self.add_link(&remote, link) {
+            debug!("Closing duplicate mesh link with node {}", remote);
+            let _ = sink.close().await;
+            return Ok(remote);
+        }
+        info!("🌐 Mesh link with node {} up", remote);
+        self.touch(&remote);
+        let _ = frames.send(Frame::Presence { peers: self.local_peers() });
+        drop(frames);
+
+        let result = loop {
+            tokio::select! {
+                frame = outbound.recv() => {
+                    // No sender left once a newer link to the node replaced this one
+                    let Some(frame) = frame else { break Ok(true) };
+                    if let Err(e) = send_sealed(&mut sink, &mut keys, &frame).await {
+                        break Err(e);
+                    }
+                }
+                message = stream.next() => match message {
+                    Some(Ok(Message::Text(text))) => match keys.open(&text) {
+                        Ok(text) => match serde_json::from_str::<Frame>(&text) {
+                            Ok(frame) => self.handle_frame(&remote, frame),
+                            Err(e) => warn!("⚠️ Ignoring unreadable mesh frame from {}: {}", remote, e),
+                        },
+                        Err(e) => break Err(e),
+                    },
+                    Some(Ok(Message::Close(_))) | None => break Ok(false),
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => break Err(e.into()),
+                }
+            }
+        };
+
+        if let Ok(true) = result {
+            debug!("Closing replaced mesh link with node {}", remote);
+            let _ = sink.close().await;
+            return Ok(remote);
+        }
+        self.links.remove_if(&remote, |_, link| link.id == id);
+        warn!("⚠️ Mesh link with node {} down", remote);
+        result.map(|_| remote)
+    }
+
+    // The dialer proves itself first, so the acceptor never signs anything for
+    // an unauthenticated node and can't be used as a signing oracle.
+    async fn handshake<S>(
+        &self,
+        sink: &mut SplitSink<WebSocketStream<S>, Message>,
+        stream: &mut SplitStream<WebSocketStream<S>>,
+        dialed: bool,
+    ) -> Result<(String, LinkKeys)>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let nonce = uuid::Uuid::new_v4().to_string();
+        let hello = Frame::Hello { node_id: self.node_id.clone(), nonce: nonce.clone() };
+        if dialed {
+            send(sink, &hello).await?;
+        }
+        let Frame::Hello { node_id: remote, nonce: remote_nonce } = receive(stream).await? else {
+            anyhow::bail!("expected hello");
+        };
+        if remote == self.node_id {
+            anyhow::bail!("remote node uses this node's id {}", remote);
+        }
+        if !dialed {
+            send(sink, &hello).await?;
+        }
+
+        let transcript = if dialed {
+            [self.node_id.as_str(), nonce.as_str(), remote.as_str(), remote_nonce.as_str()]
+        } else {
+            [remote.as_str(), remote_nonce.as_str(), self.node_id.as_str(), nonce.as_str()]
+        };
+        let (own_label, remote_label) = if dialed { (DIALER_PROOF, ACCEPTOR_PROOF) } else { (ACCEPTOR_PROOF, DIALER_PROOF) };
+        let proof = Frame::Auth {
+            proof: hex::encode(mac(self.secret.as_bytes(), own_label, &transcript).finalize().into_bytes()),
+        };
+
+        if dialed {
+            send(sink, &proof).await?;
+        }
+        let Frame::Auth { proof: remote_proof } = receive(stream).await? else {
+            anyhow::bail!("expected auth");
+        };
+        let remote_proof = hex::decode(remote_proof).unwrap_or_default();
+        if mac(self.secret.as_bytes(), remote_label, &transcript).verify_slice(&remote_proof).is_err() {
+            anyhow::bail!("node {} failed authentication", remote);
+        }
+        if !dialed {
+            send(sink, &proof).await?;
+        }
+
+        let keys = LinkKeys::new(&self.secret, &transcript, dialed);
+        Ok((remote, keys))
+    }
+
+    // Keeps one link per node. When both ends dial at once, both keep the link
+    // dialed by the node with the smaller id; otherwise the newer link wins.
+    fn add_link(&self, remote: &str, link: Link) -> bool {
+        let preferred = self.node_id.as_str().min(remote);
+        match self.links.entry(remote.to_string()) {
+            dashmap::mapref::entry::Entry::Occupied(mut existing) => {
+                if existing.get().dialer == preferred && link.dialer != preferred {
+                    return false;
+                }
+                existing.insert(link);
+            }
+            dashmap::mapref::entry::Entry::Vacant(vacant) => {
+                vacant.insert(link);
+            }
+        }
+        true
+    }
+
+    fn touch(&self, node_id: &str) {
+        self.nodes
+            .entry(node_id.to_string())
+            .or_insert_with(|| RemoteNode {
+                peers: HashMap::new(),
+                last_seen: Instant::now(),
+            })
+            .last_seen = Instant::now();
+    }
+
+    fn local_peers(&self) -> Vec<PeerInfo> {
+        self.local.iter().map(|peer| peer.value().clone()).collect()
+    }
+
+    fn handle_frame(&self, remote: &str, frame: Frame) {
+        self.touch(remote);
+        match frame {
+            Frame::Presence { peers } => {
+                if let Some(mut node) = self.nodes.get_mut(remote) {
+                    node.peers = peers.into_iter().map(|peer| (peer.id.clone(), peer)).collect();
+                }
+            }
+            Frame::Message { envelope } => {
+                if envelope.from != remote {
+                    warn!("⚠️ Ignoring mesh message from {} claiming to be from {}", remote, envelope.from);
+                    return;
+                }
+                if let Some(mut node) = self.nodes.get_mut(remote) {
+                    match &envelope.message {
+                        ClusterMessage::PeerJoined { peer } => {
+                            node.peers.insert(peer.info.id.clone(), peer.info.clone());
+                        }
+                        ClusterMessage::PeerLeft { peer_id } => {
+                            node.peers.remove(peer_id);
+                        }
+                        ClusterMessage::Deliver { .. } => {}
+                    }
+                }
+                let subscriber = self.subscriber.lock().unwrap_or_else(|e| e.into_inner());
+                if let Some(subscriber) = subscriber.as_ref() {
+                    let _ = subscriber.send(envelope);
+                }
+            }
+            Frame::Hello { .. } | Frame::Auth { .. } => {
+                warn!("⚠️ Ignoring handshake frame from {} on an open link", remote);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl PeerDirectory for MeshBackend {
+    async fn register(&self, peer: &RemotePeer) -> Result<()> {
+        if peer.node_id == self.node_id {
+            self.local.insert(peer.info.id.clone(), peer.info.clone());
+        }
+        Ok(())
+    }
+
+    async fn unregister(&self, node_id: &str, peer_id: &str) -> Result<()> {
+        if node_id == self.node_id {
+            self.local.remove(peer_id);
+        }
+        Ok(())
+    }
+
+    async fn clear_node(&self, node_id: &str) -> Result<()> {
+        if node_id == self.node_id {
+            self.local.clear();
+        } else {
+            self.nodes.remove(node_id);
+        }
+        Ok(())
+    }
+
+    async fn heartbeat(&self, _node_id: &str) -> Result<()> {
+        let peers = self.local_peers();
+        for link in self.links.iter() {
+            let _ = link.frames.send(Frame::Presence { peers: peers.clone() });
+        }
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<RemotePeer>> {
+        self.nodes.retain(|node_id, node| {
+            let alive = node.last_seen.elapsed() < self.node_timeout;
+            if !alive {
+                info!("🌐 Mesh node {} timed out, dropping its {} peers", node_id, node.peers.len());
+            }
+            alive
+        });
+
+        let local = self.local.iter().map(|peer| RemotePeer {
+            node_id: self.node_id.clone(),
+            info: peer.value().clone(),
+        });
+        let mut peers: Vec<RemotePeer> = local.collect();
+        for node in self.nodes.iter() {
+            peers.extend(node.peers.values().map(|info| RemotePeer {
+                node_id: node.key().clone(),
+                info: info.clone(),
+            }));
+        }
+        Ok(peers)
+    }
+}
+
+#[async_trait]
+impl MessageBus for MeshBackend {
+    async fn publish(&self, node_id: Option<&str>, envelope: &Envelope) -> Result<()> {
+        let frame = || Frame::Message { envelope: envelope.clone() };
+        match node_id {
+            Some(node_id) => {
+                let link = self
+                    .links
+                    .get(node_id)
+                    .ok_or_else(|| anyhow::anyhow!("No mesh link to node {}", node_id))?;
+                link.frames
+                    .send(frame())
+                    .map_err(|_| anyhow::anyhow!("Mesh link to node {} closed", node_id))?;
+            }
+            None => {
+                for link in self.links.iter() {
+                    let _ = link.frames.send(frame());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn subscribe(&self, _node_id: &str) -> Result<mpsc::UnboundedReceiver<Envelope>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        *self.subscriber.lock().unwrap_or_else(|e| e.into_inner()) = Some(tx);
+        Ok(rx)
+    }
+}
+
+fn ws_config() -> WebSocketConfig {
+    WebSocketConfig {
+        max_message_size: Some(MAX_FRAME_SIZE),
+        max_frame_size: Some(MAX_FRAME_SIZE),
+        ..Default::default()
+    }
+}
+
+// MAC over `label` and length-prefixed `parts`, so no two inputs share an encoding
+fn mac(key: &[u8], label: &str, parts: &[&str]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(label.as_bytes());
+    for part in parts {
+        mac.update(&(part.len() as u64).to_be_bytes());
+        mac.update(part.as_bytes());
+    }
+    mac
+}
+
+async fn send<S>(sink: &mut SplitSink<WebSocketStream<S>, Message>, frame: &Frame) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    sink.send(Message::Text(serde_json::to_string(frame)?)).await?;
+    Ok(())
+}
+
+async fn send_sealed<S>(sink: &mut SplitSink<WebSocketStream<S>, Message>, keys: &mut LinkKeys, frame: &Frame) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    sink.send(Message::Text(keys.seal(frame)?)).await?;
+    Ok(())
+}
+
+async fn receive<S>(stream: &mut SplitStream<WebSocketStream<S>>) -> Result<Frame>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    loop {
+        match stream.next().await {
+            Some(Ok(Message::Text(text))) => return Ok(serde_json::from_str(&text)?),
+            Some(Ok(Message::Close(_))) | None => anyhow::bail!("connection closed"),
+            Some(Ok(_)) => {}
+            Some(Err(e)) => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::config::Config;
+    use crate::modules::signaling::{CallStatus, Connection, ConnectionEvents, Method, Signaler};
+    use serde_json::json;
+    use tokio::io::DuplexStream;
+    use tokio_tungstenite::tungstenite::protocol::Role;
+
+    type Ws = WebSocketStream<DuplexStream>;
+
+    // A dialer and an acceptor end of one in-memory WebSocket
+    async fn link() -> (Ws, Ws) {
+        let (dialer, acceptor) = tokio::io::duplex(64 * 1024);
+        (
+            WebSocketStream::from_raw_socket(dialer, Role::Client, None).await,
+            WebSocketStream::from_raw_socket(acceptor, Role::Server, None).await,
+        )
+    }
+
+    fn node(node_id: &str, secret: &str) -> MeshBackend {
+        MeshBackend::new(node_id, secret, Duration::from_secs(15))
+    }
+
+    async fn handshake(node: &MeshBackend, ws: Ws, dialed: bool) -> Result<(String, LinkKeys)> {
+        let (mut sink, mut stream) = ws.split();
+        let result = node.handshake(&mut sink, &mut stream, dialed).await;
+        // Fails the other end rather than leaving it waiting
+        let _ = sink.close().await;
+        result
+    }
+
+    #[tokio::test]
+    async fn handshake_authenticates_both_nodes() {
+        let (a, b) = (node("a", "secret"), node("b", "secret"));
+        let (dialer, acceptor) = link().await;
+        let (dialed, accepted) = tokio::join!(handshake(&a, dialer, true), handshake(&b, acceptor, false));
+        let (remote_of_a, mut keys_of_a) = dialed.unwrap();
+        let (remote_of_b, mut keys_of_b) = accepted.unwrap();
+        assert_eq!(remote_of_a, "b");
+        assert_eq!(remote_of_b, "a");
+
+        let frame = keys_of_a.seal(&Frame::Presence { peers: Vec::new() }).unwrap();
+        assert!(keys_of_b.open(&frame).is_ok());
+        // Replayed frames and frames reflected back to their sender are rejected
+        assert!(keys_of_b.open(&frame).is_err());
+        let reflected = keys_of_b.seal(&Frame::Presence { peers: Vec::new() }).unwrap();
+        assert!(keys_of_b.open(&reflected).is_err());
+    }
+
+    #[tokio::test]
+    async fn handshake_rejects_wrong_secret() {
+        let (a, b) = (node("a", "secret"), node("b", "other secret"));
+        let (dialer, acceptor) = link().await;
+        let (dialed, accepted) = tokio::join!(handshake(&a, dialer, true), handshake(&b, acceptor, false));
+        assert!(dialed.is_err());
+        assert!(accepted.is_err());
+    }
+
+    #[tokio::test]
+    async fn tampered_frames_are_rejected() {
+        let (a, b) = (node("a", "secret"), node("b", "secret"));
+        let (dialer, acceptor) = link().await;
+        let (dialed, accepted) = tokio::join!(handshake(&a, dialer, true), handshake(&b, acceptor, false));
+        let (mut keys_of_a, mut keys_of_b) = (dialed.unwrap().1, accepted.unwrap().1);
+
+        let frame = keys_of_a.seal(&Frame::Presence { peers: Vec::new() }).unwrap();
+        let mut sealed: Sealed = serde_json::from_str(&frame).unwrap();
+        sealed.frame = serde_json::to_string(&Frame::Presence {
+            peers: vec![PeerInfo {
+                id: "mallory".to_string(),
+                name: "Mallory".to_string(),
+                user_agent: String::new(),
+            }],
+        })
+        .unwrap();
+        assert!(keys_of_b.open(&serde_json::to_string(&sealed).unwrap()).is_err());
+    }
+
+    #[tokio::test]
+    async fn acceptor_proves_itself_only_to_authenticated_dialers() {
+        let a = node("a", "secret");
+        let (attacker, acceptor) = link().await;
+        let attack = async {
+            let (mut sink, mut stream) = attacker.split();
+            send(&mut sink, &Frame::Hello { node_id: "b".to_string(), nonce: "n".to_string() }).await?;
+            let Frame::Hello { .. } = receive(&mut stream).await? else {
+                anyhow::bail!("expected hello");
+            };
+            send(&mut sink, &Frame::Auth { proof: "00".repeat(32) }).await?;
+            receive(&mut stream).await
+        };
+        let (attacked, accepted) = tokio::join!(attack, handshake(&a, acceptor, false));
+        assert!(accepted.is_err());
+        assert!(attacked.is_err(), "acceptor answered an unauthenticated dialer: {:?}", attacked);
+    }
+
+    #[tokio::test]
+    async fn relayed_proof_is_rejected() {
+        let (a, b) = (node("a", "secret"), node("b", "secret"));
+
+        // B dials the attacker posing as A and hands over a genuine proof
+        let (dialer, attacker) = link().await;
+        let collect = async {
+            let (mut sink, mut stream) = attacker.split();
+            let Frame::Hello { nonce, .. } = receive(&mut stream).await? else {
+                anyhow::bail!("expected hello");
+            };
+            send(&mut sink, &Frame::Hello { node_id: "a".to_string(), nonce: "attacker".to_string() }).await?;
+            let Frame::Auth { proof } = receive(&mut stream).await? else {
+                anyhow::bail!("expected auth");
+            };
+            Ok((nonce, proof))
+        };
+        let (collected, dialed) = tokio::join!(collect, handshake(&b, dialer, true));
+        assert!(dialed.is_err());
+        let (nonce_of_b, proof_of_b) = collected.unwrap();
+
+        // The attacker replays B's hello and proof to A, which picks a fresh nonce
+        let (attacker, acceptor) = link().await;
+        let replay = async {
+            let (mut sink, mut stream) = attacker.split();
+            send(&mut sink, &Frame::Hello { node_id: "b".to_string(), nonce: nonce_of_b }).await?;
+            receive(&mut stream).await?;
+            send(&mut sink, &Frame::Auth { proof: proof_of_b }).await?;
+            receive(&mut stream).await
+        };
+        let (replayed, accepted) = tokio::join!(replay, handshake(&a, acceptor, false));
+        assert!(accepted.is_err());
+        assert!(replayed.is_err());
+    }
+
+    const RETRY: Duration = Duration::from_millis(50);
+    const NODE_TIMEOUT: Duration = Duration::from_millis(500);
+
+    // A Signaler joined to the mesh as `node_id`, started on the current runtime
+    fn signaler(node_id: &str, listen: Option<&str>, peers: &[String]) -> Arc<Signaler> {
+        let mesh = MeshBackend::start(node_id, listen, peers, "secret", RETRY, NODE_TIMEOUT).unwrap();
+        let config = Config::builder().set("cluster", "heartbeat_ms", "50").build().unwrap();
+        Signaler::builder().config(config).cluster(node_id, mesh.clone(), mesh).build().unwrap()
+    }
+
+    async fn client(signaler: &Signaler, id: &str) -> (Connection, ConnectionEvents) {
+        let (connection, events) = signaler.connect(None);
+        signal(signaler, &connection, json!({"type": "new", "data": {"id": id, "name": id, "user_agent": "test"}})).await;
+        (connection, events)
+    }
+
+    async fn signal(signaler: &Signaler, connection: &Connection, message: serde_json::Value) {
+        signaler.receive(connection, serde_json::from_value(message).unwrap()).await.unwrap();
+    }
+
+    // Skips messages until one matches, e.g. the peer list catching up
+    async fn expect(events: &mut ConnectionEvents, matches: impl Fn(&Method) -> bool) -> Method {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let message = events.messages.recv().await.expect("connection closed");
+                if matches(&message) {
+                    return message;
+                }
+            }
+        })
+        .await
+        .expect("no matching message")
+    }
+
+    fn lists(message: &Method, id: &str) -> bool {
+        matches!(message, Method::Peers(peers) if peers.iter().any(|peer| peer.id == id))
+    }
+
+    #[tokio::test]
+    async fn signalers_call_each_other_over_mesh_links() {
+        let listen = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+
+        // Node a gets its own runtime, so shutting that down fails the node
+        let runtime_a = tokio::runtime::Builder::new_multi_thread().worker_threads(1).enable_all().build().unwrap();
+        let a = {
+            let _runtime = runtime_a.enter();
+            signaler("a", Some(&listen), &[])
+        };
+        let (alice, mut alice_events) = client(&a, "alice").await;
+
+        // Alice registered before the link was up, only presence tells b about her
+        let b = signaler("b", None, &[listen]);
+        let (bob, mut bob_events) = client(&b, "bob").await;
+        expect(&mut bob_events, |message| lists(message, "alice")).await;
+        expect(&mut alice_events, |message| lists(message, "bob")).await;
+
+        let offer = json!({"from": "alice", "to": "bob", "session_id": "alice-bob", "description": {"type": "offer", "sdp": "v=0\r\n"}});
+        signal(&a, &alice, json!({"type": "offer", "data": offer})).await;
+        expect(&mut bob_events, |message| matches!(message, Method::Offer(offer) if offer.from == "alice")).await;
+
+        let answer = json!({"from": "bob", "to": "alice", "session_id": "alice-bob", "description": {"type": "answer", "sdp": "v=0\r\n"}});
+        signal(&b, &bob, json!({"type": "answer", "data": answer})).await;
+        expect(&mut alice_events, |message| matches!(message, Method::Answer(answer) if answer.from == "bob")).await;
+        // The session lives on the caller's node
+        let session = a.store.session("alice-bob").await.unwrap().unwrap();
+        assert_eq!(session.status, CallStatus::Connected);
+
+        signal(&b, &bob, json!({"type": "bye", "data": {"session_id": "alice-bob", "from": "bob"}})).await;
+        expect(&mut alice_events, |message| matches!(message, Method::Bye(bye) if bye.from == "bob")).await;
+        let session = a.store.session("alice-bob").await.unwrap().unwrap();
+        assert_eq!(session.status, CallStatus::Ended);
+
+        // Node a dies without unregistering alice, b drops her once a times out
+        runtime_a.shutdown_background();
+        expect(&mut bob_events, |message| matches!(message, Method::Peers(peers) if peers.iter().all(|peer| peer.id != "alice"))).await;
+        assert_eq!(b.cluster.as_ref().unwrap().locate("alice"), None);
+    }
+}
@@ -0,0 +1,303 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use log::{error, info, warn};
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+
+use crate::modules::cluster::{Envelope, MessageBus, PeerDirectory, RemotePeer};
+
+/// Peer directory and message bus on Redis.
+///
+/// Peers are kept in the hash `<prefix>:peers` (peer id to JSON), and each node
+/// refreshes `<prefix>:node:<id>` with a TTL of `node_timeout` on every
+/// heartbeat; peers of nodes whose key expired are dropped. Broadcasts go to the
+/// pub/sub channel `<prefix>:bus`, messages for one node to `<prefix>:bus:<id>`.
+pub struct RedisBackend {
+    client: redis::Client,
+    prefix: String,
+    node_timeout: Duration,
+    // Connected on first use and dropped on error, so commands reconnect
+    connection: Mutex<Option<MultiplexedConnection>>,
+}
+
+impl RedisBackend {
+    pub fn new(url: &str, prefix: &str, node_timeout: Duration) -> Result<Self> {
+        Ok(Self {
+            client: redis::Client::open(url)
+                .map_err(|e| anyhow::anyhow!("Invalid Redis URL {}: {}", url, e))?,
+            prefix: prefix.to_string(),
+            node_timeout,
+            connection: Mutex::new(None),
+        })
+    }
+
+    fn peers_key(&self) -> String {
+        format!("{}:peers", self.prefix)
+    }
+
+    fn node_key(&self, node_id: &str) -> String {
+        format!("{}:node:{}", self.prefix, node_id)
+    }
+
+    fn channel(&self, node_id: Option<&str>) -> String {
+        match node_id {
+            Some(node_id) => format!("{}:bus:{}", self.prefix, node_id),
+            None => format!("{}:bus", self.prefix),
+        }
+    }
+
+    async fn connection(&self) -> Result<MultiplexedConnection> {
+        let mut connection = self.connection.lock().await;
+        if let Some(connection) = connection.as_ref() {
+            return Ok(connection.clone());
+        }
+        let connected = self.client.get_multiplexed_tokio_connection().await?;
+        *connection = Some(connected.clone());
+        Ok(connected)
+    }
+
+    // Runs `command` and forgets the connection if it failed
+    async fn run<T, F, Fut>(&self, command: F) -> Result<T>
+    where
+        F: FnOnce(MultiplexedConnection) -> Fut,
+        Fut: std::future::Future<Output = redis::RedisResult<T>>,
+    {
+        let result = command(self.connection().await?).await;
+        if result.is_err() {
+            *self.connection.lock().await = None;
+        }
+        Ok(result?)
+    }
+
+    async fn all_peers(&self) -> Result<HashMap<String, RemotePeer>> {
+        let key = self.peers_key();
+        let raw: HashMap<String, String> = self.run(|mut c| async move { c.hgetall(key).await }).await?;
+        Ok(raw
+            .into_iter()
+            .filter_map(|(id, json)| match serde_json::from_str(&json) {
+                Ok(peer) => Some((id, peer)),
+                Err(e) => {
+                    warn!("⚠️ Ignoring unreadable cluster peer {}: {}", id, e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    async fn remove(&self, peer_ids: Vec<String>) -> Result<()> {
+        if peer_ids.is_empty() {
+            return Ok(());
+        }
+        let key = self.peers_key();
+        self.run(|mut c| async move { c.hdel::<_, _, ()>(key, peer_ids).await }).await
+    }
+}
+
+#[async_trait]
+impl PeerDirectory for RedisBackend {
+    async fn register(&self, peer: &RemotePeer) -> Result<()> {
+        let key = self.peers_key();
+        let json = serde_json::to_string(peer)?;
+        let id = peer.info.id.clone();
+        self.run(|mut c| async move { c.hset::<_, _, _, ()>(key, id, json).await }).await
+    }
+
+    async fn unregister(&self, node_id: &str, peer_id: &str) -> Result<()> {
+        let key = self.peers_key();
+        let id = peer_id.to_string();
+        let json: Option<String> = self.run(|mut c| async move { c.hget(key, id).await }).await?;
+        let owner = json.and_then(|json| serde_json::from_str::<RemotePeer>(&json).ok());
+        // A reconnect to another node may have re-registered the peer meanwhile
+        if owner.is_some_and(|peer| peer.node_id == node_id) {
+            self.remove(vec![peer_id.to_string()]).await?;
+        }
+        Ok(())
+    }
+
+    async fn clear_node(&self, node_id: &str) -> Result<()> {
+        let stale = self
+            .all_peers()
+            .await?
+            .into_iter()
+            .filter(|(_, peer)| peer.node_id == node_id)
+            .map(|(id, _)| id)
+            .collect();
+        self.remove(stale).await
+    }
+
+    async fn heartbeat(&self, node_id: &str) -> Result<()> {
+        let key = self.node_key(node_id);
+        let ttl = self.node_timeout.as_millis() as u64;
+        self.run(|mut c| async move { c.pset_ex::<_, _, ()>(key, 1, ttl).await }).await
+    }
+
+    async fn list(&self) -> Result<Vec<RemotePeer>> {
+        let peers = self.all_peers().await?;
+
+        let nodes: Vec<String> = peers
+            .values()
+            .map(|peer| peer.node_id.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let mut alive = HashSet::new();
+        for node_id in nodes {
+            let key = self.node_key(&node_id);
+            if self.run(|mut c| async move { c.exists::<_, bool>(key).await }).await? {
+                alive.insert(node_id);
+            }
+        }
+
+        let (live, expired): (Vec<_>, Vec<_>) =
+            peers.into_iter().partition(|(_, peer)| alive.contains(&peer.node_id));
+        if !expired.is_empty() {
+            info!("Removing {} peers of expired cluster nodes", expired.len());
+            self.remove(expired.into_iter().map(|(id, _)| id).collect()).await?;
+        }
+        Ok(live.into_iter().map(|(_, peer)| peer).collect())
+    }
+}
+
+#[async_trait]
+impl MessageBus for RedisBackend {
+    async fn publish(&self, node_id: Option<&str>, envelope: &Envelope) -> Result<()> {
+        let channel = self.channel(node_id);
+        let json = serde_json::to_string(envelope)?;
+        self.run(|mut c| async move { c.publish::<_, _, ()>(channel, json).await }).await
+    }
+
+    async fn subscribe(&self, node_id: &str) -> Result<mpsc::UnboundedReceiver<Envelope>> {
+        let channels = vec![self.channel(None), self.channel(Some(node_id))];
+        let client = self.client.clone();
+        // Fail early if Redis is unreachable, then keep resubscribing in the background
+        let mut pubsub = client.get_async_pubsub().await?;
+        pubsub.subscribe(&channels).await?;
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                let mut messages = pubsub.into_on_message();
+                while let Some(message) = messages.next().await {
+                    let envelope = message
+                        .get_payload::<String>()
+                        .map_err(anyhow::Error::from)
+                        .and_then(|json| Ok(serde_json::from_str::<Envelope>(&json)?));
+                    match envelope {
+                        Ok(envelope) => {
+                            if tx.send(envelope).is_err() {
+                                return;
+                            }
+                        }
+                        Err(e) => warn!("⚠️ Ignoring unreadable cluster message: {}", e),
+                    }
+                }
+                drop(messages);
+
+                error!("❌ Lost the Redis subscription, reconnecting");
+                pubsub = loop {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    if tx.is_closed() {
+                        return;
+                    }
+                    match client.get_async_pubsub().await {
+                        Ok(mut pubsub) => match pubsub.subscribe(&channels).await {
+                            Ok(()) => break pubsub,
+                            Err(e) => warn!("⚠️ Redis subscribe failed: {}", e),
+                        },
+                        Err(e) => warn!("⚠️ Redis reconnect failed: {}", e),
+                    }
+                };
+            }
+        });
+        Ok(rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::cluster::ClusterMessage;
+    use crate::modules::signaling::PeerInfo;
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    // Keys under a fresh prefix, so runs don't see each other's peers
+    fn backend(node_timeout: Duration) -> RedisBackend {
+        let url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_string());
+        let prefix = format!("test-{}", uuid::Uuid::new_v4().simple());
+        RedisBackend::new(&url, &prefix, node_timeout).unwrap()
+    }
+
+    fn peer(node_id: &str, id: &str) -> RemotePeer {
+        RemotePeer {
+            node_id: node_id.to_string(),
+            info: PeerInfo { id: id.to_string(), name: id.to_string(), user_agent: "test".to_string() },
+        }
+    }
+
+    fn ids(mut peers: Vec<RemotePeer>) -> Vec<String> {
+        peers.sort_by(|a, b| a.info.id.cmp(&b.info.id));
+        peers.into_iter().map(|peer| format!("{}@{}", peer.info.id, peer.node_id)).collect()
+    }
+
+    async fn next(messages: &mut mpsc::UnboundedReceiver<Envelope>) -> String {
+        let envelope = tokio::time::timeout(TIMEOUT, messages.recv()).await.expect("no message").unwrap();
+        match envelope.message {
+            ClusterMessage::PeerLeft { peer_id } => peer_id,
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Redis server at $REDIS_URL or redis://127.0.0.1/"]
+    async fn directory_tracks_owners_and_expires_nodes() {
+        let redis = backend(Duration::from_millis(300));
+        redis.heartbeat("a").await.unwrap();
+        redis.heartbeat("b").await.unwrap();
+        redis.register(&peer("a", "alice")).await.unwrap();
+        redis.register(&peer("b", "bob")).await.unwrap();
+        assert_eq!(ids(redis.list().await.unwrap()), ["alice@a", "bob@b"]);
+
+        // Alice moved to b before a unregistered her
+        redis.register(&peer("b", "alice")).await.unwrap();
+        redis.unregister("a", "alice").await.unwrap();
+        assert_eq!(ids(redis.list().await.unwrap()), ["alice@b", "bob@b"]);
+        redis.unregister("b", "alice").await.unwrap();
+        assert_eq!(ids(redis.list().await.unwrap()), ["bob@b"]);
+
+        // Node b stops heartbeating and its key expires with its peers
+        redis.heartbeat("a").await.unwrap();
+        redis.register(&peer("a", "carol")).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        redis.heartbeat("a").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(ids(redis.list().await.unwrap()), ["carol@a"]);
+
+        redis.clear_node("a").await.unwrap();
+        assert!(redis.list().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Redis server at $REDIS_URL or redis://127.0.0.1/"]
+    async fn bus_delivers_to_one_node_or_all() {
+        let redis = backend(Duration::from_secs(15));
+        let mut a = redis.subscribe("a").await.unwrap();
+        let mut b = redis.subscribe("b").await.unwrap();
+
+        let left = |peer_id: &str| Envelope {
+            from: "c".to_string(),
+            message: ClusterMessage::PeerLeft { peer_id: peer_id.to_string() },
+        };
+        redis.publish(Some("b"), &left("direct")).await.unwrap();
+        redis.publish(None, &left("broadcast")).await.unwrap();
+
+        assert_eq!(next(&mut a).await, "broadcast");
+        assert_eq!(next(&mut b).await, "direct");
+        assert_eq!(next(&mut b).await, "broadcast");
+        assert!(a.try_recv().is_err(), "node a received a message for b");
+    }
+}
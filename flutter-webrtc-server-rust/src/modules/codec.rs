@@ -0,0 +1,109 @@
+use anyhow::Result;
+use axum::extract::ws::Message;
+use serde::Deserialize;
+
+use crate::modules::signaling::Method;
+use crate::modules::validation::ValidationError;
+
+/// WebSocket subprotocol for the default JSON text framing.
+pub const JSON_SUBPROTOCOL: &str = "flutter-webrtc.json";
+
+/// WebSocket subprotocol for MessagePack binary framing.
+pub const MSGPACK_SUBPROTOCOL: &str = "flutter-webrtc.msgpack";
+
+/// Subprotocols offered during the upgrade, in order of preference.
+pub const SUBPROTOCOLS: [&str; 2] = [MSGPACK_SUBPROTOCOL, JSON_SUBPROTOCOL];
+
+/// Wire encoding used for frames sent to a single connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    #[default]
+    Json,
+    MessagePack,
+}
+
+impl Codec {
+    pub fn from_subprotocol(protocol: Option<&str>) -> Self {
+        match protocol {
+            Some(MSGPACK_SUBPROTOCOL) => Codec::MessagePack,
+            _ => Codec::Json,
+        }
+    }
+
+    pub fn encode(&self, message: &Method) -> Result<Message> {
+        match self {
+            Codec::Json => Ok(Message::Text(serde_json::to_string(message)?)),
+            // Named encoding keeps the `type`/`data` map layout of the JSON protocol
+            Codec::MessagePack => Ok(Message::Binary(rmp_serde::to_vec_named(message)?)),
+        }
+    }
+}
+
+/// Decodes a text frame. Text frames are always JSON, whatever the negotiated codec.
+pub fn decode_text(text: &str) -> Result<Method, ValidationError> {
+    serde_json::from_str(text).map_err(|e| ValidationError::Malformed {
+        request: request_type(serde_json::from_str(text).ok()),
+        detail: e.to_string(),
+    })
+}
+
+/// Decodes a binary frame. Binary frames are always MessagePack.
+pub fn decode_binary(data: &[u8]) -> Result<Method, ValidationError> {
+    rmp_serde::from_slice(data).map_err(|e| ValidationError::Malformed {
+        request: request_type(rmp_serde::from_slice(data).ok()),
+        detail: e.to_string(),
+    })
+}
+
+/// Just the `type` of a message, decoded again after a failed decode so that
+/// schema errors can name the message type.
+#[derive(Deserialize)]
+struct Envelope {
+    #[serde(rename = "type")]
+    request: String,
+}
+
+fn request_type(envelope: Option<Envelope>) -> String {
+    envelope.map_or_else(|| "unknown".to_string(), |envelope| envelope.request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::protocol::{Feature, Hello};
+
+    #[test]
+    fn messagepack_round_trips() {
+        let hello = Method::Hello(Hello { version: 2, features: vec![Feature::Binary] });
+        let Message::Binary(data) = Codec::MessagePack.encode(&hello).unwrap() else {
+            panic!("MessagePack must encode to binary frames");
+        };
+        match decode_binary(&data).unwrap() {
+            Method::Hello(hello) => assert_eq!(hello.features, [Feature::Binary]),
+            other => panic!("decoded {:?}", other),
+        }
+    }
+
+    #[test]
+    fn schema_errors_name_the_message_type() {
+        let error = decode_text(r#"{"type": "offer", "data": {"to": 1}}"#).unwrap_err();
+        assert_eq!(error.request(), "offer");
+
+        let data = rmp_serde::to_vec_named(&serde_json::json!({"type": "answer", "data": 5})).unwrap();
+        assert_eq!(decode_binary(&data).unwrap_err().request(), "answer");
+
+        assert_eq!(decode_text("not json").unwrap_err().request(), "unknown");
+        assert_eq!(decode_text(r#"{"data": {}}"#).unwrap_err().request(), "unknown");
+    }
+
+    #[test]
+    fn raw_frames_are_sent_as_is_but_never_decoded() {
+        let frame = serde_json::json!({"type": "answer", "data": {"from": "bot", "session_id": "a-bot"}});
+        let Message::Text(text) = Codec::Json.encode(&Method::Raw(frame.clone())).unwrap() else {
+            panic!("JSON must encode to text frames");
+        };
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&text).unwrap(), frame);
+        assert_eq!(decode_text(&text).unwrap_err().request(), "answer");
+        assert!(decode_text(r#"{"type": "raw", "data": {}}"#).is_err());
+    }
+}
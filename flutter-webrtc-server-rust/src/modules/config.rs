@@ -1,6 +1,15 @@
 use anyhow::Result;
-use ini::Ini;
+use arc_swap::ArcSwap;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::modules::bots::BotBehavior;
+use crate::modules::config_source::{positive, ConfigErrors, Layers, Resolver, Source};
+use crate::modules::signaling::REQUEST_TYPES;
+use crate::modules::webhooks::EVENT_TYPES;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GeneralConfig {
@@ -10,6 +19,12 @@ pub struct GeneralConfig {
     pub bind: String,
     pub port: u16,
     pub html_root: String,
+    /// Origins allowed for CORS and WebSocket upgrades, `*` allows any
+    pub allowed_origins: Vec<String>,
+    /// Seconds connections and TURN allocations may drain after SIGTERM/SIGINT
+    pub shutdown_timeout: u64,
+    /// Sent in `goaway` so clients know where to reconnect
+    pub reconnect_url: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,43 +34,718 @@ pub struct TurnConfig {
     pub realm: String,
     pub username: String,
     pub password: String,
+    /// Lifetime in seconds of credentials issued by `/api/turn`
+    pub credential_ttl: i64,
+    /// TURN REST API secret the passwords of issued credentials are derived
+    /// from, random per credential when unset
+    pub secret: Option<String>,
+}
+
+/// `public_ip` of the Go server's sample config, which leaves TURN disabled.
+pub const PUBLIC_IP_PLACEHOLDER: &str = "YOUR PUBLIC IP";
+
+impl TurnConfig {
+    /// False while `public_ip` is still the placeholder.
+    pub fn is_enabled(&self) -> bool {
+        self.public_ip != PUBLIC_IP_PLACEHOLDER
+    }
+}
+
+/// Token bucket parameters: `rate` tokens per second, holding at most `burst`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BucketLimit {
+    pub rate: f64,
+    pub burst: f64,
+}
+
+impl BucketLimit {
+    /// Parses the `rate,burst` form used in the config file.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (rate, burst) = value.split_once(',')?;
+        Some(BucketLimit {
+            rate: rate.trim().parse().ok()?,
+            burst: burst.trim().parse().ok()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    pub max_connections_per_ip: usize,
+    /// Rate limit breaches tolerated on one connection before it is closed
+    pub max_violations: u32,
+    pub default: BucketLimit,
+    /// Per message type overrides of `default`, keyed by the `type` field
+    pub per_type: HashMap<String, BucketLimit>,
+}
+
+impl RateLimitConfig {
+    pub fn limit_for(&self, request_type: &str) -> BucketLimit {
+        self.per_type.get(request_type).copied().unwrap_or(self.default)
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        let per_type = HashMap::from([
+            ("new".to_string(), BucketLimit { rate: 0.2, burst: 3.0 }),
+            ("candidate".to_string(), BucketLimit { rate: 50.0, burst: 100.0 }),
+        ]);
+
+        Self {
+            max_connections_per_ip: 20,
+            max_violations: 10,
+            default: BucketLimit { rate: 20.0, burst: 40.0 },
+            per_type,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignalingConfig {
+    /// Largest inbound message. Larger WebSocket frames close the connection,
+    /// larger HTTP fallback messages get an `error` reply.
+    pub max_message_size: usize,
+    /// Concurrent WebSocket connections accepted before upgrades get `503`
+    pub max_connections: usize,
+    pub rate_limit: RateLimitConfig,
+}
+
+impl Default for SignalingConfig {
+    fn default() -> Self {
+        Self {
+            max_message_size: 64 * 1024,
+            max_connections: 10_000,
+            rate_limit: RateLimitConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AdminConfig {
+    /// Bearer token for the `/admin` API; the API is disabled when unset
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthConfig {
+    /// How long `/readyz` waits for the TURN socket to answer its STUN probe
+    pub turn_probe_timeout_ms: u64,
+    /// Days before `cert` expires at which `/readyz` starts warning
+    pub cert_warning_days: i64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            turn_probe_timeout_ms: 1000,
+            cert_warning_days: 14,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    /// Endpoints that receive every event, each with its own queue
+    pub urls: Vec<String>,
+    /// Key for the `X-Webhook-Signature` HMAC; events are sent unsigned when unset
+    pub secret: Option<String>,
+    /// Event types to send, all of them when empty
+    pub events: Vec<String>,
+    pub timeout_ms: u64,
+    /// Delivery attempts per event before it is dropped
+    pub max_attempts: u32,
+    /// First retry delay, doubled after every failed attempt up to `retry_max_ms`
+    pub retry_base_ms: u64,
+    pub retry_max_ms: u64,
+    /// Undelivered events kept per endpoint; the oldest are dropped beyond this
+    pub queue_size: usize,
+    /// Directory persisting undelivered events across restarts, in memory when unset
+    pub queue_dir: Option<String>,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            urls: Vec::new(),
+            secret: None,
+            events: Vec::new(),
+            timeout_ms: 5000,
+            max_attempts: 8,
+            retry_base_ms: 1000,
+            retry_max_ms: 300_000,
+            queue_size: 10_000,
+            queue_dir: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CdrConfig {
+    /// SQLite database call detail records are written to; none are kept when unset
+    pub path: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoreConfig {
+    /// SQLite database for sessions and TURN credentials; kept in memory when unset
+    pub path: Option<String>,
+    /// Seconds ended call sessions are kept
+    pub session_retention: u64,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            session_retention: 86400,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhipConfig {
+    /// Bearer token WHIP and WHEP clients must send; the endpoints are open when unset
+    pub token: Option<String>,
+    /// How long a WHIP or WHEP request waits for the called peer to answer
+    pub answer_timeout_ms: u64,
+    /// How long after the answer the called peer's ICE candidates are collected into it
+    pub candidate_wait_ms: u64,
+}
+
+impl Default for WhipConfig {
+    fn default() -> Self {
+        Self {
+            token: None,
+            answer_timeout_ms: 30_000,
+            candidate_wait_ms: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FallbackConfig {
+    /// How long a long-poll request waits for messages before returning empty
+    pub poll_timeout_ms: u64,
+    /// How long a long-poll connection stays open without a waiting poll
+    pub idle_timeout_ms: u64,
+    /// Interval of SSE keep-alive comments, for proxies that close quiet responses
+    pub sse_keepalive_ms: u64,
+}
+
+impl Default for FallbackConfig {
+    fn default() -> Self {
+        Self {
+            poll_timeout_ms: 25_000,
+            idle_timeout_ms: 60_000,
+            sse_keepalive_ms: 15_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebTransportConfig {
+    /// UDP address of the WebTransport endpoint, e.g. `0.0.0.0:8086`; disabled when unset
+    pub listen: Option<String>,
+    /// Path of the signaling session
+    pub path: String,
+}
+
+impl Default for WebTransportConfig {
+    fn default() -> Self {
+        Self {
+            listen: None,
+            path: "/wt".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SfuConfig {
+    /// Peer id clients call to join a room through the SFU; disabled when unset
+    pub peer_id: Option<String>,
+    /// Participants per room, further offers are declined
+    pub max_room_size: usize,
+    /// How often publishers are asked for a keyframe
+    pub keyframe_interval_ms: u64,
+}
+
+impl Default for SfuConfig {
+    fn default() -> Self {
+        Self {
+            peer_id: None,
+            max_room_size: 16,
+            keyframe_interval_ms: 3000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EchoConfig {
+    /// Peer id of the echo bot; disabled when unset
+    pub peer_id: Option<String>,
+    /// Concurrent calls, further offers are declined
+    pub max_calls: usize,
+}
+
+impl Default for EchoConfig {
+    fn default() -> Self {
+        Self {
+            peer_id: None,
+            max_calls: 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BotPeer {
+    pub peer_id: String,
+    pub behavior: BotBehavior,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BotsConfig {
+    /// Signaling-only test peers, none by default
+    pub peers: Vec<BotPeer>,
+    /// How long bots wait before replying to an offer
+    pub reply_delay_ms: u64,
+}
+
+impl Default for BotsConfig {
+    fn default() -> Self {
+        Self {
+            peers: Vec::new(),
+            reply_delay_ms: 500,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClusterBackend {
+    /// Single node, no cluster
+    #[default]
+    None,
+    /// Directory and bus in this process
+    Memory,
+    Redis,
+    /// Direct links between nodes
+    Mesh,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterConfig {
+    pub backend: ClusterBackend,
+    /// Unique per node, random when unset
+    pub node_id: Option<String>,
+    pub redis_url: String,
+    /// Prefix of the Redis keys and channels, to share a Redis between clusters
+    pub key_prefix: String,
+    /// How often a node announces itself and resyncs the peer directory
+    pub heartbeat_ms: u64,
+    /// Peers of nodes silent for this long are dropped
+    pub node_timeout_ms: u64,
+    /// Address this node accepts mesh links on
+    pub mesh_listen: Option<String>,
+    /// Mesh addresses of the other nodes, `host:port` or `ws://` URLs
+    pub mesh_peers: Vec<String>,
+    /// Shared secret mesh links authenticate with
+    pub mesh_secret: Option<String>,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            backend: ClusterBackend::None,
+            node_id: None,
+            redis_url: "redis://127.0.0.1:6379/".to_string(),
+            key_prefix: "webrtc".to_string(),
+            heartbeat_ms: 5000,
+            node_timeout_ms: 15000,
+            mesh_listen: None,
+            mesh_peers: Vec::new(),
+            mesh_secret: None,
+        }
+    }
+}
+
+/// Live configuration shared by all components, swapped as a whole on reload.
+pub type ConfigHandle = Arc<ArcSwap<Config>>;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub general: GeneralConfig,
     pub turn: TurnConfig,
+    pub signaling: SignalingConfig,
+    pub admin: AdminConfig,
+    pub health: HealthConfig,
+    pub webhooks: WebhookConfig,
+    pub cdr: CdrConfig,
+    pub store: StoreConfig,
+    pub cluster: ClusterConfig,
+    pub whip: WhipConfig,
+    pub fallback: FallbackConfig,
+    pub webtransport: WebTransportConfig,
+    pub sfu: SfuConfig,
+    pub echo: EchoConfig,
+    pub bots: BotsConfig,
+}
+
+/// Builds a `Config` from the same layers as the command line, with the same
+/// validation. Later calls override earlier ones; unset keys keep their defaults.
+///
+/// ```no_run
+/// # use flutter_webrtc_server_rust::Config;
+/// let config = Config::builder()
+///     .file("configs/config.ini")?
+///     .env()
+///     .public_ip("203.0.113.7")
+///     .set("turn", "realm", "example.com")
+///     .build()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct ConfigBuilder {
+    layers: Layers,
+}
+
+impl ConfigBuilder {
+    /// Reads an INI file, or TOML if the name ends in `.toml`.
+    pub fn file(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        self.layers.load_file(path.as_ref())?;
+        Ok(self)
+    }
+
+    /// Applies `WEBRTC_<SECTION>__<KEY>` environment variables.
+    pub fn env(mut self) -> Self {
+        self.layers.load_env(std::env::vars());
+        self
+    }
+
+    /// Sets `key` in `[section]` as if it were written in the config file.
+    pub fn set(self, section: &str, key: &str, value: impl ToString) -> Self {
+        self.set_from(section, key, value, Source::Builder)
+    }
+
+    /// Like `set`, attributing the value to `source` in validation errors.
+    pub fn set_from(mut self, section: &str, key: &str, value: impl ToString, source: Source) -> Self {
+        self.layers.set(section, key, value.to_string(), source);
+        self
+    }
+
+    pub fn bind(self, ip: impl ToString) -> Self {
+        self.set("general", "bind", ip)
+    }
+
+    pub fn port(self, port: u16) -> Self {
+        self.set("general", "port", port)
+    }
+
+    pub fn public_ip(self, ip: impl ToString) -> Self {
+        self.set("turn", "public_ip", ip)
+    }
+
+    pub fn turn_port(self, port: u16) -> Self {
+        self.set("turn", "port", port)
+    }
+
+    pub fn build(self) -> Result<Config, ConfigErrors> {
+        Config::from_layers(&self.layers)
+    }
 }
 
 impl Config {
-    pub fn load_from_file(path: &str) -> Result<Self> {
-        let conf = Ini::load_from_file(path)?;
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
+    pub fn bind_addr(&self) -> Result<SocketAddr> {
+        let ip: IpAddr = self
+            .general
+            .bind
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid bind address {}: {}", self.general.bind, e))?;
+        Ok(SocketAddr::new(ip, self.general.port))
+    }
 
-        let general_section = conf.section(Some("general")).ok_or_else(|| {
-            anyhow::anyhow!("Missing [general] section in config file")
-        })?;
+    pub fn into_handle(self) -> ConfigHandle {
+        Arc::new(ArcSwap::from_pointee(self))
+    }
 
-        let turn_section = conf.section(Some("turn")).ok_or_else(|| {
-            anyhow::anyhow!("Missing [turn] section in config file")
-        })?;
+    /// Copy safe to print or log, with secrets masked.
+    pub fn redacted(&self) -> Config {
+        let mut config = self.clone();
+        config.turn.password = "********".to_string();
+        if config.turn.secret.is_some() {
+            config.turn.secret = Some("********".to_string());
+        }
+        if config.admin.token.is_some() {
+            config.admin.token = Some("********".to_string());
+        }
+        if config.webhooks.secret.is_some() {
+            config.webhooks.secret = Some("********".to_string());
+        }
+        if config.whip.token.is_some() {
+            config.whip.token = Some("********".to_string());
+        }
+        if config.cluster.mesh_secret.is_some() {
+            config.cluster.mesh_secret = Some("********".to_string());
+        }
+        if let Ok(mut url) = reqwest::Url::parse(&config.cluster.redis_url) {
+            if url.password().is_some() && url.set_password(Some("********")).is_ok() {
+                config.cluster.redis_url = url.to_string();
+            }
+        }
+        config
+    }
+
+    /// Resolves the configuration from `layers`, falling back to defaults for unset
+    /// keys. Invalid values and unknown keys are all reported, with their source.
+    pub fn from_layers(layers: &Layers) -> Result<Self, ConfigErrors> {
+        let mut r = Resolver::new(layers);
 
         let general = GeneralConfig {
-            domain: general_section.get("domain").unwrap_or("localhost").to_string(),
-            cert: general_section.get("cert").unwrap_or("configs/certs/cert.pem").to_string(),
-            key: general_section.get("key").unwrap_or("configs/certs/key.pem").to_string(),
-            bind: general_section.get("bind").unwrap_or("0.0.0.0").to_string(),
-            port: general_section.get("port").unwrap_or("8086").parse().unwrap_or(8086),
-            html_root: general_section.get("html_root").unwrap_or("web").to_string(),
+            domain: r.string("general", "domain", "localhost"),
+            cert: r.string("general", "cert", "configs/certs/cert.pem"),
+            key: r.string("general", "key", "configs/certs/key.pem"),
+            bind: r.parse_with("general", "bind", "0.0.0.0".to_string(), |v| {
+                v.parse::<IpAddr>().map(|_| v.to_string()).map_err(|e| e.to_string())
+            }),
+            port: r.parse_with("general", "port", 8086, positive),
+            html_root: r.string("general", "html_root", "web"),
+            allowed_origins: r.parse_with("general", "allowed_origins", vec!["*".to_string()], |v| {
+                Ok(split_list(v))
+            }),
+            shutdown_timeout: r.parse("general", "shutdown_timeout", 30),
+            reconnect_url: r.optional("general", "reconnect_url"),
         };
 
         let turn = TurnConfig {
-            public_ip: turn_section.get("public_ip").unwrap_or("127.0.0.1").to_string(),
-            port: turn_section.get("port").unwrap_or("19302").parse().unwrap_or(19302),
-            realm: turn_section.get("realm").unwrap_or("flutter-webrtc").to_string(),
-            username: turn_section.get("username").unwrap_or("user").to_string(),
-            password: turn_section.get("password").unwrap_or("password").to_string(),
+            public_ip: r.parse_with("turn", "public_ip", "127.0.0.1".to_string(), |v| {
+                // A hostname is fine too, it only ends up in the TURN URIs
+                if v == PUBLIC_IP_PLACEHOLDER {
+                    Ok(v.to_string())
+                } else if v.is_empty() || v.contains(char::is_whitespace) {
+                    Err("expected an IP address or hostname".to_string())
+                } else {
+                    Ok(v.to_string())
+                }
+            }),
+            port: r.parse_with("turn", "port", 19302, positive),
+            realm: r.string("turn", "realm", "flutter-webrtc"),
+            username: r.string("turn", "username", "user"),
+            password: r.string("turn", "password", "password"),
+            credential_ttl: r.parse_with("turn", "credential_ttl", 86400, positive),
+            secret: r.optional("turn", "secret"),
+        };
+
+        // Optional sections, absent from configs shared with the Go server
+        let defaults = SignalingConfig::default();
+        let mut signaling = SignalingConfig {
+            max_message_size: r.parse_with("signaling", "max_message_size", defaults.max_message_size, positive),
+            max_connections: r.parse_with("signaling", "max_connections", defaults.max_connections, positive),
+            ..defaults
+        };
+
+        let rate_limit = &mut signaling.rate_limit;
+        for key in r.keys("ratelimit") {
+            match key.as_str() {
+                "max_connections_per_ip" => {
+                    rate_limit.max_connections_per_ip =
+                        r.parse_with("ratelimit", &key, rate_limit.max_connections_per_ip, positive)
+                }
+                "max_violations" => {
+                    rate_limit.max_violations = r.parse("ratelimit", &key, rate_limit.max_violations)
+                }
+                "default" => {
+                    rate_limit.default = r.parse_with("ratelimit", &key, rate_limit.default, parse_bucket)
+                }
+                // Any other key is a message type; the rest are left for the unknown-key check
+                request_type if REQUEST_TYPES.contains(&request_type) => {
+                    if let Some(limit) = r.value("ratelimit", request_type, parse_bucket) {
+                        rate_limit.per_type.insert(request_type.to_string(), limit);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let admin = AdminConfig {
+            token: r.optional("admin", "token"),
+        };
+
+        let defaults = HealthConfig::default();
+        let health = HealthConfig {
+            turn_probe_timeout_ms: r.parse_with("health", "turn_probe_timeout_ms", defaults.turn_probe_timeout_ms, positive),
+            cert_warning_days: r.parse("health", "cert_warning_days", defaults.cert_warning_days),
         };
 
-        Ok(Config { general, turn })
+        let defaults = WebhookConfig::default();
+        let webhooks = WebhookConfig {
+            urls: r.parse_with("webhooks", "urls", defaults.urls, |v| {
+                let urls = split_list(v);
+                match urls.iter().find(|url| !url.starts_with("http://") && !url.starts_with("https://")) {
+                    Some(url) => Err(format!("{} is not an http(s) URL", url)),
+                    None => Ok(urls),
+                }
+            }),
+            secret: r.optional("webhooks", "secret"),
+            events: r.parse_with("webhooks", "events", defaults.events, |v| {
+                let events = split_list(v);
+                match events.iter().find(|event| !EVENT_TYPES.contains(&event.as_str())) {
+                    Some(event) => Err(format!("unknown event {}, expected one of {}", event, EVENT_TYPES.join(", "))),
+                    None => Ok(events),
+                }
+            }),
+            timeout_ms: r.parse_with("webhooks", "timeout_ms", defaults.timeout_ms, positive),
+            max_attempts: r.parse_with("webhooks", "max_attempts", defaults.max_attempts, positive),
+            retry_base_ms: r.parse_with("webhooks", "retry_base_ms", defaults.retry_base_ms, positive),
+            retry_max_ms: r.parse_with("webhooks", "retry_max_ms", defaults.retry_max_ms, positive),
+            queue_size: r.parse_with("webhooks", "queue_size", defaults.queue_size, positive),
+            queue_dir: r.optional("webhooks", "queue_dir"),
+        };
+
+        let cdr = CdrConfig {
+            path: r.optional("cdr", "path"),
+        };
+
+        let defaults = StoreConfig::default();
+        let store = StoreConfig {
+            path: r.optional("store", "path"),
+            session_retention: r.parse_with("store", "session_retention", defaults.session_retention, positive),
+        };
+
+        let defaults = ClusterConfig::default();
+        let cluster = ClusterConfig {
+            backend: r.parse_with("cluster", "backend", defaults.backend, |v| match v {
+                "" | "none" => Ok(ClusterBackend::None),
+                "memory" => Ok(ClusterBackend::Memory),
+                "redis" => Ok(ClusterBackend::Redis),
+                "mesh" => Ok(ClusterBackend::Mesh),
+                _ => Err("expected none, memory, redis or mesh".to_string()),
+            }),
+            node_id: r.optional("cluster", "node_id"),
+            redis_url: r.string("cluster", "redis_url", &defaults.redis_url),
+            key_prefix: r.string("cluster", "key_prefix", &defaults.key_prefix),
+            heartbeat_ms: r.parse_with("cluster", "heartbeat_ms", defaults.heartbeat_ms, positive),
+            node_timeout_ms: r.parse_with("cluster", "node_timeout_ms", defaults.node_timeout_ms, positive),
+            mesh_listen: r.optional("cluster", "mesh_listen"),
+            mesh_peers: r.parse_with("cluster", "mesh_peers", defaults.mesh_peers, |v| Ok(split_list(v))),
+            mesh_secret: r.optional("cluster", "mesh_secret"),
+        };
+
+        let defaults = WhipConfig::default();
+        let whip = WhipConfig {
+            token: r.optional("whip", "token"),
+            answer_timeout_ms: r.parse_with("whip", "answer_timeout_ms", defaults.answer_timeout_ms, positive),
+            candidate_wait_ms: r.parse("whip", "candidate_wait_ms", defaults.candidate_wait_ms),
+        };
+
+        let defaults = FallbackConfig::default();
+        let fallback = FallbackConfig {
+            poll_timeout_ms: r.parse_with("fallback", "poll_timeout_ms", defaults.poll_timeout_ms, positive),
+            idle_timeout_ms: r.parse_with("fallback", "idle_timeout_ms", defaults.idle_timeout_ms, positive),
+            sse_keepalive_ms: r.parse_with("fallback", "sse_keepalive_ms", defaults.sse_keepalive_ms, positive),
+        };
+
+        let defaults = WebTransportConfig::default();
+        let webtransport = WebTransportConfig {
+            listen: r.optional("webtransport", "listen"),
+            path: r.string("webtransport", "path", &defaults.path),
+        };
+
+        let defaults = SfuConfig::default();
+        let sfu = SfuConfig {
+            peer_id: r.optional("sfu", "peer_id"),
+            max_room_size: r.parse_with("sfu", "max_room_size", defaults.max_room_size, positive),
+            keyframe_interval_ms: r.parse_with("sfu", "keyframe_interval_ms", defaults.keyframe_interval_ms, positive),
+        };
+
+        let defaults = EchoConfig::default();
+        let echo = EchoConfig {
+            peer_id: r.optional("echo", "peer_id"),
+            max_calls: r.parse_with("echo", "max_calls", defaults.max_calls, positive),
+        };
+
+        let defaults = BotsConfig::default();
+        let bots = BotsConfig {
+            peers: r.parse_with("bots", "peers", defaults.peers, |v| {
+                split_list(v)
+                    .iter()
+                    .map(|peer| match peer.rsplit_once(':') {
+                        Some((peer_id, behavior)) if !peer_id.is_empty() => BotBehavior::parse(behavior)
+                            .map(|behavior| BotPeer {
+                                peer_id: peer_id.to_string(),
+                                behavior,
+                            })
+                            .ok_or_else(|| {
+                                format!("unknown behavior {}, expected answer, reject, busy, timeout, malformed or undecodable", behavior)
+                            }),
+                        _ => Err(format!("{} is not id:behavior", peer)),
+                    })
+                    .collect()
+            }),
+            reply_delay_ms: r.parse("bots", "reply_delay_ms", defaults.reply_delay_ms),
+        };
+
+        r.finish()?;
+        Ok(Config { general, turn, signaling, admin, health, webhooks, cdr, store, cluster, whip, fallback, webtransport, sfu, echo, bots })
+    }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn parse_bucket(value: &str) -> Result<BucketLimit, String> {
+    let limit = BucketLimit::parse(value).ok_or("expected `rate,burst`")?;
+    if limit.rate <= 0.0 || limit.burst < 1.0 {
+        return Err("rate must be positive and burst at least 1".to_string());
     }
-}
\ No newline at end of file
+    Ok(limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ratelimit_keys_name_message_types() {
+        let config = Config::builder()
+            .set("ratelimit", "offer", "1,2")
+            .set("ratelimit", "default", "5,10")
+            .build()
+            .unwrap();
+        let rate_limit = &config.signaling.rate_limit;
+        assert_eq!(rate_limit.limit_for("offer").burst, 2.0);
+        assert_eq!(rate_limit.limit_for("answer").rate, 5.0);
+    }
+
+    #[test]
+    fn unknown_ratelimit_keys_are_rejected() {
+        let errors = Config::builder()
+            .set("ratelimit", "ofer", "1,2")
+            .set("ratelimit", "max_conections_per_ip", "5")
+            .build()
+            .unwrap_err()
+            .0;
+        let mut fields: Vec<_> = errors.iter().map(|error| error.field.as_str()).collect();
+        fields.sort();
+        assert_eq!(fields, ["ratelimit.max_conections_per_ip", "ratelimit.ofer"]);
+        assert!(errors.iter().all(|error| error.message == "unknown setting"));
+    }
+
+    #[test]
+    fn public_ip_placeholder_disables_turn() {
+        let config = Config::builder().public_ip(PUBLIC_IP_PLACEHOLDER).build().unwrap();
+        assert!(!config.turn.is_enabled());
+        assert!(Config::builder().build().unwrap().turn.is_enabled());
+
+        let errors = Config::builder().public_ip("MY PUBLIC IP").build().unwrap_err().0;
+        assert_eq!(errors[0].field, "turn.public_ip");
+    }
+}
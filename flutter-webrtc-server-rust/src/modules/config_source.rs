@@ -0,0 +1,373 @@
+use anyhow::Result;
+use ini::Ini;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Environment variables named `WEBRTC_<SECTION>__<KEY>` override config file entries,
+/// e.g. `WEBRTC_TURN__PUBLIC_IP` sets `public_ip` in `[turn]`.
+pub const ENV_PREFIX: &str = "WEBRTC_";
+
+/// Where a configuration value came from. Unset keys take the built-in default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    File { path: String, line: usize },
+    Env(String),
+    /// Command line flag
+    Cli(&'static str),
+    /// Set through `ConfigBuilder`
+    Builder,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::File { path, line } => write!(f, "{}:{}", path, line),
+            Source::Env(name) => write!(f, "env {}", name),
+            Source::Cli(flag) => write!(f, "flag {}", flag),
+            Source::Builder => write!(f, "ConfigBuilder"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Value {
+    pub raw: String,
+    pub source: Source,
+}
+
+/// Raw `section.key` entries from every layer. Layers are applied in the order
+/// they are loaded, later ones overriding earlier ones: config file, then
+/// environment, then command line.
+#[derive(Debug, Default)]
+pub struct Layers {
+    entries: BTreeMap<(String, String), Value>,
+}
+
+impl Layers {
+    pub fn set(&mut self, section: &str, key: &str, raw: impl Into<String>, source: Source) {
+        self.entries.insert(
+            (section.to_string(), key.to_string()),
+            Value { raw: raw.into(), source },
+        );
+    }
+
+    /// Loads a TOML file if the extension is `.toml`, an INI file otherwise.
+    pub fn load_file(&mut self, path: &Path) -> Result<()> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+        let name = path.display().to_string();
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => self.load_toml(&name, &text),
+            _ => self.load_ini(&name, &text),
+        }
+    }
+
+    fn load_ini(&mut self, path: &str, text: &str) -> Result<()> {
+        let conf = Ini::load_from_str(text)
+            .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", path, e))?;
+        let lines = ini_lines(text);
+
+        for (section, properties) in conf.iter() {
+            let section = section.unwrap_or_default();
+            for (key, value) in properties.iter() {
+                let line = lines.get(&(section.to_string(), key.to_string())).copied().unwrap_or(0);
+                self.set(section, key, value, Source::File { path: path.to_string(), line });
+            }
+        }
+        Ok(())
+    }
+
+    fn load_toml(&mut self, path: &str, text: &str) -> Result<()> {
+        let tables: BTreeMap<String, BTreeMap<String, toml::Spanned<toml::Value>>> =
+            toml::from_str(text).map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", path, e))?;
+
+        for (section, entries) in tables {
+            for (key, value) in entries {
+                let line = text[..value.span().start].matches('\n').count() + 1;
+                let raw = toml_to_raw(value.get_ref());
+                self.set(&section, &key, raw, Source::File { path: path.to_string(), line });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load_env(&mut self, vars: impl IntoIterator<Item = (String, String)>) {
+        for (name, value) in vars {
+            // Names without `__`, like `WEBRTC_DEBUG`, are not settings and belong
+            // to someone else
+            let Some((section, key)) = name.strip_prefix(ENV_PREFIX).and_then(|rest| rest.split_once("__")) else {
+                continue;
+            };
+            self.set(
+                &section.to_ascii_lowercase(),
+                &key.to_ascii_lowercase(),
+                value,
+                Source::Env(name.clone()),
+            );
+        }
+    }
+
+    fn get(&self, section: &str, key: &str) -> Option<&Value> {
+        self.entries.get(&(section.to_string(), key.to_string()))
+    }
+}
+
+// rust-ini does not keep positions, so map each key to the line it was last set on
+fn ini_lines(text: &str) -> HashMap<(String, String), usize> {
+    let mut lines = HashMap::new();
+    let mut section = String::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.split(']').next()) {
+            section = name.trim().to_string();
+        } else if let Some(position) = line.find(['=', ':']) {
+            lines.insert((section.clone(), line[..position].trim().to_string()), index + 1);
+        }
+    }
+    lines
+}
+
+// TOML values are flattened to the INI string form so both formats parse the same way
+fn toml_to_raw(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Array(items) => items.iter().map(toml_to_raw).collect::<Vec<_>>().join(","),
+        other => other.to_string(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldError {
+    pub field: String,
+    pub value: String,
+    pub source: Source,
+    pub message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {:?} ({}): {}", self.field, self.value, self.source, self.message)
+    }
+}
+
+/// Every invalid or unknown field found while resolving the configuration.
+#[derive(Debug)]
+pub struct ConfigErrors(pub Vec<FieldError>);
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid configuration, {} error(s):", self.0.len())?;
+        for error in &self.0 {
+            write!(f, "\n  {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigErrors {}
+
+/// Resolves typed settings from `Layers`, collecting errors instead of stopping
+/// at the first one.
+pub struct Resolver<'a> {
+    layers: &'a Layers,
+    used: HashSet<(String, String)>,
+    errors: Vec<FieldError>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(layers: &'a Layers) -> Self {
+        Self {
+            layers,
+            used: HashSet::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Parses `section.key` with `parse`. Returns `None` if the key is not set or
+    /// its value is invalid, in which case the error is recorded.
+    pub fn value<T>(
+        &mut self,
+        section: &str,
+        key: &str,
+        parse: impl FnOnce(&str) -> Result<T, String>,
+    ) -> Option<T> {
+        self.used.insert((section.to_string(), key.to_string()));
+        let value = self.layers.get(section, key)?;
+
+        match parse(value.raw.trim()) {
+            Ok(parsed) => Some(parsed),
+            Err(message) => {
+                self.errors.push(FieldError {
+                    field: field_name(section, key),
+                    value: value.raw.clone(),
+                    source: value.source.clone(),
+                    message,
+                });
+                None
+            }
+        }
+    }
+
+    pub fn parse_with<T>(
+        &mut self,
+        section: &str,
+        key: &str,
+        default: T,
+        parse: impl FnOnce(&str) -> Result<T, String>,
+    ) -> T {
+        self.value(section, key, parse).unwrap_or(default)
+    }
+
+    pub fn parse<T>(&mut self, section: &str, key: &str, default: T) -> T
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.parse_with(section, key, default, |v| v.parse().map_err(|e: T::Err| e.to_string()))
+    }
+
+    pub fn string(&mut self, section: &str, key: &str, default: &str) -> String {
+        self.parse_with(section, key, default.to_string(), |v| Ok(v.to_string()))
+    }
+
+    /// Unset and empty values are both `None`.
+    pub fn optional(&mut self, section: &str, key: &str) -> Option<String> {
+        self.value(section, key, |v| Ok(v.to_string())).filter(|v| !v.is_empty())
+    }
+
+    /// Keys present in `section`, for sections with free-form keys.
+    pub fn keys(&self, section: &str) -> Vec<String> {
+        self.layers
+            .entries
+            .keys()
+            .filter(|(s, _)| s == section)
+            .map(|(_, key)| key.clone())
+            .collect()
+    }
+
+    /// Fails with every recorded error, plus one for each key nothing asked for.
+    pub fn finish(mut self) -> Result<(), ConfigErrors> {
+        for ((section, key), value) in &self.layers.entries {
+            if !self.used.contains(&(section.clone(), key.clone())) {
+                self.errors.push(FieldError {
+                    field: field_name(section, key),
+                    value: value.raw.clone(),
+                    source: value.source.clone(),
+                    message: "unknown setting".to_string(),
+                });
+            }
+        }
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigErrors(self.errors))
+        }
+    }
+}
+
+fn field_name(section: &str, key: &str) -> String {
+    if key.is_empty() {
+        section.to_string()
+    } else {
+        format!("{}.{}", section, key)
+    }
+}
+
+/// Parser for numeric settings that must be greater than zero.
+pub fn positive<T>(value: &str) -> Result<T, String>
+where
+    T: FromStr + PartialOrd + Default,
+    T::Err: fmt::Display,
+{
+    let parsed: T = value.parse().map_err(|e: T::Err| e.to_string())?;
+    if parsed > T::default() {
+        Ok(parsed)
+    } else {
+        Err("must be greater than zero".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn later_layers_override_earlier_ones() {
+        let mut layers = Layers::default();
+        layers.load_ini("config.ini", "[general]\nport=8086\n\n[turn]\nrealm=file\n").unwrap();
+        layers.load_env(env(&[("WEBRTC_GENERAL__PORT", "9000")]));
+        layers.set("turn", "realm", "flag", Source::Cli("--realm"));
+
+        let port = layers.get("general", "port").unwrap();
+        assert_eq!(port.raw, "9000");
+        assert_eq!(port.source, Source::Env("WEBRTC_GENERAL__PORT".to_string()));
+        assert_eq!(layers.get("turn", "realm").unwrap().source, Source::Cli("--realm"));
+    }
+
+    #[test]
+    fn file_values_keep_their_line() {
+        let mut layers = Layers::default();
+        layers.load_ini("config.ini", "[general]\n; comment\nport=8086\n").unwrap();
+        let source = &layers.get("general", "port").unwrap().source;
+        assert_eq!(*source, Source::File { path: "config.ini".to_string(), line: 3 });
+
+        let mut layers = Layers::default();
+        layers.load_toml("config.toml", "[general]\n\nport = 8086\n").unwrap();
+        let source = &layers.get("general", "port").unwrap().source;
+        assert_eq!(*source, Source::File { path: "config.toml".to_string(), line: 3 });
+    }
+
+    #[test]
+    fn env_only_maps_section_key_names() {
+        let mut layers = Layers::default();
+        layers.load_env(env(&[
+            ("WEBRTC_DEBUG", "1"),
+            ("WEBRTC_TURN__PUBLIC_IP", "203.0.113.7"),
+            ("OTHER_TURN__PORT", "1"),
+        ]));
+
+        assert_eq!(layers.entries.len(), 1);
+        assert_eq!(layers.get("turn", "public_ip").unwrap().raw, "203.0.113.7");
+    }
+
+    #[test]
+    fn unused_keys_are_reported_with_their_source() {
+        let mut layers = Layers::default();
+        layers.set("general", "port", "80", Source::Builder);
+        layers.load_env(env(&[("WEBRTC_TURN__RELM", "x")]));
+
+        let mut resolver = Resolver::new(&layers);
+        assert_eq!(resolver.parse("general", "port", 0u16), 80);
+        let errors = resolver.finish().unwrap_err().0;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "turn.relm");
+        assert_eq!(errors[0].message, "unknown setting");
+        assert_eq!(errors[0].source, Source::Env("WEBRTC_TURN__RELM".to_string()));
+    }
+
+    #[test]
+    fn invalid_values_are_all_collected() {
+        let mut layers = Layers::default();
+        layers.set("general", "port", "abc", Source::Builder);
+        layers.set("general", "shutdown_timeout", "0", Source::Builder);
+
+        let mut resolver = Resolver::new(&layers);
+        assert_eq!(resolver.parse("general", "port", 8086u16), 8086);
+        assert_eq!(resolver.parse_with("general", "shutdown_timeout", 30u64, positive), 30);
+        let errors = resolver.finish().unwrap_err().0;
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[1].message, "must be greater than zero");
+    }
+}
@@ -0,0 +1,507 @@
+use anyhow::{Context, Result};
+use dashmap::DashMap;
+use log::{debug, error, info, warn};
+use rtc::media_stream::MediaStreamTrack;
+use rtc::peer_connection::configuration::media_engine::{MIME_TYPE_OPUS, MIME_TYPE_VP8};
+use rtc::rtcp::payload_feedbacks::picture_loss_indication::PictureLossIndication;
+use rtc::rtp_transceiver::rtp_sender::{
+    RTCPFeedback, RTCRtpCodec, RTCRtpCodecParameters, RTCRtpCodingParameters, RTCRtpEncodingParameters, RtpCodecKind,
+};
+use std::sync::{Arc, OnceLock};
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+use webrtc::data_channel::{DataChannel, DataChannelEvent};
+use webrtc::media_stream::track_local::static_rtp::TrackLocalStaticRTP;
+use webrtc::media_stream::track_local::TrackLocal;
+use webrtc::media_stream::track_remote::{TrackRemote, TrackRemoteEvent};
+use webrtc::peer_connection::{
+    MediaEngine, PeerConnection, PeerConnectionEventHandler, RTCPeerConnectionIceEvent, RTCPeerConnectionState,
+    RTCSessionDescription,
+};
+use webrtc::rtp_transceiver::RtpSender;
+
+use crate::modules::config::Config;
+use crate::modules::media::{candidate_init, ice_candidate, negotiated_payload_type, peer_connection, retarget};
+use crate::modules::signaling::{
+    Byebye, CandidatePayload, Connection, ConnectionEvents, DescriptionPayload, Method, PeerInfo, SessionDescription,
+    ShutdownPhase, Signaler,
+};
+
+/// How often callers are asked for a keyframe, so the echo recovers from loss
+const KEYFRAME_INTERVAL: Duration = Duration::from_secs(3);
+
+/// A virtual peer that answers every call and sends back what it receives: the
+/// caller's audio and video, and each data channel message on the channel it
+/// came in on. Lets users check their camera, microphone and TURN setup
+/// without a second device.
+pub struct EchoBot {
+    state: Arc<EchoState>,
+    events: ConnectionEvents,
+}
+
+struct EchoState {
+    signaler: Arc<Signaler>,
+    peer_id: String,
+    connection: Connection,
+    /// Keyed by session id
+    calls: DashMap<String, EchoCall>,
+}
+
+struct EchoCall {
+    caller: String,
+    /// Offers and candidates from the caller. Removing the call closes its
+    /// peer connection.
+    messages: mpsc::UnboundedSender<Method>,
+}
+
+/// A track sending the caller's media of one kind back.
+struct Loopback {
+    kind: RtpCodecKind,
+    ssrc: u32,
+    track: Arc<TrackLocalStaticRTP>,
+    sender: Arc<dyn RtpSender>,
+}
+
+impl EchoBot {
+    /// Registers `peer_id` as a peer every client can call.
+    pub async fn register(signaler: Arc<Signaler>, peer_id: &str) -> Result<Self> {
+        let (connection, events) = signaler.connect_server_peer();
+        let register = Method::New(PeerInfo {
+            id: peer_id.to_string(),
+            name: "Echo bot".to_string(),
+            user_agent: concat!("flutter-webrtc-server-rust/", env!("CARGO_PKG_VERSION")).to_string(),
+        });
+        signaler
+            .receive(&connection, register)
+            .await
+            .with_context(|| format!("Failed to register echo peer {}", peer_id))?;
+
+        let state = Arc::new(EchoState {
+            signaler,
+            peer_id: peer_id.to_string(),
+            connection,
+            calls: DashMap::new(),
+        });
+        Ok(Self { state, events })
+    }
+
+    /// Answers calls until the signaler starts draining or the peer is
+    /// disconnected, then hangs up on every caller.
+    pub async fn run(mut self) {
+        let state = self.state;
+        let mut shutdown = state.signaler.watch_shutdown();
+        shutdown.mark_changed();
+
+        loop {
+            let message = tokio::select! {
+                message = self.events.messages.recv() => match message {
+                    Some(message) => message,
+                    None => break,
+                },
+                Some(frame) = self.events.close.recv() => {
+                    info!("🔌 Echo peer {} disconnected: {}", state.peer_id, frame.reason);
+                    break;
+                }
+                Ok(()) = shutdown.changed() => {
+                    if matches!(*shutdown.borrow_and_update(), ShutdownPhase::Running) {
+                        continue;
+                    }
+                    break;
+                }
+            };
+
+            match message {
+                Method::Offer(offer) if !state.calls.contains_key(&offer.session_id) => {
+                    state.answer(offer).await;
+                }
+                Method::Offer(DescriptionPayload { ref session_id, .. })
+                | Method::Candidate(CandidatePayload { ref session_id, .. }) => {
+                    match state.calls.get(session_id) {
+                        Some(call) => {
+                            let _ = call.messages.send(message.clone());
+                        }
+                        None => debug!("Dropping {} for unknown echo session {}", message.request_type(), session_id),
+                    }
+                }
+                Method::Bye(bye) => {
+                    state.calls.remove(&bye.session_id);
+                }
+                Method::Peers(peers) => {
+                    // Callers that went away without hanging up
+                    state.calls.retain(|_, call| peers.iter().any(|peer| peer.id == call.caller));
+                }
+                Method::Error(e) => warn!("⚠️ Signaling rejected {} from the echo bot: {}", e.request, e.reason),
+                _ => {}
+            }
+        }
+
+        let sessions: Vec<String> = state.calls.iter().map(|call| call.key().clone()).collect();
+        for session_id in sessions {
+            state.hang_up(&session_id).await;
+        }
+        state.signaler.disconnect(&state.connection).await;
+        info!("Echo peer {} stopped", state.peer_id);
+    }
+}
+
+impl EchoState {
+    async fn send(&self, message: Method) {
+        let request = message.request_type();
+        if let Err(e) = self.signaler.receive(&self.connection, message).await {
+            error!("❌ Failed to send echo {}: {}", request, e);
+        }
+    }
+
+    async fn answer(self: &Arc<Self>, offer: DescriptionPayload) {
+        let config = self.signaler.config.load();
+        if self.calls.len() >= config.echo.max_calls {
+            warn!("🚫 Echo bot busy, declining {}", offer.from);
+            self.decline(&offer.session_id).await;
+            return;
+        }
+
+        let handler = Arc::new(EchoHandler {
+            state: self.clone(),
+            session_id: offer.session_id.clone(),
+            caller: offer.from.clone(),
+            loopbacks: OnceLock::new(),
+        });
+        let pc = match echo_connection(&config, handler.clone()).await {
+            Ok(pc) => pc,
+            Err(e) => {
+                error!("❌ Failed to create echo peer connection for {}: {:#}", offer.from, e);
+                self.decline(&offer.session_id).await;
+                return;
+            }
+        };
+        let loopbacks = match add_loopbacks(&pc).await {
+            Ok(loopbacks) => loopbacks,
+            Err(e) => {
+                error!("❌ Failed to add echo tracks for {}: {:#}", offer.from, e);
+                let _ = pc.close().await;
+                self.decline(&offer.session_id).await;
+                return;
+            }
+        };
+        let _ = handler.loopbacks.set(loopbacks);
+
+        info!("🔁 Echo bot answering {} (session: {})", offer.from, offer.session_id);
+        let (messages_tx, messages) = mpsc::unbounded_channel();
+        let _ = messages_tx.send(Method::Offer(offer.clone()));
+        self.calls.insert(
+            offer.session_id.clone(),
+            EchoCall {
+                caller: offer.from.clone(),
+                messages: messages_tx,
+            },
+        );
+        tokio::spawn(run_call(self.clone(), offer.session_id, offer.from, pc, messages));
+    }
+
+    async fn decline(&self, session_id: &str) {
+        self.send(Method::Bye(Byebye {
+            session_id: session_id.to_string(),
+            from: self.peer_id.clone(),
+            reason: None,
+        }))
+        .await;
+    }
+
+    async fn hang_up(&self, session_id: &str) {
+        if self.calls.remove(session_id).is_some() {
+            self.decline(session_id).await;
+        }
+    }
+}
+
+// Answers the caller's offers, including renegotiations, until the call is
+// removed
+async fn run_call(
+    state: Arc<EchoState>,
+    session_id: String,
+    caller: String,
+    pc: Arc<dyn PeerConnection>,
+    mut messages: mpsc::UnboundedReceiver<Method>,
+) {
+    while let Some(message) = messages.recv().await {
+        let result = match message {
+            Method::Offer(offer) => answer(&state, &pc, offer).await,
+            Method::Candidate(candidate) => pc
+                .add_ice_candidate(candidate_init(candidate.candidate))
+                .await
+                .map_err(Into::into),
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            warn!("⚠️ Echo negotiation with {} failed: {:#}", caller, e);
+        }
+    }
+
+    if let Err(e) = pc.close().await {
+        debug!("Failed to close echo peer connection of {}: {}", caller, e);
+    }
+    info!("🔁 Echo call with {} ended (session: {})", caller, session_id);
+}
+
+async fn answer(state: &EchoState, pc: &Arc<dyn PeerConnection>, offer: DescriptionPayload) -> Result<()> {
+    pc.set_remote_description(RTCSessionDescription::offer(offer.description.sdp)?).await?;
+    let answer = pc.create_answer(None).await?;
+    pc.set_local_description(answer.clone()).await?;
+    state
+        .send(Method::Answer(DescriptionPayload {
+            from: state.peer_id.clone(),
+            to: offer.from,
+            session_id: offer.session_id,
+            description: SessionDescription {
+                sdp: answer.sdp,
+                sdp_type: "answer".to_string(),
+            },
+            extra: serde_json::Map::new(),
+        }))
+        .await;
+    Ok(())
+}
+
+struct EchoHandler {
+    state: Arc<EchoState>,
+    session_id: String,
+    caller: String,
+    loopbacks: OnceLock<Vec<Arc<Loopback>>>,
+}
+
+#[async_trait::async_trait]
+impl PeerConnectionEventHandler for EchoHandler {
+    async fn on_ice_candidate(&self, event: RTCPeerConnectionIceEvent) {
+        let candidate = match ice_candidate(&event.candidate) {
+            Ok(candidate) => candidate,
+            Err(e) => {
+                debug!("Failed to encode echo ICE candidate: {}", e);
+                return;
+            }
+        };
+        self.state
+            .send(Method::Candidate(CandidatePayload {
+                from: self.state.peer_id.clone(),
+                to: self.caller.clone(),
+                session_id: self.session_id.clone(),
+                candidate,
+                extra: serde_json::Map::new(),
+            }))
+            .await;
+    }
+
+    async fn on_connection_state_change(&self, state: RTCPeerConnectionState) {
+        debug!("Echo connection of {} is {}", self.caller, state);
+        if state == RTCPeerConnectionState::Failed {
+            warn!("⚠️ Echo connection of {} failed", self.caller);
+            self.state.hang_up(&self.session_id).await;
+        }
+    }
+
+    async fn on_track(&self, track: Arc<dyn TrackRemote>) {
+        let kind = track.kind().await;
+        let loopback = self
+            .loopbacks
+            .get()
+            .and_then(|loopbacks| loopbacks.iter().find(|loopback| loopback.kind == kind).cloned());
+        match loopback {
+            Some(loopback) => {
+                tokio::spawn(echo_track(track, loopback));
+            }
+            None => debug!("No echo track for {} from {}", kind, self.caller),
+        }
+    }
+
+    async fn on_data_channel(&self, channel: Arc<dyn DataChannel>) {
+        tokio::spawn(echo_messages(channel));
+    }
+}
+
+// Sends the track's packets back on the loopback of its kind, asking the caller
+// for keyframes so the echo recovers from loss
+async fn echo_track(track: Arc<dyn TrackRemote>, loopback: Arc<Loopback>) {
+    let Some(media_ssrc) = track.ssrcs().await.first().copied() else {
+        return;
+    };
+    let keyframes = (loopback.kind == RtpCodecKind::Video).then(|| {
+        let track = track.clone();
+        tokio::spawn(async move {
+            loop {
+                let pli = PictureLossIndication {
+                    sender_ssrc: 0,
+                    media_ssrc,
+                };
+                if track.write_rtcp(vec![Box::new(pli)]).await.is_err() {
+                    return;
+                }
+                tokio::time::sleep(KEYFRAME_INTERVAL).await;
+            }
+        })
+    });
+
+    let mut payload_type = None;
+    while let Some(event) = track.poll().await {
+        let mut packet = match event {
+            TrackRemoteEvent::OnRtpPacket(packet) => packet,
+            TrackRemoteEvent::OnEnded => break,
+            _ => continue,
+        };
+        if payload_type.is_none() {
+            payload_type = negotiated_payload_type(&loopback.sender, &codec(loopback.kind).mime_type).await;
+        }
+        let Some(payload_type) = payload_type else {
+            continue;
+        };
+        retarget(&mut packet, loopback.ssrc, payload_type);
+        if let Err(e) = loopback.track.write_rtp(packet).await {
+            debug!("Failed to echo RTP packet: {}", e);
+        }
+    }
+
+    if let Some(keyframes) = keyframes {
+        keyframes.abort();
+    }
+}
+
+async fn echo_messages(channel: Arc<dyn DataChannel>) {
+    let label = channel.label().await.unwrap_or_default();
+    debug!("Echoing data channel {}", label);
+    while let Some(event) = channel.poll().await {
+        let message = match event {
+            DataChannelEvent::OnMessage(message) => message,
+            DataChannelEvent::OnClose => break,
+            _ => continue,
+        };
+        let result = if message.is_string {
+            channel.send_text(&String::from_utf8_lossy(&message.data)).await
+        } else {
+            channel.send(message.data).await
+        };
+        if let Err(e) = result {
+            debug!("Failed to echo message on data channel {}: {}", label, e);
+        }
+    }
+}
+
+// Only codecs the echo can send back as they came in
+async fn echo_connection(config: &Config, handler: Arc<EchoHandler>) -> Result<Arc<dyn PeerConnection>> {
+    let mut media_engine = MediaEngine::default();
+    for kind in [RtpCodecKind::Audio, RtpCodecKind::Video] {
+        let payload_type = match kind {
+            RtpCodecKind::Video => 96,
+            _ => 111,
+        };
+        media_engine.register_codec(
+            RTCRtpCodecParameters {
+                rtp_codec: codec(kind),
+                payload_type,
+            },
+            kind,
+        )?;
+    }
+    peer_connection(config, media_engine, handler).await
+}
+
+async fn add_loopbacks(pc: &Arc<dyn PeerConnection>) -> Result<Vec<Arc<Loopback>>> {
+    let mut loopbacks = Vec::new();
+    for kind in [RtpCodecKind::Audio, RtpCodecKind::Video] {
+        let ssrc = uuid::Uuid::new_v4().as_u128() as u32;
+        let track = Arc::new(TrackLocalStaticRTP::new(MediaStreamTrack::new(
+            "echo".to_string(),
+            format!("echo-{}", kind),
+            format!("echo-{}", kind),
+            kind,
+            vec![RTCRtpEncodingParameters {
+                rtp_coding_parameters: RTCRtpCodingParameters {
+                    ssrc: Some(ssrc),
+                    ..Default::default()
+                },
+                codec: codec(kind),
+                ..Default::default()
+            }],
+        )));
+        let sender = pc.add_track(track.clone() as Arc<dyn TrackLocal>).await?;
+        loopbacks.push(Arc::new(Loopback {
+            kind,
+            ssrc,
+            track,
+            sender,
+        }));
+    }
+    Ok(loopbacks)
+}
+
+fn codec(kind: RtpCodecKind) -> RTCRtpCodec {
+    match kind {
+        RtpCodecKind::Video => RTCRtpCodec {
+            mime_type: MIME_TYPE_VP8.to_string(),
+            clock_rate: 90000,
+            channels: 0,
+            sdp_fmtp_line: String::new(),
+            rtcp_feedback: vec![
+                RTCPFeedback {
+                    typ: "nack".to_string(),
+                    parameter: String::new(),
+                },
+                RTCPFeedback {
+                    typ: "nack".to_string(),
+                    parameter: "pli".to_string(),
+                },
+            ],
+        },
+        _ => RTCRtpCodec {
+            mime_type: MIME_TYPE_OPUS.to_string(),
+            clock_rate: 48000,
+            channels: 2,
+            sdp_fmtp_line: "minptime=10;useinbandfec=1".to_string(),
+            rtcp_feedback: vec![],
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::media::testing;
+    use crate::modules::signaling::CallStatus;
+    use std::sync::atomic::Ordering;
+
+    #[tokio::test]
+    async fn echo_answers_and_sends_media_and_messages_back() {
+        let signaler = Signaler::builder().config(Config::builder().build().unwrap()).build().unwrap();
+        let echo = EchoBot::register(signaler.clone(), "echo").await.unwrap();
+        tokio::spawn(echo.run());
+
+        let ssrc = 0xec0;
+        let track = testing::video_track("alice", ssrc);
+        let sent = track.clone();
+        let (alice, channel) = testing::call(signaler.clone(), "alice", "echo", |pc| async move {
+            pc.add_track(sent as Arc<dyn TrackLocal>).await.unwrap();
+            pc.create_data_channel("chat", None).await.unwrap()
+        })
+        .await;
+
+        let echoed = testing::send_until_received(&track, ssrc, &alice.received, 5).await;
+        assert!(echoed, "alice got {} packets back", alice.received.load(Ordering::SeqCst));
+        let session = signaler.store.session("alice-echo").await.unwrap().unwrap();
+        assert_eq!(session.status, CallStatus::Connected);
+
+        let message = tokio::time::timeout(Duration::from_secs(5), async {
+            while let Some(event) = channel.poll().await {
+                match event {
+                    DataChannelEvent::OnOpen => channel.send_text("ping").await.unwrap(),
+                    DataChannelEvent::OnMessage(message) => return Some(message),
+                    _ => {}
+                }
+            }
+            None
+        })
+        .await
+        .expect("no data channel echo")
+        .unwrap();
+        assert!(message.is_string);
+        assert_eq!(&message.data[..], b"ping");
+
+        alice.pc.close().await.unwrap();
+    }
+}
@@ -0,0 +1,381 @@
+use axum::{
+    extract::{
+        ws::{close_code, CloseFrame},
+        ConnectInfo, Path, State,
+    },
+    http::{HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Json, Response,
+    },
+    routing::{get, post},
+    Router,
+};
+use dashmap::DashMap;
+use log::{debug, error, info};
+use serde_json::json;
+use std::sync::atomic::Ordering;
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{Duration, Instant};
+
+use crate::modules::http::admit;
+use crate::modules::rate_limit::ConnectionGuard;
+use crate::modules::signaling::{Connection, ConnectionEvents, Method, ShutdownPhase, Signaler};
+
+/// A signaling connection kept open by an SSE stream or by long-polling.
+struct HttpConnection {
+    connection: Connection,
+    /// Waiting long-poll requests, answered with the messages queued meanwhile
+    polls: Option<mpsc::UnboundedSender<oneshot::Sender<PollReply>>>,
+}
+
+/// Answer to a waiting long-poll request.
+enum PollReply {
+    Messages(Vec<Method>),
+    /// The server closed the connection, after every queued message was delivered
+    Closed(CloseFrame<'static>),
+}
+
+#[derive(Clone)]
+struct FallbackState {
+    signaler: Arc<Signaler>,
+    connections: Arc<DashMap<String, Arc<HttpConnection>>>,
+}
+
+impl FallbackState {
+    fn open(
+        &self,
+        remote_addr: Option<SocketAddr>,
+        polls: Option<mpsc::UnboundedSender<oneshot::Sender<PollReply>>>,
+    ) -> (String, ConnectionEvents) {
+        let (connection, events) = self.signaler.connect(remote_addr);
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        self.connections.insert(id.clone(), Arc::new(HttpConnection { connection, polls }));
+        self.signaler.active_connections.fetch_add(1, Ordering::SeqCst);
+        (id, events)
+    }
+
+    async fn close(&self, id: &str) {
+        if let Some((_, http)) = self.connections.remove(id) {
+            self.signaler.disconnect(&http.connection).await;
+            self.signaler.active_connections.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Signaling over plain HTTP for clients whose proxies block WebSocket upgrades.
+/// `GET /sse` streams server messages as Server-Sent Events, `POST /poll` opens a
+/// long-polling connection read with `GET /poll/:id`. Clients send messages with
+/// `POST /sse/:id` or `POST /poll/:id`, in the JSON format of `/ws` text frames.
+pub fn router<S>(signaler: Arc<Signaler>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/sse", get(open_sse))
+        .route("/sse/:id", post(send).delete(close))
+        .route("/poll", post(open_poll))
+        .route("/poll/:id", get(poll).post(send).delete(close))
+        .with_state(FallbackState {
+            signaler,
+            connections: Arc::new(DashMap::new()),
+        })
+}
+
+async fn open_sse(
+    State(state): State<FallbackState>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Response {
+    info!("New SSE connection attempt from {}", remote_addr);
+    let (remote_addr, guard) = match admit(&state.signaler, Some(remote_addr), headers).await {
+        Ok(admitted) => admitted,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    let (id, events) = state.open(remote_addr, None);
+    let (stream_tx, stream_rx) = mpsc::channel(64);
+    let connected = json!({ "connection_id": id }).to_string();
+    let _ = stream_tx.try_send(Event::default().event("connected").data(connected));
+    tokio::spawn(run_sse(state.clone(), id, events, stream_tx, guard));
+
+    let keepalive = Duration::from_millis(state.signaler.config.load().fallback.sse_keepalive_ms);
+    let stream = futures_util::stream::unfold(stream_rx, |mut events| async move {
+        let event = events.recv().await?;
+        Some((Ok::<_, Infallible>(event), events))
+    });
+    Sse::new(stream).keep_alive(KeepAlive::new().interval(keepalive)).into_response()
+}
+
+async fn open_poll(
+    State(state): State<FallbackState>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Response {
+    info!("New long-poll connection attempt from {}", remote_addr);
+    let (remote_addr, guard) = match admit(&state.signaler, Some(remote_addr), headers).await {
+        Ok(admitted) => admitted,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    let (polls_tx, polls_rx) = mpsc::unbounded_channel();
+    let (id, events) = state.open(remote_addr, Some(polls_tx));
+    tokio::spawn(run_poll(state.clone(), id.clone(), events, polls_rx, guard));
+    (StatusCode::CREATED, Json(json!({ "connection_id": id }))).into_response()
+}
+
+async fn poll(State(state): State<FallbackState>, Path(id): Path<String>) -> Response {
+    let polls = state.connections.get(&id).and_then(|http| http.polls.clone());
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if polls.is_none_or(|polls| polls.send(reply_tx).is_err()) {
+        return (StatusCode::NOT_FOUND, "Unknown connection").into_response();
+    }
+
+    let poll_timeout = Duration::from_millis(state.signaler.config.load().fallback.poll_timeout_ms);
+    match tokio::time::timeout(poll_timeout, reply_rx).await {
+        Ok(Ok(PollReply::Messages(messages))) => Json(messages).into_response(),
+        Ok(Ok(PollReply::Closed(frame))) => (StatusCode::GONE, Json(close_data(&frame))).into_response(),
+        // The connection closed while the request waited
+        Ok(Err(_)) => (StatusCode::NOT_FOUND, "Unknown connection").into_response(),
+        Err(_) => Json(Vec::<Method>::new()).into_response(),
+    }
+}
+
+async fn send(State(state): State<FallbackState>, Path(id): Path<String>, body: String) -> Response {
+    let Some(http) = state.connections.get(&id).map(|http| http.clone()) else {
+        return (StatusCode::NOT_FOUND, "Unknown connection").into_response();
+    };
+    debug!("Received HTTP message on {}: {}", id, body);
+    if let Err(e) = state.signaler.receive_text(&http.connection, &body).await {
+        error!("Error handling message: {}", e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to handle message").into_response();
+    }
+    StatusCode::ACCEPTED.into_response()
+}
+
+async fn close(State(state): State<FallbackState>, Path(id): Path<String>) -> Response {
+    if !state.connections.contains_key(&id) {
+        return (StatusCode::NOT_FOUND, "Unknown connection").into_response();
+    }
+    info!("HTTP connection {} closed by the client", id);
+    state.close(&id).await;
+    StatusCode::NO_CONTENT.into_response()
+}
+
+// Streams messages until the client goes away or the connection is closed. The
+// HTTP server waits for open responses before it drains, so a shutdown ends the
+// stream right after the goaway.
+async fn run_sse(
+    state: FallbackState,
+    id: String,
+    mut events: ConnectionEvents,
+    stream: mpsc::Sender<Event>,
+    _guard: Option<ConnectionGuard>,
+) {
+    let mut shutdown = state.signaler.watch_shutdown();
+    shutdown.mark_changed();
+
+    loop {
+        let (message, last) = tokio::select! {
+            message = events.messages.recv() => match message {
+                Some(message) => (message, false),
+                None => break,
+            },
+            Some(frame) = events.close.recv() => {
+                let _ = stream.send(close_event(&frame)).await;
+                break;
+            }
+            Ok(()) = shutdown.changed() => {
+                let phase = shutdown.borrow_and_update().clone();
+                match phase {
+                    ShutdownPhase::Running => continue,
+                    ShutdownPhase::Draining(goaway) => (Method::GoAway(goaway), true),
+                    ShutdownPhase::Closing => break,
+                }
+            }
+            _ = stream.closed() => break,
+        };
+
+        match serde_json::to_string(&message) {
+            Ok(data) => {
+                if stream.send(Event::default().data(data)).await.is_err() {
+                    break;
+                }
+            }
+            Err(e) => error!("Failed to serialize message: {}", e),
+        }
+        if last {
+            break;
+        }
+    }
+
+    info!("SSE connection {} ended", id);
+    state.close(&id).await;
+}
+
+// Queues messages for the next poll. Closes the connection once no poll has been
+// waiting for `idle_timeout_ms`. A connection the server closes stays open until
+// a poll has taken the queued messages and the close, or until its deadline.
+async fn run_poll(
+    state: FallbackState,
+    id: String,
+    mut events: ConnectionEvents,
+    mut polls: mpsc::UnboundedReceiver<oneshot::Sender<PollReply>>,
+    _guard: Option<ConnectionGuard>,
+) {
+    let mut shutdown = state.signaler.watch_shutdown();
+    shutdown.mark_changed();
+    let mut queue = Vec::new();
+    let mut waiting: Option<oneshot::Sender<PollReply>> = None;
+    let mut last_seen = Instant::now();
+    let mut ticker = tokio::time::interval(Duration::from_secs(1));
+    // Close frame to answer the last poll with, and when to give up on that poll
+    let mut closing: Option<(CloseFrame<'static>, Instant)> = None;
+
+    loop {
+        let idle_timeout = Duration::from_millis(state.signaler.config.load().fallback.idle_timeout_ms);
+        tokio::select! {
+            message = events.messages.recv() => match message {
+                Some(message) => queue.push(message),
+                None => break,
+            },
+            Some(poll) = polls.recv() => {
+                // A newer poll replaces one still waiting
+                if let Some(previous) = waiting.replace(poll) {
+                    let _ = previous.send(PollReply::Messages(Vec::new()));
+                }
+            }
+            Some(frame) = events.close.recv() => {
+                info!("🔌 Closing long-poll connection {}: {}", id, frame.reason);
+                closing.get_or_insert((frame, Instant::now() + idle_timeout));
+            }
+            Ok(()) = shutdown.changed() => {
+                let phase = shutdown.borrow_and_update().clone();
+                match phase {
+                    ShutdownPhase::Running => {}
+                    ShutdownPhase::Draining(goaway) => {
+                        let frame = CloseFrame {
+                            code: close_code::AWAY,
+                            reason: goaway.reason.clone().into(),
+                        };
+                        let deadline = Instant::now() + Duration::from_secs(goaway.deadline);
+                        queue.push(Method::GoAway(goaway));
+                        closing.get_or_insert((frame, deadline));
+                    }
+                    ShutdownPhase::Closing => break,
+                }
+            }
+            _ = ticker.tick() => {}
+        }
+
+        if waiting.as_ref().is_some_and(|poll| !poll.is_closed()) {
+            last_seen = Instant::now();
+        }
+        if let Some(poll) = waiting.take() {
+            if !queue.is_empty() {
+                // A poll that timed out hands the messages back for the next one
+                if let Err(PollReply::Messages(messages)) = poll.send(PollReply::Messages(std::mem::take(&mut queue))) {
+                    queue = messages;
+                }
+            } else if let Some((frame, _)) = &closing {
+                if poll.send(PollReply::Closed(frame.clone())).is_ok() {
+                    break;
+                }
+            } else {
+                waiting = Some(poll);
+            }
+        }
+
+        if last_seen.elapsed() > idle_timeout {
+            info!("Long-poll connection {} idle for {}s, closing", id, idle_timeout.as_secs());
+            break;
+        }
+        if closing.as_ref().is_some_and(|(_, deadline)| Instant::now() >= *deadline) {
+            info!("Long-poll connection {} closed before a poll took the close", id);
+            break;
+        }
+    }
+
+    state.close(&id).await;
+}
+
+fn close_event(frame: &CloseFrame<'static>) -> Event {
+    Event::default().event("close").data(close_data(frame).to_string())
+}
+
+fn close_data(frame: &CloseFrame<'static>) -> serde_json::Value {
+    json!({ "code": frame.code, "reason": frame.reason })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::config::Config;
+
+    async fn serve(signaler: Arc<Signaler>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let app = router::<()>(signaler).into_make_service_with_connect_info::<SocketAddr>();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        base
+    }
+
+    async fn post(client: &reqwest::Client, url: String, message: serde_json::Value) -> StatusCode {
+        let response = client.post(url).body(message.to_string()).send().await.unwrap();
+        StatusCode::from_u16(response.status().as_u16()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn long_poll_and_websocket_peers_call_each_other() {
+        let signaler = Signaler::builder().config(Config::builder().build().unwrap()).build().unwrap();
+        let base = serve(signaler.clone()).await;
+        let client = reqwest::Client::new();
+
+        let opened = client.post(format!("{}/poll", base)).send().await.unwrap();
+        assert_eq!(opened.status().as_u16(), 201);
+        let opened: serde_json::Value = serde_json::from_str(&opened.text().await.unwrap()).unwrap();
+        let alice = format!("{}/poll/{}", base, opened["connection_id"].as_str().unwrap());
+        let new = json!({"type": "new", "data": {"id": "alice", "name": "alice", "user_agent": "test"}});
+        assert_eq!(post(&client, alice.clone(), new).await, StatusCode::ACCEPTED);
+
+        // Bob is connected the way the /ws handler connects WebSocket clients
+        let (bob, mut bob_events) = signaler.connect(None);
+        let new = json!({"type": "new", "data": {"id": "bob", "name": "bob", "user_agent": "test"}});
+        signaler.receive(&bob, serde_json::from_value(new).unwrap()).await.unwrap();
+
+        let description = json!({"type": "offer", "sdp": "v=0\r\n"});
+        let offer = json!({"from": "alice", "to": "bob", "session_id": "alice-bob", "description": description});
+        assert_eq!(post(&client, alice.clone(), json!({"type": "offer", "data": offer})).await, StatusCode::ACCEPTED);
+        let offer = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(Method::Offer(offer)) = bob_events.messages.recv().await {
+                    break offer;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(offer.from, "alice");
+        assert_eq!(offer.session_id, "alice-bob");
+
+        let answer = json!({"from": "bob", "to": "alice", "session_id": "alice-bob", "description": {"type": "answer", "sdp": "v=0\r\n"}});
+        signaler.receive(&bob, serde_json::from_value(json!({"type": "answer", "data": answer})).unwrap()).await.unwrap();
+        let answer = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let messages = client.get(&alice).send().await.unwrap().text().await.unwrap();
+                let messages: Vec<serde_json::Value> = serde_json::from_str(&messages).unwrap();
+                if let Some(answer) = messages.into_iter().find(|message| message["type"] == "answer") {
+                    break answer;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(answer["data"]["from"], "bob");
+        assert_eq!(answer["data"]["session_id"], "alice-bob");
+
+        assert_eq!(client.delete(&alice).send().await.unwrap().status().as_u16(), 204);
+        assert!(signaler.peers.get("alice").is_none());
+    }
+}
@@ -0,0 +1,203 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::modules::config::ConfigHandle;
+use crate::modules::signaling::{ShutdownPhase, Signaler};
+use crate::modules::turn_server::{self, TurnStatus};
+
+#[derive(Clone)]
+pub struct HealthState {
+    pub signaler: Arc<Signaler>,
+    /// False in TURN-only mode, where there is no signaling to check
+    pub signaling_enabled: bool,
+    pub turn: TurnStatus,
+    pub config: ConfigHandle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum CheckStatus {
+    Ok,
+    /// Degraded but still serving, does not fail readiness
+    Warn,
+    /// Check not applicable to this deployment
+    Skipped,
+    Fail,
+}
+
+#[derive(Debug, Serialize)]
+struct Check {
+    status: CheckStatus,
+    detail: String,
+}
+
+impl Check {
+    fn new(status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self { status, detail: detail.into() }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Readiness {
+    ready: bool,
+    signaling: Check,
+    turn: Check,
+    tls: Check,
+}
+
+/// `/healthz` (liveness) and `/readyz` (readiness) routes.
+pub fn router<S>(state: HealthState) -> Router<S> {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(state)
+}
+
+// Answering at all means the runtime is alive; dependencies are `/readyz`'s job
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+async fn readyz(State(state): State<HealthState>) -> Response {
+    let config = state.config.load_full();
+    let signaling = check_signaling(&state.signaler, state.signaling_enabled);
+    let turn = check_turn(state.turn, Duration::from_millis(config.health.turn_probe_timeout_ms)).await;
+    let tls = check_certificate(&config.general.cert, config.health.cert_warning_days).await;
+
+    let ready = [&signaling, &turn, &tls]
+        .iter()
+        .all(|check| check.status != CheckStatus::Fail);
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    if !ready {
+        warn!("Readiness check failed: signaling={}, turn={}, tls={}",
+              signaling.detail, turn.detail, tls.detail);
+    }
+
+    (status, Json(Readiness { ready, signaling, turn, tls })).into_response()
+}
+
+fn check_signaling(signaler: &Signaler, enabled: bool) -> Check {
+    let active = signaler.active_connections.load(Ordering::SeqCst);
+    let limit = signaler.config.load().signaling.max_connections;
+
+    match signaler.shutdown_phase() {
+        ShutdownPhase::Running if !enabled => Check::new(CheckStatus::Skipped, "signaling disabled"),
+        ShutdownPhase::Running if signaler.has_capacity() => {
+            Check::new(CheckStatus::Ok, format!("{}/{} connections", active, limit))
+        }
+        ShutdownPhase::Running => {
+            Check::new(CheckStatus::Fail, format!("at capacity, {}/{} connections", active, limit))
+        }
+        _ => Check::new(CheckStatus::Fail, "shutting down"),
+    }
+}
+
+async fn check_turn(status: TurnStatus, timeout: Duration) -> Check {
+    match status {
+        TurnStatus::Disabled => Check::new(CheckStatus::Skipped, "TURN server disabled"),
+        TurnStatus::NotRunning => Check::new(CheckStatus::Fail, "TURN server not running"),
+        TurnStatus::Listening(addr) => match turn_server::probe(addr, timeout).await {
+            Ok(rtt) => Check::new(
+                CheckStatus::Ok,
+                format!("STUN binding on {} answered in {}ms", addr, rtt.as_millis()),
+            ),
+            Err(e) => Check::new(CheckStatus::Fail, format!("STUN probe of {} failed: {}", addr, e)),
+        },
+    }
+}
+
+async fn check_certificate(path: &str, warning_days: i64) -> Check {
+    // TLS is usually terminated by a reverse proxy, so a missing cert is not an error
+    let pem = match tokio::fs::read(path).await {
+        Ok(pem) => pem,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Check::new(CheckStatus::Skipped, format!("{} not found", path));
+        }
+        Err(e) => return Check::new(CheckStatus::Fail, format!("cannot read {}: {}", path, e)),
+    };
+
+    let not_after = match certificate_expiry(&pem) {
+        Ok(not_after) => not_after,
+        Err(e) => return Check::new(CheckStatus::Fail, format!("cannot parse {}: {}", path, e)),
+    };
+
+    let remaining = not_after - Utc::now();
+    if remaining <= chrono::Duration::zero() {
+        Check::new(CheckStatus::Fail, format!("certificate expired at {}", not_after))
+    } else if remaining < chrono::Duration::days(warning_days) {
+        Check::new(
+            CheckStatus::Warn,
+            format!("certificate expires at {} ({} days left)", not_after, remaining.num_days()),
+        )
+    } else {
+        Check::new(CheckStatus::Ok, format!("certificate valid until {}", not_after))
+    }
+}
+
+fn certificate_expiry(pem: &[u8]) -> anyhow::Result<DateTime<Utc>> {
+    let (_, pem) = x509_parser::pem::parse_x509_pem(pem)?;
+    let certificate = pem.parse_x509()?;
+    let timestamp = certificate.validity().not_after.timestamp();
+    DateTime::from_timestamp(timestamp, 0)
+        .ok_or_else(|| anyhow::anyhow!("certificate expiry out of range"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::config::Config;
+    use crate::modules::turn_server::TurnServer;
+
+    #[tokio::test]
+    async fn failed_turn_startup_fails_readiness() {
+        // Hold the TURN port so the server cannot bind it
+        let taken = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+        let missing = std::env::temp_dir().join(format!("health-{}", uuid::Uuid::new_v4().simple()));
+        let config = Config::builder()
+            .public_ip("203.0.113.7")
+            .turn_port(taken.local_addr().unwrap().port())
+            .set("general", "cert", missing.join("cert.pem").to_str().unwrap())
+            .build()
+            .unwrap()
+            .into_handle();
+        let signaler = Signaler::builder().config_handle(config.clone()).build().unwrap();
+        let mut turn = TurnServer::builder(signaler.clone()).build();
+        assert!(turn.start().await.is_err());
+        assert_eq!(turn.status(), TurnStatus::NotRunning);
+
+        let state = HealthState {
+            signaler,
+            signaling_enabled: true,
+            turn: turn.status(),
+            config,
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let app = router::<()>(state);
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let response = reqwest::get(format!("{}/readyz", base)).await.unwrap();
+        assert_eq!(response.status().as_u16(), 503);
+        let readiness: serde_json::Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+        assert_eq!(readiness["ready"], false);
+        assert_eq!(readiness["turn"]["status"], "fail");
+        assert_eq!(readiness["turn"]["detail"], "TURN server not running");
+        assert_eq!(readiness["signaling"]["status"], "ok");
+        assert_eq!(readiness["tls"]["status"], "skipped");
+
+        // Liveness does not depend on TURN
+        let response = reqwest::get(format!("{}/healthz", base)).await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
@@ -0,0 +1,171 @@
+use async_trait::async_trait;
+use axum::http::HeaderMap;
+use std::net::SocketAddr;
+
+use crate::modules::signaling::{Byebye, CandidatePayload, DescriptionPayload, PeerInfo};
+
+/// What a hook decided about a connection or message.
+#[derive(Debug, Clone)]
+pub enum HookDecision<T> {
+    /// Continue with the original value
+    Allow,
+    /// Refuse it; the reason is sent back to the client
+    Reject(String),
+    /// Continue with this value instead of the original
+    Rewrite(T),
+}
+
+/// A WebSocket upgrade request, before any signaling happens.
+#[derive(Debug, Clone)]
+pub struct ConnectContext {
+    /// `None` when the app is served without connect info
+    pub remote_addr: Option<SocketAddr>,
+    pub headers: HeaderMap,
+}
+
+/// The connection a message arrived on.
+#[derive(Debug, Clone)]
+pub struct HookContext {
+    pub remote_addr: Option<SocketAddr>,
+    /// Registered peer, `None` until the connection sends `new`
+    pub peer_id: Option<String>,
+}
+
+/// Business logic plugged into the signaling flow, e.g. to veto calls, enrich
+/// `PeerInfo` or mirror events to another system.
+///
+/// Message hooks run after rate limiting and validation, and their rewrites
+/// are trusted as-is. Every method defaults to allowing, so implementations
+/// only override what they need.
+#[async_trait]
+pub trait SignalingHooks: Send + Sync {
+    /// Called before the WebSocket upgrade, opening an SSE, long-poll or WebTransport
+    /// connection or a WHIP/WHEP offer. A rejection answers 403 with the reason; a
+    /// rewrite replaces the context, e.g. to take the client address from a proxy
+    /// header, before per-IP limits apply.
+    async fn on_connect(&self, _connect: &ConnectContext) -> HookDecision<ConnectContext> {
+        HookDecision::Allow
+    }
+
+    async fn on_register(&self, _ctx: &HookContext, _peer: &PeerInfo) -> HookDecision<PeerInfo> {
+        HookDecision::Allow
+    }
+
+    async fn on_offer(
+        &self,
+        _ctx: &HookContext,
+        _offer: &DescriptionPayload,
+    ) -> HookDecision<DescriptionPayload> {
+        HookDecision::Allow
+    }
+
+    async fn on_answer(
+        &self,
+        _ctx: &HookContext,
+        _answer: &DescriptionPayload,
+    ) -> HookDecision<DescriptionPayload> {
+        HookDecision::Allow
+    }
+
+    async fn on_candidate(
+        &self,
+        _ctx: &HookContext,
+        _candidate: &CandidatePayload,
+    ) -> HookDecision<CandidatePayload> {
+        HookDecision::Allow
+    }
+
+    async fn on_bye(&self, _ctx: &HookContext, _bye: &Byebye) -> HookDecision<Byebye> {
+        HookDecision::Allow
+    }
+
+    /// Called once the connection is gone, so there is nothing left to veto.
+    /// `peer` is the registration it held, if any.
+    async fn on_disconnect(&self, _ctx: &HookContext, _peer: Option<&PeerInfo>) {}
+}
+
+/// Allows everything; the default when no hooks are registered.
+pub struct NoopHooks;
+
+impl SignalingHooks for NoopHooks {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::config::Config;
+    use crate::modules::signaling::{Connection, ConnectionEvents, Method, Signaler};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    /// Verifies names, keeps callers away from `carol` and lets everything else through.
+    struct Screening;
+
+    #[async_trait]
+    impl SignalingHooks for Screening {
+        async fn on_register(&self, ctx: &HookContext, peer: &PeerInfo) -> HookDecision<PeerInfo> {
+            assert!(ctx.peer_id.is_none());
+            let mut peer = peer.clone();
+            peer.name = format!("{} (verified)", peer.name);
+            HookDecision::Rewrite(peer)
+        }
+
+        async fn on_offer(&self, ctx: &HookContext, offer: &DescriptionPayload) -> HookDecision<DescriptionPayload> {
+            assert_eq!(ctx.peer_id.as_deref(), Some(offer.from.as_str()));
+            if offer.to == "carol" {
+                return HookDecision::Reject("carol does not take calls".to_string());
+            }
+            HookDecision::Allow
+        }
+    }
+
+    async fn client(signaler: &Signaler, id: &str) -> (Connection, ConnectionEvents) {
+        let (connection, events) = signaler.connect(None);
+        let new = json!({"type": "new", "data": {"id": id, "name": id, "user_agent": "test"}});
+        signaler.receive(&connection, serde_json::from_value(new).unwrap()).await.unwrap();
+        (connection, events)
+    }
+
+    async fn offer(signaler: &Signaler, connection: &Connection, to: &str) {
+        let offer = json!({"from": "alice", "to": to, "session_id": format!("alice-{}", to),
+                           "description": {"type": "offer", "sdp": "v=0\r\n"}});
+        signaler.receive(connection, serde_json::from_value(json!({"type": "offer", "data": offer})).unwrap()).await.unwrap();
+    }
+
+    fn drain(events: &mut ConnectionEvents) -> Vec<Method> {
+        std::iter::from_fn(|| events.messages.try_recv().ok()).collect()
+    }
+
+    #[tokio::test]
+    async fn hooks_reject_rewrite_or_pass_messages() {
+        let config = Config::builder().build().unwrap();
+        let signaler = Signaler::builder().config(config).hooks(Arc::new(Screening)).build().unwrap();
+        let (alice, mut alice_events) = client(&signaler, "alice").await;
+        let (_bob, mut bob_events) = client(&signaler, "bob").await;
+        let (_carol, mut carol_events) = client(&signaler, "carol").await;
+
+        // Rewritten registrations are what other peers see
+        assert_eq!(signaler.peers.get("alice").unwrap().info.name, "alice (verified)");
+
+        offer(&signaler, &alice, "carol").await;
+        let rejected = drain(&mut alice_events).into_iter().find_map(|message| match message {
+            Method::Error(e) => Some(e),
+            _ => None,
+        });
+        let rejected = rejected.expect("alice gets the rejection");
+        assert_eq!(rejected.request, "offer");
+        assert_eq!(rejected.reason, "carol does not take calls");
+        assert_eq!(rejected.rule.as_deref(), Some("hook"));
+        assert!(!drain(&mut carol_events).iter().any(|message| matches!(message, Method::Offer(_))));
+        assert!(signaler.store.session("alice-carol").await.unwrap().is_none());
+
+        offer(&signaler, &alice, "bob").await;
+        let forwarded = drain(&mut bob_events).into_iter().find_map(|message| match message {
+            Method::Offer(offer) => Some(offer),
+            _ => None,
+        });
+        let forwarded = forwarded.expect("bob gets the offer");
+        assert_eq!(forwarded.from, "alice");
+        assert_eq!(forwarded.description.sdp, "v=0\r\n");
+        assert!(!drain(&mut alice_events).iter().any(|message| matches!(message, Method::Error(_))));
+    }
+}
@@ -0,0 +1,223 @@
+use axum::{
+    extract::{ConnectInfo, Query, State, WebSocketUpgrade},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json},
+    routing::get,
+    Router,
+};
+use log::{error, info, warn};
+use serde::Deserialize;
+use std::{net::SocketAddr, sync::Arc};
+
+use crate::modules::codec;
+use crate::modules::fallback;
+use crate::modules::hooks::{ConnectContext, HookDecision};
+use crate::modules::origin::OriginPolicy;
+use crate::modules::rate_limit::ConnectionGuard;
+use crate::modules::signaling::{ShutdownPhase, Signaler};
+use crate::modules::whip;
+
+#[derive(Debug, Deserialize)]
+struct TurnQuery {
+    service: String,
+    username: String,
+}
+
+#[derive(Clone)]
+struct SignalingState {
+    signaler: Arc<Signaler>,
+}
+
+/// Signaling routes, `/ws`, `/api/turn`, the WHIP/WHEP endpoints and the SSE and
+/// long-poll fallback, nested under `prefix` (`""` or `"/"`
+/// mounts them at the root). Merge the result into any app; it needs no state
+/// from it. The app must be served with
+/// `into_make_service_with_connect_info::<SocketAddr>()` for per-IP limits.
+pub fn router<S>(signaler: Arc<Signaler>, prefix: &str) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let routes = Router::new()
+        .route("/ws", get(websocket_handler))
+        .route("/api/turn", get(turn_credentials_handler))
+        .with_state(SignalingState { signaler: signaler.clone() })
+        .merge(whip::router(signaler.clone()))
+        .merge(fallback::router(signaler));
+
+    match prefix.trim_matches('/') {
+        "" => routes,
+        prefix => Router::new().nest(&format!("/{}", prefix), routes),
+    }
+}
+
+/// `/metrics` in the Prometheus text format.
+pub fn metrics_router<S>(signaler: Arc<Signaler>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(SignalingState { signaler })
+}
+
+async fn websocket_handler(
+    ws: WebSocketUpgrade,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    State(state): State<SignalingState>,
+) -> impl IntoResponse {
+    // Apps served without connect info still accept WebSockets, without per-IP limits
+    let remote_addr = connect_info.map(|ConnectInfo(remote_addr)| remote_addr);
+    info!("New WebSocket connection attempt from {}", describe(remote_addr));
+
+    let (remote_addr, connection_guard) = match admit(&state.signaler, remote_addr, headers).await {
+        Ok(admitted) => admitted,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    // Larger frames are refused while reading, before they are buffered
+    let max_message_size = state.signaler.config.load().signaling.max_message_size;
+    ws.protocols(codec::SUBPROTOCOLS)
+        .max_message_size(max_message_size)
+        .max_frame_size(max_message_size)
+        .on_upgrade(move |socket| async move {
+            info!("WebSocket connection established, starting signaling handler");
+            state.signaler.handle_websocket(socket, remote_addr).await;
+            drop(connection_guard);
+        })
+        .into_response()
+}
+
+/// Checks a new signaling connection against shutdown, capacity, the origin
+/// allowlist, the `on_connect` hook and per-IP limits. Returns the client address,
+/// as rewritten by the hook, and the connection's per-IP slot. Connections
+/// without an address skip the per-IP limits.
+pub async fn admit(
+    signaler: &Signaler,
+    remote_addr: Option<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<(Option<SocketAddr>, Option<ConnectionGuard>), (StatusCode, String)> {
+    // Kept-alive HTTP connections can still ask for upgrades while draining
+    if !matches!(signaler.shutdown_phase(), ShutdownPhase::Running) {
+        return Err((StatusCode::SERVICE_UNAVAILABLE, "Server shutting down".to_string()));
+    }
+
+    if !signaler.has_capacity() {
+        warn!("Connection limit reached, rejecting connection from {}", describe(remote_addr));
+        return Err((StatusCode::SERVICE_UNAVAILABLE, "Server at capacity".to_string()));
+    }
+
+    // Browsers always send Origin; native clients usually don't and are let through
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        let origin = origin.to_str().unwrap_or_default();
+        let origins = OriginPolicy::new(&signaler.config.load().general.allowed_origins);
+        if !origins.allows(origin) {
+            warn!("Rejecting connection from disallowed origin: {}", origin);
+            return Err((StatusCode::FORBIDDEN, "Origin not allowed".to_string()));
+        }
+    }
+
+    let connect = ConnectContext { remote_addr, headers };
+    let remote_addr = match signaler.hooks.on_connect(&connect).await {
+        HookDecision::Allow => remote_addr,
+        HookDecision::Reject(reason) => {
+            warn!("Hook rejected connection from {}: {}", describe(remote_addr), reason);
+            return Err((StatusCode::FORBIDDEN, reason));
+        }
+        HookDecision::Rewrite(connect) => connect.remote_addr,
+    };
+
+    let Some(ip) = remote_addr.map(|remote_addr| remote_addr.ip()) else {
+        return Ok((None, None));
+    };
+    match signaler.rate_limiter.try_acquire(ip) {
+        Some(guard) => Ok((remote_addr, Some(guard))),
+        None => {
+            warn!("Too many connections from {}, rejecting connection", ip);
+            Err((StatusCode::TOO_MANY_REQUESTS, "Too many connections".to_string()))
+        }
+    }
+}
+
+pub(crate) fn describe(remote_addr: Option<SocketAddr>) -> String {
+    remote_addr.map_or_else(|| "an unknown address".to_string(), |remote_addr| remote_addr.to_string())
+}
+
+async fn turn_credentials_handler(
+    Query(params): Query<TurnQuery>,
+    State(state): State<SignalingState>,
+) -> impl IntoResponse {
+    info!("TURN credentials request for user: {}, service: {}", params.username, params.service);
+    
+    if params.service != "turn" {
+        warn!("Invalid service requested: {}", params.service);
+        return (StatusCode::BAD_REQUEST, Json("Invalid service")).into_response();
+    }
+
+    match state.signaler.generate_turn_credentials(&params.username).await {
+        Ok(credentials) => {
+            info!("Successfully generated TURN credentials for user: {}", params.username);
+            Json(credentials).into_response()
+        },
+        Err(e) => {
+            error!("Failed to generate TURN credentials for user {}: {}", params.username, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json("Failed to generate credentials")).into_response()
+        }
+    }
+}
+
+async fn metrics_handler(State(state): State<SignalingState>) -> impl IntoResponse {
+    match state.signaler.render_metrics().await {
+        Ok(body) => (
+            [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+            body,
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Failed to render metrics: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to render metrics").into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::config::Config;
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+
+    #[tokio::test]
+    async fn websockets_work_without_connect_info() {
+        let config = Config::builder().set("ratelimit", "max_connections_per_ip", "1").build().unwrap();
+        let signaler = Signaler::builder().config(config).build().unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/ws", listener.local_addr().unwrap());
+        let app = router::<()>(signaler.clone(), "");
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        // Without an address there is no per-IP slot to run out of
+        let mut clients = Vec::new();
+        for id in ["alice", "bob"] {
+            let (mut client, _) = tokio_tungstenite::connect_async(url.as_str()).await.unwrap();
+            let new = serde_json::json!({"type": "new", "data": {"id": id, "name": id, "user_agent": "test"}});
+            client.send(Message::Text(new.to_string())).await.unwrap();
+            clients.push(client);
+        }
+
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                let Some(Ok(Message::Text(text))) = clients[1].next().await else {
+                    panic!("connection closed");
+                };
+                let message: serde_json::Value = serde_json::from_str(&text).unwrap();
+                if message["type"] == "peers" && message["data"].as_array().unwrap().len() == 2 {
+                    break;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(signaler.peers.len(), 2);
+    }
+}
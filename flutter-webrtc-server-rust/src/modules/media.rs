@@ -0,0 +1,259 @@
+use anyhow::Result;
+use rtc::rtp;
+use std::net::IpAddr;
+use std::sync::Arc;
+use webrtc::peer_connection::{
+    register_default_interceptors, MediaEngine, PeerConnection, PeerConnectionBuilder, PeerConnectionEventHandler,
+    RTCConfigurationBuilder, RTCIceCandidate, RTCIceCandidateInit, RTCIceCandidateType, Registry,
+    SettingEngineBuilder,
+};
+use webrtc::rtp_transceiver::RtpSender;
+
+use crate::modules::config::Config;
+use crate::modules::signaling::IceCandidate;
+
+/// Creates a peer connection for a virtual peer that handles media itself, such
+/// as the SFU, supporting the codecs registered in `media_engine`.
+pub(crate) async fn peer_connection(
+    config: &Config,
+    mut media_engine: MediaEngine,
+    handler: Arc<dyn PeerConnectionEventHandler>,
+) -> Result<Arc<dyn PeerConnection>> {
+    let registry = register_default_interceptors(Registry::new(), &mut media_engine)?;
+
+    let mut settings = SettingEngineBuilder::new();
+    // Behind NAT, advertise the public address TURN is configured with
+    if config.turn.public_ip.parse::<IpAddr>().is_ok_and(|ip| !ip.is_loopback()) {
+        settings = settings.with_nat_1to1_ips(vec![config.turn.public_ip.clone()], RTCIceCandidateType::Srflx);
+    }
+
+    let pc = PeerConnectionBuilder::new()
+        .with_configuration(RTCConfigurationBuilder::new().build())
+        .with_media_engine(media_engine)
+        .with_interceptor_registry(registry)
+        .with_setting_engine(settings.build())
+        .with_handler(handler)
+        .with_udp_addrs(vec!["0.0.0.0:0".to_string()])
+        .build()
+        .await?;
+    Ok(Arc::new(pc))
+}
+
+/// The signaling form of a gathered candidate.
+pub(crate) fn ice_candidate(candidate: &RTCIceCandidate) -> Result<IceCandidate> {
+    let candidate = candidate.to_json()?;
+    // All media is bundled on the first m-line
+    Ok(IceCandidate {
+        candidate: candidate.candidate,
+        sdp_mid: None,
+        sdp_mline_index: Some(0),
+    })
+}
+
+pub(crate) fn candidate_init(candidate: IceCandidate) -> RTCIceCandidateInit {
+    RTCIceCandidateInit {
+        candidate: candidate.candidate,
+        sdp_mid: candidate.sdp_mid,
+        sdp_mline_index: candidate.sdp_mline_index,
+        ..Default::default()
+    }
+}
+
+/// Payload type `sender` negotiated for the codec, if negotiation finished.
+pub(crate) async fn negotiated_payload_type(sender: &Arc<dyn RtpSender>, mime_type: &str) -> Option<u8> {
+    let parameters = sender.get_parameters().await.ok()?;
+    parameters
+        .rtp_parameters
+        .codecs
+        .iter()
+        .find(|codec| codec.rtp_codec.mime_type.eq_ignore_ascii_case(mime_type))
+        .map(|codec| codec.payload_type)
+}
+
+/// Readies a received packet for sending on another track: its SSRC and payload
+/// type, without the header extensions, whose ids are negotiated per sender.
+pub(crate) fn retarget(packet: &mut rtp::Packet, ssrc: u32, payload_type: u8) {
+    packet.header.ssrc = ssrc;
+    packet.header.payload_type = payload_type;
+    packet.header.extension = false;
+    packet.header.extensions.clear();
+}
+
+/// A headless WebRTC client for tests of the virtual peers.
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use crate::modules::signaling::{
+        CandidatePayload, DescriptionPayload, Method, PeerInfo, SessionDescription, Signaler,
+    };
+    use rtc::media_stream::MediaStreamTrack;
+    use rtc::peer_connection::configuration::media_engine::MIME_TYPE_VP8;
+    use rtc::rtp_transceiver::rtp_sender::{RTCRtpCodec, RTCRtpCodingParameters, RTCRtpEncodingParameters, RtpCodecKind};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+    use webrtc::media_stream::track_local::static_rtp::TrackLocalStaticRTP;
+    use webrtc::media_stream::track_local::TrackLocal;
+    use webrtc::media_stream::track_remote::{TrackRemote, TrackRemoteEvent};
+    use webrtc::peer_connection::{RTCPeerConnectionIceEvent, RTCSessionDescription};
+
+    // Trickles candidates to the signaling task and counts the RTP packets of
+    // the tracks it receives
+    struct ClientHandler {
+        candidates: mpsc::UnboundedSender<IceCandidate>,
+        received: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl PeerConnectionEventHandler for ClientHandler {
+        async fn on_ice_candidate(&self, event: RTCPeerConnectionIceEvent) {
+            if let Ok(candidate) = ice_candidate(&event.candidate) {
+                let _ = self.candidates.send(candidate);
+            }
+        }
+
+        async fn on_track(&self, track: Arc<dyn TrackRemote>) {
+            let received = self.received.clone();
+            tokio::spawn(async move {
+                while let Some(event) = track.poll().await {
+                    match event {
+                        TrackRemoteEvent::OnRtpPacket(_) => {
+                            received.fetch_add(1, Ordering::SeqCst);
+                        }
+                        TrackRemoteEvent::OnEnded => break,
+                        _ => {}
+                    }
+                }
+            });
+        }
+    }
+
+    pub(crate) struct MediaClient {
+        pub pc: Arc<dyn PeerConnection>,
+        /// RTP packets received on all tracks
+        pub received: Arc<AtomicUsize>,
+    }
+
+    /// Registers `id` and calls `callee` with a peer connection set up by
+    /// `prepare`, then answers renegotiations and trickles candidates in the
+    /// background. Returns what `prepare` did, e.g. the data channels it opened.
+    pub(crate) async fn call<T, F, Fut>(signaler: Arc<Signaler>, id: &str, callee: &str, prepare: F) -> (MediaClient, T)
+    where
+        F: FnOnce(Arc<dyn PeerConnection>) -> Fut,
+        Fut: std::future::Future<Output = T>,
+    {
+        let (connection, mut events) = signaler.connect(None);
+        let register = PeerInfo { id: id.to_string(), name: id.to_string(), user_agent: "test".to_string() };
+        signaler.receive(&connection, Method::New(register)).await.unwrap();
+
+        let (candidates, mut candidates_rx) = mpsc::unbounded_channel();
+        let received = Arc::new(AtomicUsize::new(0));
+        let handler = Arc::new(ClientHandler { candidates, received: received.clone() });
+        let mut media_engine = MediaEngine::default();
+        media_engine.register_default_codecs().unwrap();
+        let config = Config::builder().build().unwrap();
+        let pc = peer_connection(&config, media_engine, handler).await.unwrap();
+        let prepared = prepare(pc.clone()).await;
+
+        let session_id = format!("{}-{}", id, callee);
+        let offer = pc.create_offer(None).await.unwrap();
+        pc.set_local_description(offer.clone()).await.unwrap();
+        let offer = description(id, callee, &session_id, "offer", offer.sdp);
+        signaler.receive(&connection, Method::Offer(offer)).await.unwrap();
+
+        let (id, callee) = (id.to_string(), callee.to_string());
+        let client_pc = pc.clone();
+        tokio::spawn(async move {
+            let pc = client_pc;
+            loop {
+                tokio::select! {
+                    Some(candidate) = candidates_rx.recv() => {
+                        let candidate = Method::Candidate(CandidatePayload {
+                            from: id.clone(),
+                            to: callee.clone(),
+                            session_id: session_id.clone(),
+                            candidate,
+                            extra: serde_json::Map::new(),
+                        });
+                        let _ = signaler.receive(&connection, candidate).await;
+                    }
+                    message = events.messages.recv() => match message {
+                        Some(Method::Offer(offer)) => {
+                            let sdp = RTCSessionDescription::offer(offer.description.sdp).unwrap();
+                            pc.set_remote_description(sdp).await.unwrap();
+                            let answer = pc.create_answer(None).await.unwrap();
+                            pc.set_local_description(answer.clone()).await.unwrap();
+                            let answer = description(&id, &callee, &session_id, "answer", answer.sdp);
+                            let _ = signaler.receive(&connection, Method::Answer(answer)).await;
+                        }
+                        Some(Method::Answer(answer)) => {
+                            let sdp = RTCSessionDescription::answer(answer.description.sdp).unwrap();
+                            pc.set_remote_description(sdp).await.unwrap();
+                        }
+                        Some(Method::Candidate(candidate)) => {
+                            let _ = pc.add_ice_candidate(candidate_init(candidate.candidate)).await;
+                        }
+                        Some(_) => {}
+                        None => break,
+                    },
+                }
+            }
+        });
+        (MediaClient { pc, received }, prepared)
+    }
+
+    fn description(from: &str, to: &str, session_id: &str, sdp_type: &str, sdp: String) -> DescriptionPayload {
+        DescriptionPayload {
+            from: from.to_string(),
+            to: to.to_string(),
+            session_id: session_id.to_string(),
+            description: SessionDescription { sdp, sdp_type: sdp_type.to_string() },
+            extra: serde_json::Map::new(),
+        }
+    }
+
+    /// A VP8 track sending with `ssrc`.
+    pub(crate) fn video_track(stream_id: &str, ssrc: u32) -> Arc<TrackLocalStaticRTP> {
+        Arc::new(TrackLocalStaticRTP::new(MediaStreamTrack::new(
+            stream_id.to_string(),
+            "camera".to_string(),
+            "camera".to_string(),
+            RtpCodecKind::Video,
+            vec![RTCRtpEncodingParameters {
+                rtp_coding_parameters: RTCRtpCodingParameters { ssrc: Some(ssrc), ..Default::default() },
+                codec: RTCRtpCodec { mime_type: MIME_TYPE_VP8.to_string(), clock_rate: 90000, ..Default::default() },
+                ..Default::default()
+            }],
+        )))
+    }
+
+    /// Writes packets to `track` until `received` counts `count` of them
+    /// somewhere else. Returns false if that took too long.
+    pub(crate) async fn send_until_received(
+        track: &TrackLocalStaticRTP,
+        ssrc: u32,
+        received: &AtomicUsize,
+        count: usize,
+    ) -> bool {
+        let sending = async {
+            let mut sequence_number = 0u16;
+            while received.load(Ordering::SeqCst) < count {
+                let packet = rtp::Packet {
+                    header: rtp::header::Header {
+                        version: 2,
+                        payload_type: 96,
+                        sequence_number,
+                        timestamp: sequence_number as u32 * 3000,
+                        ssrc,
+                        ..Default::default()
+                    },
+                    payload: vec![0x10, 0x00, 0x00, 0x9d].into(),
+                };
+                let _ = track.write_rtp(packet).await;
+                sequence_number = sequence_number.wrapping_add(1);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(15), sending).await.is_ok()
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::Result;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+
+/// Prometheus metrics shared by the signaling server and the TURN relay.
+pub struct Metrics {
+    registry: Registry,
+
+    // Signaling
+    pub connected_peers: IntGauge,
+    pub registrations: IntCounter,
+    pub messages_received: IntCounterVec,
+    pub messages_rejected: IntCounterVec,
+    pub delivery_failures: IntCounterVec,
+    pub rate_limit_breaches: IntCounterVec,
+    pub call_sessions: IntGaugeVec,
+    pub call_setup_seconds: Histogram,
+    pub webhook_deliveries: IntCounterVec,
+    pub webhook_queue_depth: IntGauge,
+
+    // TURN
+    pub turn_allocations: IntGauge,
+    pub turn_relayed_bytes: IntCounter,
+    pub turn_relayed_packets: IntCounter,
+    pub turn_stun_requests: IntCounter,
+    pub turn_permissions: IntGauge,
+    pub turn_channels: IntGauge,
+    pub turn_auth_failures: IntCounter,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self> {
+        let registry = Registry::new();
+
+        let connected_peers = IntGauge::new(
+            "signaling_connected_peers",
+            "Peers currently registered with the signaling server",
+        )?;
+        let registrations = IntCounter::new(
+            "signaling_registrations_total",
+            "Peer registrations (`new` messages) accepted",
+        )?;
+        let messages_received = IntCounterVec::new(
+            Opts::new("signaling_messages_received_total", "Inbound signaling messages by type"),
+            &["type"],
+        )?;
+        let messages_rejected = IntCounterVec::new(
+            Opts::new(
+                "signaling_messages_rejected_total",
+                "Inbound messages rejected by validation or hooks, by rule",
+            ),
+            &["rule"],
+        )?;
+        let delivery_failures = IntCounterVec::new(
+            Opts::new(
+                "signaling_delivery_failures_total",
+                "Messages that could not be delivered to their recipient, by type",
+            ),
+            &["type"],
+        )?;
+        let rate_limit_breaches = IntCounterVec::new(
+            Opts::new(
+                "signaling_rate_limit_breaches_total",
+                "Rate limit breaches by message type, or `connections` for rejected upgrades",
+            ),
+            &["type"],
+        )?;
+        let call_sessions = IntGaugeVec::new(
+            Opts::new("signaling_call_sessions", "Call sessions by status"),
+            &["status"],
+        )?;
+        let call_setup_seconds = Histogram::with_opts(
+            HistogramOpts::new(
+                "signaling_call_setup_seconds",
+                "Time from offer to answer for answered calls",
+            )
+            .buckets(vec![0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 30.0, 60.0]),
+        )?;
+
+        let webhook_deliveries = IntCounterVec::new(
+            Opts::new(
+                "signaling_webhook_deliveries_total",
+                "Webhook delivery attempts by result: delivered, retried, failed or dropped",
+            ),
+            &["result"],
+        )?;
+        let webhook_queue_depth = IntGauge::new(
+            "signaling_webhook_queue_depth",
+            "Webhook events waiting for delivery, across all endpoints",
+        )?;
+
+        let turn_allocations = IntGauge::new("turn_allocations", "Active TURN allocations")?;
+        let turn_permissions = IntGauge::new("turn_permissions", "Active TURN permissions")?;
+        let turn_channels = IntGauge::new("turn_channels", "Active TURN channel bindings")?;
+        let turn_relayed_bytes =
+            IntCounter::new("turn_relayed_bytes_total", "Bytes relayed by the TURN server")?;
+        let turn_relayed_packets =
+            IntCounter::new("turn_relayed_packets_total", "Packets relayed by the TURN server")?;
+        let turn_auth_failures = IntCounter::new(
+            "turn_auth_failures_total",
+            "TURN requests rejected for bad or expired credentials",
+        )?;
+        let turn_stun_requests = IntCounter::new(
+            "turn_stun_requests_total",
+            "STUN/TURN requests received on the TURN socket",
+        )?;
+
+        registry.register(Box::new(connected_peers.clone()))?;
+        registry.register(Box::new(registrations.clone()))?;
+        registry.register(Box::new(messages_received.clone()))?;
+        registry.register(Box::new(messages_rejected.clone()))?;
+        registry.register(Box::new(delivery_failures.clone()))?;
+        registry.register(Box::new(rate_limit_breaches.clone()))?;
+        registry.register(Box::new(call_sessions.clone()))?;
+        registry.register(Box::new(call_setup_seconds.clone()))?;
+        registry.register(Box::new(webhook_deliveries.clone()))?;
+        registry.register(Box::new(webhook_queue_depth.clone()))?;
+        registry.register(Box::new(turn_allocations.clone()))?;
+        registry.register(Box::new(turn_permissions.clone()))?;
+        registry.register(Box::new(turn_channels.clone()))?;
+        registry.register(Box::new(turn_relayed_bytes.clone()))?;
+        registry.register(Box::new(turn_relayed_packets.clone()))?;
+        registry.register(Box::new(turn_auth_failures.clone()))?;
+        registry.register(Box::new(turn_stun_requests.clone()))?;
+
+        Ok(Self {
+            registry,
+            connected_peers,
+            registrations,
+            messages_received,
+            messages_rejected,
+            delivery_failures,
+            rate_limit_breaches,
+            call_sessions,
+            call_setup_seconds,
+            webhook_deliveries,
+            webhook_queue_depth,
+            turn_allocations,
+            turn_permissions,
+            turn_channels,
+            turn_relayed_bytes,
+            turn_relayed_packets,
+            turn_auth_failures,
+            turn_stun_requests,
+        })
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> Result<String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
@@ -1,3 +1,31 @@
+pub mod admin;
+pub mod bots;
+pub mod cdr;
+pub mod cli;
+pub mod cluster;
+pub mod cluster_mesh;
+pub mod cluster_redis;
+pub mod codec;
 pub mod config;
+pub mod config_source;
+pub mod echo;
+pub mod fallback;
+pub mod health;
+pub mod hooks;
+pub mod http;
+pub mod media;
+pub mod metrics;
+pub mod origin;
+pub mod protocol;
+pub mod rate_limit;
+pub mod reload;
+pub mod server;
+pub mod sfu;
 pub mod signaling;
-pub mod turn_server;
\ No newline at end of file
+pub mod store;
+pub mod turn_server;
+pub mod util;
+pub mod validation;
+pub mod webhooks;
+pub mod webtransport;
+pub mod whip;
\ No newline at end of file
@@ -0,0 +1,148 @@
+/// Browser origins allowed to use the HTTP API and open signaling connections.
+///
+/// Patterns are either `*` (any origin), an exact origin such as
+/// `https://app.example.com`, or a wildcard subdomain such as
+/// `https://*.example.com`. Patterns without a scheme match any scheme. Like
+/// browsers comparing origins, an exact origin without a port only matches the
+/// scheme's default port, while a wildcard without one (`https://*.example.com`
+/// rather than `https://*.example.com:8443`) matches any port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OriginPolicy {
+    patterns: Vec<String>,
+}
+
+impl OriginPolicy {
+    pub fn new(patterns: &[String]) -> Self {
+        Self {
+            patterns: patterns
+                .iter()
+                .map(|p| p.trim().trim_end_matches('/').to_ascii_lowercase())
+                .filter(|p| !p.is_empty())
+                .collect(),
+        }
+    }
+
+    pub fn allows_any(&self) -> bool {
+        self.patterns.iter().any(|p| p == "*")
+    }
+
+    pub fn allows(&self, origin: &str) -> bool {
+        let origin = origin.trim_end_matches('/').to_ascii_lowercase();
+        self.allows_any() || self.patterns.iter().any(|p| matches_pattern(p, &origin))
+    }
+}
+
+fn matches_pattern(pattern: &str, origin: &str) -> bool {
+    let (pattern_scheme, pattern_host) = split_scheme(pattern);
+    let (origin_scheme, origin_host) = split_scheme(origin);
+    let (pattern_host, pattern_port) = split_port(pattern_host);
+    let (origin_host, origin_port) = split_port(origin_host);
+
+    if pattern_scheme.is_some() && pattern_scheme != origin_scheme {
+        return false;
+    }
+
+    match pattern_host.strip_prefix("*.") {
+        // `*.example.com` matches `a.example.com` and `a.b.example.com`, not `example.com`
+        Some(suffix) => {
+            (pattern_port.is_none() || pattern_port == origin_port)
+                && origin_host
+                    .strip_suffix(suffix)
+                    .is_some_and(|label| label.len() > 1 && label.ends_with('.'))
+        }
+        None => {
+            let default_port = origin_scheme.and_then(default_port);
+            pattern_host == origin_host && pattern_port.or(default_port) == origin_port.or(default_port)
+        }
+    }
+}
+
+fn default_port(scheme: &str) -> Option<&'static str> {
+    match scheme {
+        "http" | "ws" => Some("80"),
+        "https" | "wss" => Some("443"),
+        _ => None,
+    }
+}
+
+fn split_scheme(value: &str) -> (Option<&str>, &str) {
+    match value.split_once("://") {
+        Some((scheme, rest)) => (Some(scheme), rest),
+        None => (None, value),
+    }
+}
+
+// `[::1]:8080` splits into `[::1]` and `8080`, a bare `::1` has no port
+fn split_port(host: &str) -> (&str, Option<&str>) {
+    match host.rsplit_once(':') {
+        Some((name, port))
+            if !port.is_empty()
+                && port.bytes().all(|b| b.is_ascii_digit())
+                && (!name.contains(':') || name.ends_with(']')) =>
+        {
+            (name, Some(port))
+        }
+        _ => (host, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(patterns: &[&str]) -> OriginPolicy {
+        OriginPolicy::new(&patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn exact_origins() {
+        let origins = policy(&["https://app.example.com/"]);
+        assert!(origins.allows("https://app.example.com"));
+        assert!(origins.allows("HTTPS://App.Example.com/"));
+        assert!(!origins.allows("http://app.example.com"));
+        assert!(!origins.allows("https://app.example.com.evil.net"));
+    }
+
+    #[test]
+    fn wildcard_subdomains() {
+        assert!(matches_pattern("https://*.example.com", "https://a.example.com"));
+        assert!(matches_pattern("https://*.example.com", "https://a.b.example.com"));
+        assert!(!matches_pattern("https://*.example.com", "https://example.com"));
+        assert!(!matches_pattern("https://*.example.com", "https://badexample.com"));
+        assert!(!matches_pattern("https://*.example.com", "http://a.example.com"));
+        // No scheme, any scheme
+        assert!(matches_pattern("*.example.com", "http://a.example.com"));
+    }
+
+    #[test]
+    fn ports() {
+        // No port in a wildcard, any port
+        assert!(matches_pattern("https://*.example.com", "https://a.example.com:8443"));
+        assert!(matches_pattern("*.example.com:8443", "https://a.example.com:8443"));
+        assert!(!matches_pattern("*.example.com:8443", "https://a.example.com:9443"));
+        assert!(!matches_pattern("*.example.com:8443", "https://a.example.com"));
+        assert!(matches_pattern("http://[::1]:8080", "http://[::1]:8080"));
+        assert!(!matches_pattern("http://[::1]:8080", "http://[::1]:9090"));
+    }
+
+    #[test]
+    fn exact_origins_without_a_port_mean_the_default_port() {
+        assert!(matches_pattern("http://localhost", "http://localhost"));
+        assert!(matches_pattern("http://localhost", "http://localhost:80"));
+        assert!(matches_pattern("https://app.example.com:443", "https://app.example.com"));
+        assert!(!matches_pattern("http://localhost", "http://localhost:3000"));
+        assert!(!matches_pattern("https://app.example.com", "https://app.example.com:8443"));
+        assert!(!matches_pattern("http://[::1]", "http://[::1]:8080"));
+        // Without a scheme, the origin's default port
+        assert!(matches_pattern("app.example.com", "https://app.example.com:443"));
+        assert!(!matches_pattern("app.example.com", "http://app.example.com:8080"));
+    }
+
+    #[test]
+    fn star_allows_everything() {
+        let origins = policy(&["https://app.example.com", "*"]);
+        assert!(origins.allows_any());
+        assert!(origins.allows("null"));
+        assert!(!policy(&[]).allows("https://app.example.com"));
+    }
+}
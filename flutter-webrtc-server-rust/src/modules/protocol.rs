@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// The original Go-compatible protocol. Clients that never send `hello` speak this.
+pub const LEGACY_PROTOCOL_VERSION: u32 = 1;
+
+/// Highest protocol version understood by this server.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Optional protocol features a connection can opt into via `hello`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Feature {
+    Deltas,
+    Acks,
+    Rooms,
+    Binary,
+    #[serde(other)]
+    Unknown,
+}
+
+/// Features this server is able to honour when a client asks for them.
+pub const SERVER_FEATURES: &[Feature] = &[Feature::Acks, Feature::Binary];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hello {
+    pub version: u32,
+    #[serde(default)]
+    pub features: Vec<Feature>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ack {
+    pub request: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+}
+
+/// What has been agreed with a single connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    pub version: u32,
+    pub features: BTreeSet<Feature>,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            version: LEGACY_PROTOCOL_VERSION,
+            features: BTreeSet::new(),
+        }
+    }
+}
+
+impl Capabilities {
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.features.contains(&feature)
+    }
+
+    pub fn to_hello(&self) -> Hello {
+        Hello {
+            version: self.version,
+            features: self.features.iter().copied().collect(),
+        }
+    }
+}
+
+/// Picks the highest common version and the intersection of requested and
+/// supported features. Returns the rejection reason if no common version exists.
+pub fn negotiate(client: &Hello) -> Result<Capabilities, String> {
+    if client.version < LEGACY_PROTOCOL_VERSION {
+        return Err(format!(
+            "Unsupported protocol version {} (server supports {}..={})",
+            client.version, LEGACY_PROTOCOL_VERSION, PROTOCOL_VERSION
+        ));
+    }
+
+    let version = client.version.min(PROTOCOL_VERSION);
+
+    // Version 1 has no optional features, so anything requested is ignored
+    let features = if version > LEGACY_PROTOCOL_VERSION {
+        client
+            .features
+            .iter()
+            .copied()
+            .filter(|feature| SERVER_FEATURES.contains(feature))
+            .collect()
+    } else {
+        BTreeSet::new()
+    };
+
+    Ok(Capabilities { version, features })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hello(version: u32, features: &[Feature]) -> Hello {
+        Hello { version, features: features.to_vec() }
+    }
+
+    #[test]
+    fn negotiate_keeps_supported_features() {
+        let capabilities = negotiate(&hello(2, &[Feature::Acks, Feature::Rooms, Feature::Unknown])).unwrap();
+        assert_eq!(capabilities.version, 2);
+        assert_eq!(capabilities.features, BTreeSet::from([Feature::Acks]));
+    }
+
+    #[test]
+    fn negotiate_caps_newer_clients_at_the_server_version() {
+        let capabilities = negotiate(&hello(PROTOCOL_VERSION + 3, &[Feature::Binary])).unwrap();
+        assert_eq!(capabilities.version, PROTOCOL_VERSION);
+        assert!(capabilities.supports(Feature::Binary));
+    }
+
+    #[test]
+    fn negotiate_ignores_features_on_the_legacy_version() {
+        let capabilities = negotiate(&hello(LEGACY_PROTOCOL_VERSION, &[Feature::Acks])).unwrap();
+        assert_eq!(capabilities, Capabilities::default());
+    }
+
+    #[test]
+    fn negotiate_rejects_versions_below_legacy() {
+        assert!(negotiate(&hello(0, &[])).is_err());
+    }
+
+    #[test]
+    fn unknown_features_deserialize() {
+        let hello: Hello = serde_json::from_str(r#"{"version": 2, "features": ["acks", "teleport"]}"#).unwrap();
+        assert_eq!(hello.features, [Feature::Acks, Feature::Unknown]);
+    }
+}
@@ -0,0 +1,242 @@
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::modules::config::{BucketLimit, ConfigHandle};
+use crate::modules::metrics::Metrics;
+
+/// Breach counter key used for rejected connections.
+pub const CONNECTIONS_BREACH: &str = "connections";
+
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    limit: BucketLimit,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: BucketLimit) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    fn try_take(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.rate).min(self.limit.burst);
+        self.refilled_at = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Token buckets for a single connection, one per message type. Limits are read
+/// from the live config, so a reload also applies to open connections.
+#[derive(Debug, Clone)]
+pub struct ConnectionLimiter {
+    config: ConfigHandle,
+    buckets: HashMap<&'static str, TokenBucket>,
+    violations: u32,
+    exempt: bool,
+}
+
+impl ConnectionLimiter {
+    pub fn new(config: ConfigHandle) -> Self {
+        Self {
+            config,
+            buckets: HashMap::new(),
+            violations: 0,
+            exempt: false,
+        }
+    }
+
+    /// A limiter that lets everything through, for peers run by the server itself.
+    pub fn exempt(config: ConfigHandle) -> Self {
+        Self {
+            exempt: true,
+            ..Self::new(config)
+        }
+    }
+
+    /// Consumes a token for `request_type`. Returns false if the bucket is empty.
+    pub fn check(&mut self, request_type: &'static str) -> bool {
+        if self.exempt {
+            return true;
+        }
+        let limit = self.config.load().signaling.rate_limit.limit_for(request_type);
+        let bucket = self
+            .buckets
+            .entry(request_type)
+            .or_insert_with(|| TokenBucket::new(limit));
+        if bucket.limit != limit {
+            bucket.limit = limit;
+            bucket.tokens = bucket.tokens.min(limit.burst);
+        }
+
+        if bucket.try_take() {
+            return true;
+        }
+        self.violations += 1;
+        false
+    }
+
+    /// True once the connection has broken its limits too often to be kept.
+    pub fn exhausted(&self) -> bool {
+        self.violations > self.config.load().signaling.rate_limit.max_violations
+    }
+}
+
+/// Server-wide rate limiting state: connections per source IP and breach counters.
+pub struct RateLimiter {
+    config: ConfigHandle,
+    connections: Arc<DashMap<IpAddr, usize>>,
+    metrics: Arc<Metrics>,
+}
+
+impl RateLimiter {
+    pub fn new(config: ConfigHandle, metrics: Arc<Metrics>) -> Self {
+        Self {
+            config,
+            connections: Arc::new(DashMap::new()),
+            metrics,
+        }
+    }
+
+    /// Reserves a connection slot for `ip`, released when the guard is dropped.
+    pub fn try_acquire(&self, ip: IpAddr) -> Option<ConnectionGuard> {
+        let max_connections = self.config.load().signaling.rate_limit.max_connections_per_ip;
+        let mut count = self.connections.entry(ip).or_insert(0);
+        if *count >= max_connections {
+            drop(count);
+            self.record_breach(CONNECTIONS_BREACH);
+            return None;
+        }
+        *count += 1;
+
+        Some(ConnectionGuard {
+            ip,
+            connections: self.connections.clone(),
+        })
+    }
+
+    pub fn connection_limiter(&self) -> ConnectionLimiter {
+        ConnectionLimiter::new(self.config.clone())
+    }
+
+    pub fn record_breach(&self, kind: &str) {
+        self.metrics.rate_limit_breaches.with_label_values(&[kind]).inc();
+    }
+}
+
+#[derive(Debug)]
+pub struct ConnectionGuard {
+    ip: IpAddr,
+    connections: Arc<DashMap<IpAddr, usize>>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let now_empty = match self.connections.get_mut(&self.ip) {
+            Some(mut count) => {
+                *count = count.saturating_sub(1);
+                *count == 0
+            }
+            None => false,
+        };
+        if now_empty {
+            self.connections.remove_if(&self.ip, |_, count| *count == 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::config::Config;
+    use std::time::Duration;
+
+    fn config(entries: &[(&str, &str)]) -> ConfigHandle {
+        let builder = entries
+            .iter()
+            .fold(Config::builder(), |builder, (key, value)| builder.set("ratelimit", key, value));
+        builder.build().unwrap().into_handle()
+    }
+
+    #[test]
+    fn bucket_allows_a_burst_then_refills_at_the_rate() {
+        let mut bucket = TokenBucket::new(BucketLimit { rate: 2.0, burst: 3.0 });
+        assert!((0..3).all(|_| bucket.try_take()));
+        assert!(!bucket.try_take());
+
+        // Half a second at 2/s is one token
+        bucket.refilled_at -= Duration::from_millis(500);
+        assert!(bucket.try_take());
+        assert!(!bucket.try_take());
+
+        // Refills never exceed the burst
+        bucket.refilled_at -= Duration::from_secs(60);
+        assert!((0..3).all(|_| bucket.try_take()));
+        assert!(!bucket.try_take());
+    }
+
+    #[test]
+    fn limits_apply_per_message_type() {
+        let mut limiter = ConnectionLimiter::new(config(&[("offer", "0.001,1"), ("default", "0.001,2")]));
+        assert!(limiter.check("offer"));
+        assert!(!limiter.check("offer"));
+        assert!(limiter.check("answer"));
+        assert!(limiter.check("answer"));
+        assert!(!limiter.check("answer"));
+    }
+
+    #[test]
+    fn connection_is_exhausted_after_max_violations() {
+        let mut limiter = ConnectionLimiter::new(config(&[("offer", "0.001,1"), ("max_violations", "1")]));
+        assert!(limiter.check("offer"));
+        assert!(!limiter.check("offer"));
+        assert!(!limiter.exhausted());
+        assert!(!limiter.check("offer"));
+        assert!(limiter.exhausted());
+
+        let mut exempt = ConnectionLimiter::exempt(config(&[("offer", "0.001,1")]));
+        assert!((0..10).all(|_| exempt.check("offer")));
+    }
+
+    #[test]
+    fn reloaded_limits_apply_to_open_connections() {
+        let handle = config(&[("offer", "0.001,5")]);
+        let mut limiter = ConnectionLimiter::new(handle.clone());
+        assert!(limiter.check("offer"));
+
+        let reloaded = Config::builder().set("ratelimit", "offer", "0.001,1").build().unwrap();
+        handle.store(Arc::new(reloaded));
+        // The lower burst caps the tokens left over from the old limit
+        assert!(limiter.check("offer"));
+        assert!(!limiter.check("offer"));
+    }
+
+    #[test]
+    fn connections_per_ip_are_released_with_their_guard() {
+        let limiter = RateLimiter::new(config(&[("max_connections_per_ip", "2")]), Arc::new(Metrics::new().unwrap()));
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        let first = limiter.try_acquire(ip).unwrap();
+        let _second = limiter.try_acquire(ip).unwrap();
+        assert!(limiter.try_acquire(ip).is_none());
+        assert!(limiter.try_acquire("192.0.2.2".parse().unwrap()).is_some());
+
+        drop(first);
+        assert!(limiter.try_acquire(ip).is_some());
+        assert_eq!(limiter.metrics.rate_limit_breaches.with_label_values(&[CONNECTIONS_BREACH]).get(), 1);
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::Result;
+use log::{info, warn};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+
+use crate::modules::config::{Config, ConfigHandle};
+
+/// Settings only read when the listeners start. A reload keeps their running
+/// value and reports the change until the next restart.
+const RESTART_REQUIRED: &[&str] = &[
+    "general.domain",
+    "general.cert",
+    "general.key",
+    "general.bind",
+    "general.port",
+    "general.html_root",
+    "turn.port",
+    "webhooks.urls",
+    "webhooks.queue_dir",
+    "cdr.path",
+    "store.path",
+    "cluster.backend",
+    "cluster.node_id",
+    "cluster.redis_url",
+    "cluster.key_prefix",
+    "cluster.heartbeat_ms",
+    "cluster.node_timeout_ms",
+    "cluster.mesh_listen",
+    "cluster.mesh_peers",
+    "cluster.mesh_secret",
+    "webtransport.listen",
+    "sfu.peer_id",
+    "echo.peer_id",
+    "bots.peers",
+];
+
+#[derive(Debug, Default, Serialize)]
+pub struct ReloadReport {
+    /// Changed settings now in effect
+    pub applied: Vec<String>,
+    /// Changed settings ignored until the server restarts
+    pub restart_required: Vec<String>,
+}
+
+type Loader = Box<dyn Fn() -> Result<Config> + Send + Sync>;
+
+/// Re-reads the configuration from its original sources and swaps it into the
+/// live `ConfigHandle`.
+pub struct ConfigReloader {
+    handle: ConfigHandle,
+    load: Loader,
+    // Serializes reloads so concurrent triggers diff against the config they replace
+    lock: Mutex<()>,
+}
+
+impl ConfigReloader {
+    pub fn new(handle: ConfigHandle, load: impl Fn() -> Result<Config> + Send + Sync + 'static) -> Self {
+        Self {
+            handle,
+            load: Box::new(load),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Loads and validates the new configuration and applies it. On error the
+    /// running configuration is left untouched.
+    pub fn reload(&self) -> Result<ReloadReport> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+
+        let current = self.handle.load_full();
+        let (config, report) = merge(&current, (self.load)()?)?;
+        self.handle.store(Arc::new(config));
+
+        info!("Configuration reloaded, applied: {:?}", report.applied);
+        if !report.restart_required.is_empty() {
+            warn!("Configuration changes that need a restart: {:?}", report.restart_required);
+        }
+        Ok(report)
+    }
+}
+
+// Compares section by section through the serialized form, so new settings are
+// picked up without listing them here
+fn merge(current: &Config, loaded: Config) -> Result<(Config, ReloadReport)> {
+    let old = serde_json::to_value(current)?;
+    let mut new = serde_json::to_value(&loaded)?;
+    let mut report = ReloadReport::default();
+
+    for (section, fields) in old.as_object().into_iter().flatten() {
+        for (key, old_value) in fields.as_object().into_iter().flatten() {
+            let Some(new_value) = new.get_mut(section).and_then(|s| s.get_mut(key)) else {
+                continue;
+            };
+            if new_value == old_value {
+                continue;
+            }
+
+            let name = format!("{}.{}", section, key);
+            if RESTART_REQUIRED.contains(&name.as_str()) {
+                *new_value = old_value.clone();
+                report.restart_required.push(name);
+            } else {
+                report.applied.push(name);
+            }
+        }
+    }
+
+    let mut config: Config = serde_json::from_value(new)?;
+
+    // The admin API is mounted at startup: its token can change, but not appear or go away
+    if current.admin.token.is_some() != config.admin.token.is_some() {
+        config.admin.token = current.admin.token.clone();
+        report.applied.retain(|name| name != "admin.token");
+        report.restart_required.push("admin.token".to_string());
+    }
+
+    Ok((config, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reloads_keep_the_running_config_on_errors() {
+        let dir = std::env::temp_dir().join(format!("reload-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.ini");
+        std::fs::write(&path, "[turn]\nrealm = first\n").unwrap();
+
+        let load = {
+            let path = path.clone();
+            move || Ok(Config::builder().file(&path)?.build()?)
+        };
+        let handle = load().unwrap().into_handle();
+        let reloader = ConfigReloader::new(handle.clone(), load);
+
+        std::fs::write(&path, "[turn]\nrealm = second\n").unwrap();
+        let report = reloader.reload().unwrap();
+        assert_eq!(report.applied, vec!["turn.realm".to_string()]);
+        assert_eq!(handle.load().turn.realm, "second");
+
+        // Neither a file that does not parse nor an invalid value replaces it
+        let running = handle.load_full();
+        for broken in ["[turn\nrealm = third\n", "[turn]\nrealm = third\ncredential_ttl = soon\n"] {
+            std::fs::write(&path, broken).unwrap();
+            assert!(reloader.reload().is_err());
+            assert!(Arc::ptr_eq(&running, &handle.load_full()));
+            assert_eq!(handle.load().turn.realm, "second");
+        }
+
+        std::fs::write(&path, "[turn]\nrealm = fourth\n").unwrap();
+        reloader.reload().unwrap();
+        assert_eq!(handle.load().turn.realm, "fourth");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
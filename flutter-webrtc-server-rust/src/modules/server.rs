@@ -0,0 +1,275 @@
+use anyhow::Result;
+use axum::{http::HeaderValue, routing::get_service, Router};
+use log::{error, info};
+use std::{net::SocketAddr, sync::Arc};
+use tower::ServiceBuilder;
+use tower_http::{
+    cors::{AllowOrigin, Any, CorsLayer},
+    services::ServeDir,
+};
+
+use crate::modules::admin::{self, AdminState};
+use crate::modules::bots::SignalingBot;
+use crate::modules::config::Config;
+use crate::modules::echo::EchoBot;
+use crate::modules::health::{self, HealthState};
+use crate::modules::http;
+use crate::modules::origin::OriginPolicy;
+use crate::modules::reload::ConfigReloader;
+use crate::modules::sfu::SfuPeer;
+use crate::modules::signaling::{GoAway, Signaler};
+use crate::modules::turn_server::{TurnServer, TurnStatus};
+use crate::modules::webtransport::WebTransportServer;
+
+/// Which halves of the server `serve` runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServeMode {
+    All,
+    TurnOnly,
+    SignalingOnly,
+}
+
+impl ServeMode {
+    pub fn runs_turn(self) -> bool {
+        self != ServeMode::SignalingOnly
+    }
+
+    pub fn runs_signaling(self) -> bool {
+        self != ServeMode::TurnOnly
+    }
+}
+
+/// Runs the server until SIGTERM/SIGINT, then drains it. `load` re-reads the
+/// configuration on SIGHUP or `/admin/config/reload`.
+pub async fn serve(
+    config: Config,
+    mode: ServeMode,
+    load: impl Fn() -> Result<Config> + Send + Sync + 'static,
+) -> Result<()> {
+    info!("Loaded configuration: {:?}", config.redacted());
+
+    let bind_addr = config.bind_addr()?;
+    let html_root = config.general.html_root.clone();
+    let admin_enabled = config.admin.token.is_some();
+
+    let config = config.into_handle();
+    let reloader = Arc::new(ConfigReloader::new(config.clone(), load));
+    reload_on_sighup(reloader.clone());
+
+    let signaler = Signaler::builder().config_handle(config.clone()).build()?;
+    let mut turn_server = TurnServer::builder(signaler.clone()).build();
+
+    // Start TURN server
+    let turn_status = if mode.runs_turn() {
+        if let Err(e) = turn_server.start().await {
+            error!("Failed to start TURN server: {}", e);
+        }
+        turn_server.status()
+    } else {
+        info!("Signaling-only mode, TURN server not started");
+        TurnStatus::Disabled
+    };
+
+    // Echoes allowed origins instead of sending `*`, so allowlist reloads apply
+    let cors_config = config.clone();
+    let allow_origin = AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+        let origins = OriginPolicy::new(&cors_config.load().general.allowed_origins);
+        origin.to_str().map(|o| origins.allows(o)).unwrap_or(false)
+    });
+
+    let mut app = Router::new()
+        .merge(http::metrics_router(signaler.clone()))
+        .merge(health::router(HealthState {
+            signaler: signaler.clone(),
+            signaling_enabled: mode.runs_signaling(),
+            turn: turn_status,
+            config: config.clone(),
+        }));
+
+    if mode.runs_signaling() {
+        start_webtransport(signaler.clone(), &config.load()).await;
+        start_sfu(signaler.clone(), &config.load()).await;
+        start_echo(signaler.clone(), &config.load()).await;
+        start_bots(signaler.clone(), &config.load()).await;
+        app = app
+            .merge(http::router(signaler.clone(), "/"))
+            .nest_service("/", get_service(ServeDir::new(&html_root)));
+    } else {
+        info!("TURN-only mode, signaling and static files disabled");
+    }
+
+    if admin_enabled {
+        let admin_state = AdminState {
+            signaler: signaler.clone(),
+            allocations: turn_server.allocations(),
+            reloader,
+        };
+        app = app.nest("/admin", admin::router(admin_state));
+        info!("Admin API enabled on /admin");
+    } else {
+        info!("Admin API disabled, set [admin] token to enable it");
+    }
+
+    let app = app
+        .layer(
+            ServiceBuilder::new()
+                .layer(
+                    CorsLayer::new()
+                        .allow_origin(allow_origin)
+                        .allow_methods(Any)
+                        .allow_headers(Any),
+                )
+        );
+
+    info!("Flutter WebRTC Server listening on: {}", bind_addr);
+
+    // For simplicity, start with HTTP server
+    // TLS can be added later by configuring a reverse proxy like nginx
+    let listener = tokio::net::TcpListener::bind(bind_addr).await?;
+
+    // Tell connected clients to move on at the first signal. Long-poll clients
+    // need new requests to pick up the goaway, so HTTP keeps accepting until the
+    // connections have drained; new ones are refused meanwhile. WebSocket
+    // connections outlive `serve`, so they are drained again below.
+    let shutdown_signaler = signaler.clone();
+    let shutdown_config = config.clone();
+    let (deadline_tx, deadline_rx) = tokio::sync::oneshot::channel();
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async move {
+            shutdown_signal().await;
+            let general = shutdown_config.load().general.clone();
+            shutdown_signaler.begin_shutdown(GoAway {
+                reason: "Server shutting down".to_string(),
+                reconnect_url: general.reconnect_url.clone(),
+                deadline: general.shutdown_timeout,
+            });
+            info!("Draining for up to {}s", general.shutdown_timeout);
+            let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(general.shutdown_timeout);
+            let _ = deadline_tx.send(deadline);
+            shutdown_signaler.drain(deadline).await;
+        })
+        .await?;
+
+    info!("Stopped accepting connections");
+    let deadline = deadline_rx.await.unwrap_or_else(|_| tokio::time::Instant::now());
+    let (_, turn_result) = tokio::join!(signaler.drain(deadline), turn_server.drain(deadline));
+    if let Err(e) = turn_result {
+        error!("Failed to stop TURN server: {}", e);
+    }
+
+    info!("Shutdown complete");
+    Ok(())
+}
+
+// Like TURN, a WebTransport endpoint that fails to start leaves the rest running
+async fn start_webtransport(signaler: Arc<Signaler>, config: &Config) {
+    let Some(listen) = &config.webtransport.listen else {
+        return;
+    };
+    let addr = match listen.parse::<SocketAddr>() {
+        Ok(addr) => addr,
+        Err(e) => {
+            error!("Invalid WebTransport listen address {}: {}", listen, e);
+            return;
+        }
+    };
+
+    match WebTransportServer::bind(signaler, addr, &config.general.cert, &config.general.key).await {
+        Ok(server) => {
+            info!("WebTransport signaling on https://{}{}", addr, config.webtransport.path);
+            tokio::spawn(server.run());
+        }
+        Err(e) => error!("Failed to start WebTransport endpoint: {:#}", e),
+    }
+}
+
+async fn start_sfu(signaler: Arc<Signaler>, config: &Config) {
+    let Some(peer_id) = &config.sfu.peer_id else {
+        return;
+    };
+    match SfuPeer::register(signaler, peer_id).await {
+        Ok(sfu) => {
+            info!("SFU available as peer {}", peer_id);
+            tokio::spawn(sfu.run());
+        }
+        Err(e) => error!("Failed to start SFU: {:#}", e),
+    }
+}
+
+async fn start_echo(signaler: Arc<Signaler>, config: &Config) {
+    let Some(peer_id) = &config.echo.peer_id else {
+        return;
+    };
+    match EchoBot::register(signaler, peer_id).await {
+        Ok(bot) => {
+            info!("Echo bot available as peer {}", peer_id);
+            tokio::spawn(bot.run());
+        }
+        Err(e) => error!("Failed to start echo bot: {:#}", e),
+    }
+}
+
+async fn start_bots(signaler: Arc<Signaler>, config: &Config) {
+    for peer in &config.bots.peers {
+        match SignalingBot::register(signaler.clone(), &peer.peer_id, peer.behavior).await {
+            Ok(bot) => {
+                info!("Test bot available as peer {} ({})", peer.peer_id, peer.behavior.as_str());
+                tokio::spawn(bot.run());
+            }
+            Err(e) => error!("Failed to start test bot {}: {:#}", peer.peer_id, e),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn reload_on_sighup(reloader: Arc<ConfigReloader>) {
+    tokio::spawn(async move {
+        let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+            Ok(signal) => signal,
+            Err(e) => {
+                error!("Failed to listen for SIGHUP: {}", e);
+                return;
+            }
+        };
+
+        while hangup.recv().await.is_some() {
+            info!("Received SIGHUP, reloading configuration");
+            if let Err(e) = reloader.reload() {
+                error!("Configuration reload failed, keeping the running configuration: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+fn reload_on_sighup(_reloader: Arc<ConfigReloader>) {}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for SIGINT: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Received SIGINT"),
+        _ = terminate => info!("Received SIGTERM"),
+    }
+}
@@ -0,0 +1,751 @@
+use anyhow::{Context, Result};
+use dashmap::DashMap;
+use log::{debug, error, info, warn};
+use rtc::media_stream::MediaStreamTrack;
+use rtc::rtcp::payload_feedbacks::picture_loss_indication::PictureLossIndication;
+use rtc::rtp;
+use rtc::rtp_transceiver::rtp_sender::{RTCRtpCodec, RTCRtpCodingParameters, RTCRtpEncodingParameters, RtpCodecKind};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+use webrtc::media_stream::track_local::static_rtp::TrackLocalStaticRTP;
+use webrtc::media_stream::track_local::TrackLocal;
+use webrtc::media_stream::track_remote::{TrackRemote, TrackRemoteEvent};
+use webrtc::peer_connection::{
+    MediaEngine, PeerConnection, PeerConnectionEventHandler, RTCPeerConnectionIceEvent, RTCPeerConnectionState,
+    RTCSessionDescription,
+};
+use webrtc::rtp_transceiver::RtpSender;
+
+use crate::modules::config::Config;
+use crate::modules::media::{candidate_init, ice_candidate, negotiated_payload_type, peer_connection, retarget};
+use crate::modules::signaling::{
+    Byebye, CandidatePayload, Connection, ConnectionEvents, DescriptionPayload, IceCandidate, Method, PeerInfo,
+    SessionDescription, ShutdownPhase, Signaler,
+};
+
+/// Room of offers that don't name one
+const DEFAULT_ROOM: &str = "default";
+
+/// A selective forwarding unit running as a virtual peer. Clients join a room by
+/// calling it with an offer carrying `room`; it forwards the RTP of every track
+/// they publish to the other participants of the room. Tracks added or removed
+/// later are negotiated with offers from the SFU on the same session.
+pub struct SfuPeer {
+    state: Arc<SfuState>,
+    events: ConnectionEvents,
+}
+
+struct SfuState {
+    signaler: Arc<Signaler>,
+    peer_id: String,
+    connection: Connection,
+    /// Keyed by session id
+    participants: DashMap<String, ParticipantHandle>,
+    /// Keyed by publication id
+    publications: DashMap<String, Arc<Publication>>,
+}
+
+struct ParticipantHandle {
+    client_id: String,
+    room: String,
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+enum Command {
+    /// An offer, answer or candidate from the client
+    Signal(Method),
+    /// Subscribe to the room's current publications
+    Sync,
+    Close,
+}
+
+/// A track a participant sends to the SFU.
+struct Publication {
+    id: String,
+    /// Session of the publisher
+    session_id: String,
+    client_id: String,
+    room: String,
+    kind: RtpCodecKind,
+    codec: RTCRtpCodec,
+    packets: broadcast::Sender<rtp::Packet>,
+    keyframes: Notify,
+}
+
+/// A publication forwarded to a participant.
+struct Subscription {
+    sender: Arc<dyn RtpSender>,
+    forwarder: JoinHandle<()>,
+}
+
+impl SfuPeer {
+    /// Registers `peer_id` as a peer every client can call.
+    pub async fn register(signaler: Arc<Signaler>, peer_id: &str) -> Result<Self> {
+        let (connection, events) = signaler.connect_server_peer();
+        let register = Method::New(PeerInfo {
+            id: peer_id.to_string(),
+            name: "SFU".to_string(),
+            user_agent: concat!("flutter-webrtc-server-rust/", env!("CARGO_PKG_VERSION")).to_string(),
+        });
+        signaler
+            .receive(&connection, register)
+            .await
+            .with_context(|| format!("Failed to register SFU peer {}", peer_id))?;
+
+        let state = Arc::new(SfuState {
+            signaler,
+            peer_id: peer_id.to_string(),
+            connection,
+            participants: DashMap::new(),
+            publications: DashMap::new(),
+        });
+        Ok(Self { state, events })
+    }
+
+    /// Handles calls until the signaler starts draining or the peer is
+    /// disconnected, then hangs up on every participant.
+    pub async fn run(mut self) {
+        let state = self.state;
+        let mut shutdown = state.signaler.watch_shutdown();
+        shutdown.mark_changed();
+
+        loop {
+            let message = tokio::select! {
+                message = self.events.messages.recv() => match message {
+                    Some(message) => message,
+                    None => break,
+                },
+                Some(frame) = self.events.close.recv() => {
+                    info!("🔌 SFU peer {} disconnected: {}", state.peer_id, frame.reason);
+                    break;
+                }
+                Ok(()) = shutdown.changed() => {
+                    if matches!(*shutdown.borrow_and_update(), ShutdownPhase::Running) {
+                        continue;
+                    }
+                    break;
+                }
+            };
+
+            match message {
+                Method::Offer(offer) if !state.participants.contains_key(&offer.session_id) => {
+                    state.join(offer).await;
+                }
+                Method::Offer(_) | Method::Answer(_) | Method::Candidate(_) => {
+                    let session_id = match &message {
+                        Method::Offer(payload) | Method::Answer(payload) => payload.session_id.clone(),
+                        Method::Candidate(payload) => payload.session_id.clone(),
+                        _ => unreachable!(),
+                    };
+                    match state.participants.get(&session_id) {
+                        Some(participant) => {
+                            let _ = participant.commands.send(Command::Signal(message));
+                        }
+                        None => debug!("Dropping {} for unknown SFU session {}", message.request_type(), session_id),
+                    }
+                }
+                Method::Bye(bye) => {
+                    state.leave(&bye.session_id);
+                }
+                Method::Peers(peers) => {
+                    // Clients that went away without hanging up
+                    let gone: Vec<String> = state
+                        .participants
+                        .iter()
+                        .filter(|participant| !peers.iter().any(|peer| peer.id == participant.client_id))
+                        .map(|participant| participant.key().clone())
+                        .collect();
+                    for session_id in gone {
+                        state.leave(&session_id);
+                    }
+                }
+                Method::Error(e) => warn!("⚠️ Signaling rejected {} from the SFU: {}", e.request, e.reason),
+                _ => {}
+            }
+        }
+
+        let sessions: Vec<String> = state.participants.iter().map(|participant| participant.key().clone()).collect();
+        for session_id in sessions {
+            state.hang_up(&session_id).await;
+        }
+        state.signaler.disconnect(&state.connection).await;
+        info!("SFU peer {} stopped", state.peer_id);
+    }
+}
+
+impl SfuState {
+    async fn send(&self, message: Method) {
+        let request = message.request_type();
+        if let Err(e) = self.signaler.receive(&self.connection, message).await {
+            error!("❌ Failed to send SFU {}: {}", request, e);
+        }
+    }
+
+    async fn join(self: &Arc<Self>, offer: DescriptionPayload) {
+        let room = offer
+            .extra
+            .get("room")
+            .and_then(|room| room.as_str())
+            .unwrap_or(DEFAULT_ROOM)
+            .to_string();
+        let config = self.signaler.config.load();
+        let size = self.participants.iter().filter(|participant| participant.room == room).count();
+        if size >= config.sfu.max_room_size {
+            warn!("🚫 SFU room {} is full, declining {}", room, offer.from);
+            self.decline(&offer.session_id).await;
+            return;
+        }
+
+        let (commands, commands_rx) = mpsc::unbounded_channel();
+        let handler = Arc::new(ParticipantHandler {
+            state: self.clone(),
+            session_id: offer.session_id.clone(),
+            client_id: offer.from.clone(),
+            room: room.clone(),
+        });
+        let pc = match participant_connection(&config, handler).await {
+            Ok(pc) => pc,
+            Err(e) => {
+                error!("❌ Failed to create SFU peer connection for {}: {:#}", offer.from, e);
+                self.decline(&offer.session_id).await;
+                return;
+            }
+        };
+
+        info!("👥 {} joined SFU room {} (session: {})", offer.from, room, offer.session_id);
+        self.participants.insert(
+            offer.session_id.clone(),
+            ParticipantHandle {
+                client_id: offer.from.clone(),
+                room: room.clone(),
+                commands: commands.clone(),
+            },
+        );
+        let participant = Participant {
+            state: self.clone(),
+            session_id: offer.session_id.clone(),
+            client_id: offer.from.clone(),
+            room,
+            pc,
+            subscriptions: HashMap::new(),
+            offering: false,
+            resync: false,
+        };
+        tokio::spawn(participant.run(commands_rx));
+        let _ = commands.send(Command::Signal(Method::Offer(offer)));
+    }
+
+    async fn decline(&self, session_id: &str) {
+        self.send(Method::Bye(Byebye {
+            session_id: session_id.to_string(),
+            from: self.peer_id.clone(),
+            reason: None,
+        }))
+        .await;
+    }
+
+    /// Closes the participant's peer connection. Returns false if the session
+    /// was not in a room.
+    fn leave(&self, session_id: &str) -> bool {
+        match self.participants.remove(session_id) {
+            Some((_, participant)) => {
+                let _ = participant.commands.send(Command::Close);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Like `leave`, for sessions ended by the SFU, which tells the client.
+    async fn hang_up(&self, session_id: &str) {
+        if self.leave(session_id) {
+            self.decline(session_id).await;
+        }
+    }
+
+    /// Has the other participants of `room` catch up with its publications.
+    fn sync_room(&self, room: &str, except: &str) {
+        for participant in self.participants.iter() {
+            if participant.room == room && participant.key() != except {
+                let _ = participant.commands.send(Command::Sync);
+            }
+        }
+    }
+}
+
+/// One client's peer connection. Commands run one at a time, so offers from
+/// both sides never interleave.
+struct Participant {
+    state: Arc<SfuState>,
+    session_id: String,
+    client_id: String,
+    room: String,
+    pc: Arc<dyn PeerConnection>,
+    subscriptions: HashMap<String, Subscription>,
+    /// Waiting for the answer to an offer from the SFU
+    offering: bool,
+    /// Publications changed while offering
+    resync: bool,
+}
+
+impl Participant {
+    async fn run(mut self, mut commands: mpsc::UnboundedReceiver<Command>) {
+        while let Some(command) = commands.recv().await {
+            let result = match command {
+                Command::Signal(Method::Offer(offer)) => self.answer(offer.description.sdp).await,
+                Command::Signal(Method::Answer(answer)) => self.accept(answer.description.sdp).await,
+                Command::Signal(Method::Candidate(candidate)) => self.add_candidate(candidate.candidate).await,
+                Command::Signal(_) => Ok(()),
+                Command::Sync => self.sync().await,
+                Command::Close => break,
+            };
+            if let Err(e) = result {
+                warn!("⚠️ SFU negotiation with {} failed: {:#}", self.client_id, e);
+            }
+        }
+
+        for (_, subscription) in self.subscriptions.drain() {
+            subscription.forwarder.abort();
+        }
+        if let Err(e) = self.pc.close().await {
+            debug!("Failed to close SFU peer connection of {}: {}", self.client_id, e);
+        }
+        // Publish tasks end with their tracks, this only spares the others a
+        // round of offers for each of them
+        let owned: Vec<String> = self
+            .state
+            .publications
+            .iter()
+            .filter(|publication| publication.session_id == self.session_id)
+            .map(|publication| publication.key().clone())
+            .collect();
+        for id in &owned {
+            self.state.publications.remove(id);
+        }
+        if !owned.is_empty() {
+            self.state.sync_room(&self.room, &self.session_id);
+        }
+        info!("👋 {} left SFU room {}", self.client_id, self.room);
+    }
+
+    async fn answer(&mut self, sdp: String) -> Result<()> {
+        if self.offering {
+            // The SFU is the polite side of a glare and withdraws its offer
+            debug!("Rolling back SFU offer to {} for theirs", self.client_id);
+            self.pc.set_local_description(RTCSessionDescription::rollback(None)?).await?;
+            self.offering = false;
+            self.resync = true;
+        }
+        self.pc.set_remote_description(RTCSessionDescription::offer(sdp)?).await?;
+        let answer = self.pc.create_answer(None).await?;
+        self.pc.set_local_description(answer.clone()).await?;
+        self.send_description("answer", answer.sdp).await;
+        self.sync().await
+    }
+
+    async fn accept(&mut self, sdp: String) -> Result<()> {
+        if !self.offering {
+            debug!("Ignoring unexpected answer from {}", self.client_id);
+            return Ok(());
+        }
+        self.pc.set_remote_description(RTCSessionDescription::answer(sdp)?).await?;
+        self.offering = false;
+        if std::mem::take(&mut self.resync) {
+            self.sync().await?;
+        }
+        Ok(())
+    }
+
+    async fn add_candidate(&self, candidate: IceCandidate) -> Result<()> {
+        self.pc.add_ice_candidate(candidate_init(candidate)).await?;
+        Ok(())
+    }
+
+    // Subscribes to the publications of the other participants and drops those
+    // that ended, then offers the changes
+    async fn sync(&mut self) -> Result<()> {
+        if self.offering {
+            self.resync = true;
+            return Ok(());
+        }
+
+        let publications: Vec<Arc<Publication>> = self
+            .state
+            .publications
+            .iter()
+            .filter(|publication| publication.room == self.room && publication.session_id != self.session_id)
+            .map(|publication| publication.clone())
+            .collect();
+        let ended: Vec<String> = self
+            .subscriptions
+            .keys()
+            .filter(|id| !publications.iter().any(|publication| &publication.id == *id))
+            .cloned()
+            .collect();
+
+        let mut changed = false;
+        for id in ended {
+            if let Some(subscription) = self.subscriptions.remove(&id) {
+                subscription.forwarder.abort();
+                self.pc.remove_track(&subscription.sender).await?;
+                changed = true;
+            }
+        }
+        for publication in publications {
+            if !self.subscriptions.contains_key(&publication.id) {
+                let subscription = self.subscribe(&publication).await?;
+                self.subscriptions.insert(publication.id.clone(), subscription);
+                changed = true;
+            }
+        }
+
+        if changed {
+            let offer = self.pc.create_offer(None).await?;
+            self.pc.set_local_description(offer.clone()).await?;
+            self.offering = true;
+            self.send_description("offer", offer.sdp).await;
+        }
+        Ok(())
+    }
+
+    async fn subscribe(&self, publication: &Arc<Publication>) -> Result<Subscription> {
+        // A local track binds to one peer connection, so each subscriber gets its own
+        let ssrc = uuid::Uuid::new_v4().as_u128() as u32;
+        let track = Arc::new(TrackLocalStaticRTP::new(MediaStreamTrack::new(
+            publication.client_id.clone(),
+            publication.id.clone(),
+            publication.id.clone(),
+            publication.kind,
+            vec![RTCRtpEncodingParameters {
+                rtp_coding_parameters: RTCRtpCodingParameters {
+                    ssrc: Some(ssrc),
+                    ..Default::default()
+                },
+                codec: publication.codec.clone(),
+                ..Default::default()
+            }],
+        )));
+        let sender = self.pc.add_track(track.clone() as Arc<dyn TrackLocal>).await?;
+        debug!("{} subscribed to {} of {}", self.client_id, publication.id, publication.client_id);
+
+        let forwarder = tokio::spawn(forward(
+            publication.packets.subscribe(),
+            track,
+            sender.clone(),
+            ssrc,
+            publication.codec.mime_type.clone(),
+        ));
+        // Subscribers can't decode anything before the next keyframe
+        publication.keyframes.notify_one();
+        Ok(Subscription { sender, forwarder })
+    }
+
+    async fn send_description(&self, sdp_type: &str, sdp: String) {
+        let description = DescriptionPayload {
+            from: self.state.peer_id.clone(),
+            to: self.client_id.clone(),
+            session_id: self.session_id.clone(),
+            description: SessionDescription {
+                sdp,
+                sdp_type: sdp_type.to_string(),
+            },
+            extra: serde_json::Map::new(),
+        };
+        let message = match sdp_type {
+            "offer" => Method::Offer(description),
+            _ => Method::Answer(description),
+        };
+        self.state.send(message).await;
+    }
+}
+
+struct ParticipantHandler {
+    state: Arc<SfuState>,
+    session_id: String,
+    client_id: String,
+    room: String,
+}
+
+#[async_trait::async_trait]
+impl PeerConnectionEventHandler for ParticipantHandler {
+    async fn on_ice_candidate(&self, event: RTCPeerConnectionIceEvent) {
+        let candidate = match ice_candidate(&event.candidate) {
+            Ok(candidate) => candidate,
+            Err(e) => {
+                debug!("Failed to encode SFU ICE candidate: {}", e);
+                return;
+            }
+        };
+        self.state
+            .send(Method::Candidate(CandidatePayload {
+                from: self.state.peer_id.clone(),
+                to: self.client_id.clone(),
+                session_id: self.session_id.clone(),
+                candidate,
+                extra: serde_json::Map::new(),
+            }))
+            .await;
+    }
+
+    async fn on_connection_state_change(&self, state: RTCPeerConnectionState) {
+        debug!("SFU connection of {} is {}", self.client_id, state);
+        if state == RTCPeerConnectionState::Failed {
+            warn!("⚠️ SFU connection of {} failed", self.client_id);
+            self.state.hang_up(&self.session_id).await;
+        }
+    }
+
+    async fn on_track(&self, track: Arc<dyn TrackRemote>) {
+        tokio::spawn(publish(
+            self.state.clone(),
+            self.session_id.clone(),
+            self.client_id.clone(),
+            self.room.clone(),
+            track,
+        ));
+    }
+}
+
+// Fans the track's packets out to subscribers until it ends, asking the
+// publisher for keyframes periodically and whenever someone subscribes
+async fn publish(state: Arc<SfuState>, session_id: String, client_id: String, room: String, track: Arc<dyn TrackRemote>) {
+    let Some(ssrc) = track.ssrcs().await.first().copied() else {
+        return;
+    };
+    let Some(codec) = track.codec(ssrc).await else {
+        warn!("⚠️ Track of {} has no negotiated codec", client_id);
+        return;
+    };
+    let publication = Arc::new(Publication {
+        id: uuid::Uuid::new_v4().simple().to_string(),
+        session_id,
+        client_id,
+        room,
+        kind: track.kind().await,
+        codec,
+        packets: broadcast::channel(256).0,
+        keyframes: Notify::new(),
+    });
+    info!("📹 {} published {} in SFU room {}",
+          publication.client_id, publication.codec.mime_type, publication.room);
+    state.publications.insert(publication.id.clone(), publication.clone());
+    state.sync_room(&publication.room, &publication.session_id);
+
+    let keyframes = (publication.kind == RtpCodecKind::Video).then(|| {
+        let interval = Duration::from_millis(state.signaler.config.load().sfu.keyframe_interval_ms);
+        tokio::spawn(request_keyframes(publication.clone(), track.clone(), ssrc, interval))
+    });
+    while let Some(event) = track.poll().await {
+        match event {
+            TrackRemoteEvent::OnRtpPacket(packet) => {
+                // No receivers is fine, nobody subscribed yet
+                let _ = publication.packets.send(packet);
+            }
+            TrackRemoteEvent::OnEnded => break,
+            _ => {}
+        }
+    }
+
+    if let Some(keyframes) = keyframes {
+        keyframes.abort();
+    }
+    if state.publications.remove(&publication.id).is_some() {
+        info!("📹 {} unpublished {}", publication.client_id, publication.codec.mime_type);
+        state.sync_room(&publication.room, &publication.session_id);
+    }
+}
+
+async fn request_keyframes(publication: Arc<Publication>, track: Arc<dyn TrackRemote>, ssrc: u32, interval: Duration) {
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = publication.keyframes.notified() => {}
+        }
+        let pli = PictureLossIndication {
+            sender_ssrc: 0,
+            media_ssrc: ssrc,
+        };
+        if track.write_rtcp(vec![Box::new(pli)]).await.is_err() {
+            return;
+        }
+    }
+}
+
+// Rewrites the publisher's packets for one subscriber: its own SSRC, the payload
+// type negotiated with it, and no header extensions, whose ids differ per
+// connection
+async fn forward(
+    mut packets: broadcast::Receiver<rtp::Packet>,
+    track: Arc<TrackLocalStaticRTP>,
+    sender: Arc<dyn RtpSender>,
+    ssrc: u32,
+    mime_type: String,
+) {
+    let mut payload_type = None;
+    loop {
+        let mut packet = match packets.recv().await {
+            Ok(packet) => packet,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                debug!("SFU subscriber fell behind, skipped {} packets", skipped);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        if payload_type.is_none() {
+            payload_type = negotiated_payload_type(&sender, &mime_type).await;
+        }
+        // Not negotiated yet
+        let Some(payload_type) = payload_type else {
+            continue;
+        };
+
+        retarget(&mut packet, ssrc, payload_type);
+        if let Err(e) = track.write_rtp(packet).await {
+            debug!("Failed to forward RTP packet: {}", e);
+        }
+    }
+}
+
+// Forwards whatever the publishers send, so any codec goes
+async fn participant_connection(config: &Config, handler: Arc<ParticipantHandler>) -> Result<Arc<dyn PeerConnection>> {
+    let mut media_engine = MediaEngine::default();
+    media_engine.register_default_codecs()?;
+    peer_connection(config, media_engine, handler).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::media::testing;
+    use rtc::rtp_transceiver::{RTCRtpTransceiverDirection, RTCRtpTransceiverInit};
+    use serde_json::json;
+    use std::sync::atomic::Ordering;
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    async fn sfu() -> (Arc<Signaler>, SfuPeer) {
+        let signaler = Signaler::builder().config(Config::builder().build().unwrap()).build().unwrap();
+        let sfu = SfuPeer::register(signaler.clone(), "sfu").await.unwrap();
+        (signaler, sfu)
+    }
+
+    // Adds a participant without a peer connection, returning its commands
+    fn participant(state: &SfuState, session_id: &str, client_id: &str, room: &str) -> mpsc::UnboundedReceiver<Command> {
+        let (commands, commands_rx) = mpsc::unbounded_channel();
+        let handle = ParticipantHandle { client_id: client_id.to_string(), room: room.to_string(), commands };
+        state.participants.insert(session_id.to_string(), handle);
+        commands_rx
+    }
+
+    async fn client(signaler: &Signaler, id: &str) -> (Connection, ConnectionEvents) {
+        let (connection, events) = signaler.connect(None);
+        let register = json!({"type": "new", "data": {"id": id, "name": id, "user_agent": "test"}});
+        signaler.receive(&connection, serde_json::from_value(register).unwrap()).await.unwrap();
+        (connection, events)
+    }
+
+    async fn next_matching(events: &mut ConnectionEvents, matches: impl Fn(&Method) -> bool) -> Method {
+        tokio::time::timeout(TIMEOUT, async {
+            loop {
+                let message = events.messages.recv().await.unwrap();
+                if matches(&message) {
+                    return message;
+                }
+            }
+        })
+        .await
+        .expect("expected message not received")
+    }
+
+    #[tokio::test]
+    async fn sync_room_reaches_the_other_participants_of_the_room() {
+        let (_signaler, sfu) = sfu().await;
+        let mut alice = participant(&sfu.state, "alice-sfu", "alice", "blue");
+        let mut bob = participant(&sfu.state, "bob-sfu", "bob", "blue");
+        let mut carol = participant(&sfu.state, "carol-sfu", "carol", "green");
+
+        sfu.state.sync_room("blue", "alice-sfu");
+        assert!(matches!(bob.try_recv(), Ok(Command::Sync)));
+        assert!(alice.try_recv().is_err(), "the publisher itself is not resynced");
+        assert!(carol.try_recv().is_err(), "other rooms are not resynced");
+    }
+
+    #[tokio::test]
+    async fn leaving_closes_the_participant_once() {
+        let (_signaler, sfu) = sfu().await;
+        let mut alice = participant(&sfu.state, "alice-sfu", "alice", DEFAULT_ROOM);
+
+        assert!(sfu.state.leave("alice-sfu"));
+        assert!(matches!(alice.try_recv(), Ok(Command::Close)));
+        assert!(!sfu.state.leave("alice-sfu"));
+    }
+
+    #[tokio::test]
+    async fn full_rooms_decline_offers() {
+        let config = Config::builder().set("sfu", "max_room_size", 1).build().unwrap();
+        let signaler = Signaler::builder().config(config).build().unwrap();
+        // Registered before the SFU, or its first peer list would drop bob
+        let (_bob, _bob_events) = client(&signaler, "bob").await;
+        let sfu = SfuPeer::register(signaler.clone(), "sfu").await.unwrap();
+        let _bob_commands = participant(&sfu.state, "bob-sfu", "bob", DEFAULT_ROOM);
+        tokio::spawn(sfu.run());
+        let (alice, mut alice_events) = client(&signaler, "alice").await;
+
+        let offer = json!({"type": "offer", "data": {
+            "from": "alice", "to": "sfu", "session_id": "alice-sfu",
+            "description": {"type": "offer", "sdp": "v=0\r\n"},
+        }});
+        signaler.receive(&alice, serde_json::from_value(offer).unwrap()).await.unwrap();
+        match next_matching(&mut alice_events, |message| matches!(message, Method::Bye(_))).await {
+            Method::Bye(bye) => assert_eq!((bye.session_id.as_str(), bye.from.as_str()), ("alice-sfu", "sfu")),
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn participants_that_went_away_are_dropped() {
+        let (signaler, sfu) = sfu().await;
+        let mut carol = participant(&sfu.state, "carol-sfu", "carol", DEFAULT_ROOM);
+        let state = sfu.state.clone();
+        tokio::spawn(sfu.run());
+
+        // Any peer list without carol, here the one sent when alice registers
+        let (_alice, _alice_events) = client(&signaler, "alice").await;
+        let closed = tokio::time::timeout(TIMEOUT, carol.recv()).await.unwrap();
+        assert!(matches!(closed, Some(Command::Close)));
+        assert!(state.participants.is_empty());
+    }
+
+    #[tokio::test]
+    async fn published_rtp_reaches_subscribers() {
+        let (signaler, sfu) = sfu().await;
+        tokio::spawn(sfu.run());
+
+        let ssrc = 0x5f0;
+        let track = testing::video_track("alice", ssrc);
+        let published = track.clone();
+        let (alice, ()) = testing::call(signaler.clone(), "alice", "sfu", |pc| async move {
+            pc.add_track(published as Arc<dyn TrackLocal>).await.unwrap();
+        })
+        .await;
+        let (bob, ()) = testing::call(signaler.clone(), "bob", "sfu", |pc| async move {
+            let receive_only = RTCRtpTransceiverInit {
+                direction: RTCRtpTransceiverDirection::Recvonly,
+                ..Default::default()
+            };
+            pc.add_transceiver_from_kind(RtpCodecKind::Video, Some(receive_only)).await.unwrap();
+        })
+        .await;
+
+        // Keeps sending until the SFU subscribed bob and forwarded some of it
+        let forwarded = testing::send_until_received(&track, ssrc, &bob.received, 5).await;
+        assert!(forwarded, "bob received {} packets", bob.received.load(Ordering::SeqCst));
+        assert_eq!(alice.received.load(Ordering::SeqCst), 0, "publishers don't get their own tracks back");
+
+        alice.pc.close().await.unwrap();
+        bob.pc.close().await.unwrap();
+    }
+}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use axum::extract::ws::{Message, WebSocket};
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocket};
 use chrono::{Duration, Utc};
 use dashmap::DashMap;
 use futures_util::{SinkExt, StreamExt};
@@ -7,10 +7,24 @@ use hmac::{Hmac, Mac};
 use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
 use sha1::Sha1;
-use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use tokio::sync::{mpsc, watch};
 
-const SHARED_KEY: &str = "flutter-webrtc-turn-server-shared-key";
+use crate::modules::cdr::{CallRecord, CdrStore};
+use crate::modules::cluster::{Cluster, ClusterEvent, MemoryBus, MemoryDirectory, MessageBus, PeerDirectory};
+use crate::modules::cluster_mesh::MeshBackend;
+use crate::modules::cluster_redis::RedisBackend;
+use crate::modules::codec::{self, Codec};
+use crate::modules::config::{ClusterBackend, Config, ConfigHandle};
+use crate::modules::hooks::{HookContext, HookDecision, NoopHooks, SignalingHooks};
+use crate::modules::metrics::Metrics;
+use crate::modules::protocol::{self, Ack, Capabilities, Feature, Hello};
+use crate::modules::rate_limit::{ConnectionLimiter, RateLimiter};
+use crate::modules::store::{MemoryStore, SqliteStore, StateStore};
+use crate::modules::validation::{self, ValidationError};
+use crate::modules::webhooks::{self, WebhookEvent, Webhooks};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TurnCredentials {
@@ -20,7 +34,48 @@ pub struct TurnCredentials {
     pub uris: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl TurnCredentials {
+    /// Creates TURN REST API credentials for `username`, valid for `credential_ttl`.
+    /// The relay only accepts them once they are in the state store.
+    pub fn generate(turn_config: &crate::modules::config::TurnConfig, username: &str) -> Result<Self> {
+        let timestamp = Utc::now().timestamp();
+        let turn_username = format!("{}:{}", timestamp, username);
+
+        let secret = match &turn_config.secret {
+            Some(secret) => secret.clone(),
+            None => uuid::Uuid::new_v4().to_string(),
+        };
+        let mut mac = Hmac::<Sha1>::new_from_slice(secret.as_bytes())?;
+        mac.update(turn_username.as_bytes());
+        let turn_password = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, mac.finalize().into_bytes());
+
+        let ttl = turn_config.credential_ttl;
+        let host = format!("{}:{}", turn_config.public_ip, turn_config.port);
+        
+        Ok(Self {
+            username: turn_username,
+            password: turn_password,
+            ttl,
+            uris: vec![format!("turn:{}?transport=udp", host)],
+        })
+    }
+
+    /// Generates credentials for `username` and records them in `store`, where
+    /// the relay looks them up.
+    pub async fn issue(turn_config: &crate::modules::config::TurnConfig, store: &dyn StateStore, username: &str) -> Result<Self> {
+        let credentials = Self::generate(turn_config, username)?;
+        let expires_at = Utc::now() + Duration::seconds(credentials.ttl);
+        store
+            .put_credential(&ExpiredCredential {
+                credential: credentials.clone(),
+                expires_at,
+            })
+            .await?;
+        Ok(credentials)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PeerInfo {
     pub id: String,
     pub name: String,
@@ -34,16 +89,92 @@ pub struct Negotiation {
     pub session_id: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionDescription {
+    pub sdp: String,
+    #[serde(rename = "type")]
+    pub sdp_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IceCandidate {
+    pub candidate: String,
+    #[serde(rename = "sdpMid", default, skip_serializing_if = "Option::is_none")]
+    pub sdp_mid: Option<String>,
+    #[serde(rename = "sdpMLineIndex", default, skip_serializing_if = "Option::is_none")]
+    pub sdp_mline_index: Option<u16>,
+}
+
+/// Payload of `offer` and `answer` messages.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DescriptionPayload {
+    pub from: String,
+    pub to: String,
+    pub session_id: String,
+    pub description: SessionDescription,
+    /// Client-defined fields (such as `media`) relayed untouched
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl DescriptionPayload {
+    pub fn negotiation(&self) -> Negotiation {
+        Negotiation {
+            from: self.from.clone(),
+            to: self.to.clone(),
+            session_id: self.session_id.clone(),
+        }
+    }
+}
+
+/// Payload of `candidate` messages.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CandidatePayload {
+    pub from: String,
+    pub to: String,
+    pub session_id: String,
+    pub candidate: IceCandidate,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl CandidatePayload {
+    pub fn negotiation(&self) -> Negotiation {
+        Negotiation {
+            from: self.from.clone(),
+            to: self.to.clone(),
+            session_id: self.session_id.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Byebye {
     pub session_id: String,
     pub from: String,
+    /// Why the call ended, e.g. `busy`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SignalingError {
     pub request: String,
     pub reason: String,
+    /// Name of the violated validation rule, if the error came from validation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+}
+
+/// Tells clients the server is shutting down and they should reconnect.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GoAway {
+    pub reason: String,
+    /// Where to reconnect, if the deployment has a separate endpoint for that
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reconnect_url: Option<String>,
+    /// Seconds until remaining connections are closed
+    pub deadline: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -54,11 +185,11 @@ pub enum Method {
     #[serde(rename = "bye")]
     Bye(Byebye),
     #[serde(rename = "offer")]
-    Offer(serde_json::Value),
+    Offer(DescriptionPayload),
     #[serde(rename = "answer")]
-    Answer(serde_json::Value),
+    Answer(DescriptionPayload),
     #[serde(rename = "candidate")]
-    Candidate(serde_json::Value),
+    Candidate(CandidatePayload),
     #[serde(rename = "leave")]
     Leave(String),
     #[serde(rename = "keepalive")]
@@ -67,140 +198,928 @@ pub enum Method {
     Peers(Vec<PeerInfo>),
     #[serde(rename = "error")]
     Error(SignalingError),
+    #[serde(rename = "hello")]
+    Hello(Hello),
+    #[serde(rename = "ack")]
+    Ack(Ack),
+    #[serde(rename = "goaway")]
+    GoAway(GoAway),
+    /// Written to the wire as-is and never decoded, so test bots can send
+    /// frames that clients must reject
+    #[serde(untagged, skip_deserializing)]
+    Raw(serde_json::Value),
+}
+
+/// Wire names of every message type, as used in the `type` field.
+pub const REQUEST_TYPES: &[&str] = &[
+    "new", "bye", "offer", "answer", "candidate", "leave", "keepalive", "peers", "error", "hello", "ack", "goaway",
+];
+
+impl Method {
+    /// The wire name of this message, as used in the `type` field.
+    pub fn request_type(&self) -> &'static str {
+        match self {
+            Method::New(_) => "new",
+            Method::Bye(_) => "bye",
+            Method::Offer(_) => "offer",
+            Method::Answer(_) => "answer",
+            Method::Candidate(_) => "candidate",
+            Method::Leave(_) => "leave",
+            Method::Keepalive => "keepalive",
+            Method::Peers(_) => "peers",
+            Method::Error(_) => "error",
+            Method::Hello(_) => "hello",
+            Method::Ack(_) => "ack",
+            Method::GoAway(_) => "goaway",
+            Method::Raw(_) => "raw",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Peer {
     pub info: PeerInfo,
     pub sender: mpsc::UnboundedSender<Method>,
+    pub capabilities: Capabilities,
+    pub connected_at: chrono::DateTime<Utc>,
+    pub remote_addr: Option<SocketAddr>,
+    /// Asks the owning connection to close with the given frame
+    pub closer: Option<mpsc::UnboundedSender<CloseFrame<'static>>>,
 }
 
-#[derive(Debug, Clone)]
+/// Per-connection state tracked by the WebSocket handler and `Connection`.
+#[derive(Debug)]
+pub struct ConnectionState {
+    pub peer_id: Option<String>,
+    pub capabilities: Capabilities,
+    pub limiter: ConnectionLimiter,
+    /// Set when the server decides to drop the connection
+    pub close: Option<CloseFrame<'static>>,
+    pub connected_at: chrono::DateTime<Utc>,
+    pub remote_addr: Option<SocketAddr>,
+    pub closer: Option<mpsc::UnboundedSender<CloseFrame<'static>>>,
+}
+
+/// A signaling connection over a transport other than the `/ws` WebSocket,
+/// such as WHIP. Its messages pass the same rate limits, validation and hooks.
+pub struct Connection {
+    state: Arc<tokio::sync::Mutex<ConnectionState>>,
+    sender: mpsc::UnboundedSender<Method>,
+}
+
+/// What a `Connection`'s transport has to pass on to its client.
+pub struct ConnectionEvents {
+    /// Messages for the client, including those for the peer it registered
+    pub messages: mpsc::UnboundedReceiver<Method>,
+    /// Requests to close the connection, e.g. from `disconnect_peer`
+    pub close: mpsc::UnboundedReceiver<CloseFrame<'static>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExpiredCredential {
     pub credential: TurnCredentials,
     pub expires_at: chrono::DateTime<Utc>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CallSession {
     pub session_id: String,
     pub caller_id: String,
     pub callee_id: String,
     pub started_at: chrono::DateTime<Utc>,
     pub status: CallStatus,
+    /// `room` field of the offer, for clients that group calls
+    pub room: Option<String>,
+    pub answered_at: Option<chrono::DateTime<Utc>>,
+    pub ended_at: Option<chrono::DateTime<Utc>>,
+    pub end_reason: Option<EndReason>,
+    /// A relay (TURN) candidate was exchanged
+    pub turn_used: bool,
 }
 
-#[derive(Debug, Clone)]
+/// Call states in the order a session goes through them.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum CallStatus {
     Calling,    // Offer sent, waiting for answer
     Connected,  // Answer received, call in progress
     Ended,      // Call terminated
 }
 
+impl CallStatus {
+    pub const ALL: [CallStatus; 3] = [CallStatus::Calling, CallStatus::Connected, CallStatus::Ended];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CallStatus::Calling => "calling",
+            CallStatus::Connected => "connected",
+            CallStatus::Ended => "ended",
+        }
+    }
+}
+
+/// Why a call session ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EndReason {
+    /// One side sent `bye`
+    Bye,
+    /// The offer or answer could not be delivered
+    Unreachable,
+    /// Caller or callee disconnected without `bye`
+    Disconnect,
+    /// Ended through the admin API
+    Admin,
+}
+
+impl EndReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EndReason::Bye => "bye",
+            EndReason::Unreachable => "unreachable",
+            EndReason::Disconnect => "disconnect",
+            EndReason::Admin => "admin",
+        }
+    }
+}
+
+impl std::str::FromStr for EndReason {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "bye" => Ok(EndReason::Bye),
+            "unreachable" => Ok(EndReason::Unreachable),
+            "disconnect" => Ok(EndReason::Disconnect),
+            "admin" => Ok(EndReason::Admin),
+            other => Err(format!("unknown end reason {}", other)),
+        }
+    }
+}
+
+/// Lifecycle of the signaling server during shutdown.
+#[derive(Debug, Clone)]
+pub enum ShutdownPhase {
+    Running,
+    /// `goaway` sent, existing connections may finish their calls
+    Draining(GoAway),
+    /// Drain deadline passed, remaining connections are being closed
+    Closing,
+}
+
 pub struct Signaler {
     pub peers: Arc<DashMap<String, Peer>>,
-    pub sessions: Arc<DashMap<String, CallSession>>,
-    pub turn_credentials: Arc<DashMap<String, ExpiredCredential>>,
-    pub turn_config: crate::modules::config::TurnConfig,
+    /// Call sessions and issued TURN credentials
+    pub store: Arc<dyn StateStore>,
+    pub config: ConfigHandle,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub metrics: Arc<Metrics>,
+    pub active_connections: Arc<AtomicUsize>,
+    pub hooks: Arc<dyn SignalingHooks>,
+    /// Set when `[webhooks] urls` lists endpoints
+    pub webhooks: Option<Arc<Webhooks>>,
+    /// Set when `[cdr] path` is configured
+    pub cdr: Option<Arc<CdrStore>>,
+    /// Set in cluster mode, routes messages to peers on other nodes
+    pub cluster: Option<Arc<Cluster>>,
+    shutdown: watch::Sender<ShutdownPhase>,
+}
+
+/// Why a message could not be handed to its recipient.
+#[derive(Debug)]
+enum RouteError {
+    /// Not connected here or, in cluster mode, on any other node
+    NotFound,
+    /// Connected here, but its connection is closing
+    Unreachable(String),
+}
+
+/// Builds a `Signaler`, defaulting to the built-in configuration and a fresh
+/// metrics registry.
+#[derive(Default)]
+pub struct SignalerBuilder {
+    config: Option<ConfigHandle>,
+    metrics: Option<Arc<Metrics>>,
+    hooks: Option<Arc<dyn SignalingHooks>>,
+    webhooks: Option<Arc<Webhooks>>,
+    cdr: Option<Arc<CdrStore>>,
+    store: Option<Arc<dyn StateStore>>,
+    cluster: Option<ClusterMembership>,
+}
+
+// Node id, directory and bus to join a cluster with
+type ClusterMembership = (String, Arc<dyn PeerDirectory>, Arc<dyn MessageBus>);
+
+impl SignalerBuilder {
+    pub fn config(self, config: Config) -> Self {
+        self.config_handle(config.into_handle())
+    }
+
+    /// Shares a live config, e.g. with a `ConfigReloader` or `TurnServer`.
+    pub fn config_handle(mut self, config: ConfigHandle) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    pub fn metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Business logic run on connections and messages, see `SignalingHooks`.
+    pub fn hooks(mut self, hooks: Arc<dyn SignalingHooks>) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// Uses `webhooks` instead of starting them from `[webhooks]`.
+    pub fn webhooks(mut self, webhooks: Arc<Webhooks>) -> Self {
+        self.webhooks = Some(webhooks);
+        self
+    }
+
+    /// Uses `cdr` instead of opening the `[cdr]` database.
+    pub fn cdr(mut self, cdr: Arc<CdrStore>) -> Self {
+        self.cdr = Some(cdr);
+        self
+    }
+
+    /// Keeps sessions and TURN credentials in `store` instead of the `[store]` one.
+    pub fn store(mut self, store: Arc<dyn StateStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Joins a cluster as `node_id` through `directory` and `bus` instead of
+    /// the `[cluster]` backend.
+    pub fn cluster(
+        mut self,
+        node_id: impl Into<String>,
+        directory: Arc<dyn PeerDirectory>,
+        bus: Arc<dyn MessageBus>,
+    ) -> Self {
+        self.cluster = Some((node_id.into(), directory, bus));
+        self
+    }
+
+    /// Starts webhook delivery if `[webhooks] urls` is set and joins the cluster
+    /// if one is configured, both of which need a Tokio runtime.
+    pub fn build(self) -> Result<Arc<Signaler>> {
+        let config = match self.config {
+            Some(config) => config,
+            None => Config::builder().build()?.into_handle(),
+        };
+        let metrics = match self.metrics {
+            Some(metrics) => metrics,
+            None => Arc::new(Metrics::new()?),
+        };
+        let webhooks = match self.webhooks {
+            Some(webhooks) => Some(webhooks),
+            None if !config.load().webhooks.urls.is_empty() => {
+                Some(Webhooks::start(config.clone(), metrics.clone())?)
+            }
+            None => None,
+        };
+        let cdr = match self.cdr {
+            Some(cdr) => Some(cdr),
+            None => match &config.load().cdr.path {
+                Some(path) => Some(Arc::new(CdrStore::open(path)?)),
+                None => None,
+            },
+        };
+
+        let store: Arc<dyn StateStore> = match self.store {
+            Some(store) => store,
+            None => match &config.load().store.path {
+                Some(path) => {
+                    let store = SqliteStore::open(path)?;
+                    // Clients reuse session ids, a new offer must not renegotiate a dead call
+                    let ended = store.end_open_sessions(Utc::now())?;
+                    if ended > 0 {
+                        info!("Ended {} call sessions left open by the previous run", ended);
+                    }
+                    Arc::new(store)
+                }
+                None => Arc::new(MemoryStore::default()),
+            },
+        };
+        let cluster = match self.cluster {
+            Some(members) => Some(members),
+            None => {
+                let snapshot = config.load();
+                let settings = &snapshot.cluster;
+                let node_id = settings
+                    .node_id
+                    .clone()
+                    .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+                match settings.backend {
+                    ClusterBackend::None => None,
+                    ClusterBackend::Memory => {
+                        Some((node_id, Arc::new(MemoryDirectory::default()) as _, Arc::new(MemoryBus::default()) as _))
+                    }
+                    ClusterBackend::Redis => {
+                        let redis = Arc::new(RedisBackend::new(
+                            &settings.redis_url,
+                            &settings.key_prefix,
+                            std::time::Duration::from_millis(settings.node_timeout_ms),
+                        )?);
+                        Some((node_id, redis.clone() as _, redis as _))
+                    }
+                    ClusterBackend::Mesh => {
+                        let mesh = MeshBackend::start(
+                            &node_id,
+                            settings.mesh_listen.as_deref(),
+                            &settings.mesh_peers,
+                            settings.mesh_secret.as_deref().unwrap_or_default(),
+                            std::time::Duration::from_millis(settings.heartbeat_ms),
+                            std::time::Duration::from_millis(settings.node_timeout_ms),
+                        )?;
+                        Some((node_id, mesh.clone() as _, mesh as _))
+                    }
+                }
+            }
+        };
+        let cluster = match cluster {
+            Some((node_id, directory, bus)) => {
+                let sync_interval = std::time::Duration::from_millis(config.load().cluster.heartbeat_ms);
+                Some(Cluster::start(node_id, directory, bus, sync_interval)?)
+            }
+            None => None,
+        };
+
+        let mut signaler = Signaler::new(config, metrics);
+        if let Some(hooks) = self.hooks {
+            signaler.hooks = hooks;
+        }
+        signaler.webhooks = webhooks;
+        signaler.cdr = cdr;
+        signaler.store = store;
+        let events = cluster.map(|(cluster, events)| {
+            signaler.cluster = Some(cluster);
+            events
+        });
+
+        let signaler = Arc::new(signaler);
+        if let Some(events) = events {
+            tokio::spawn(cluster_events(Arc::downgrade(&signaler), events));
+        }
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(purge_state(Arc::downgrade(&signaler)));
+        }
+        Ok(signaler)
+    }
 }
 
 impl Signaler {
-    pub fn new(turn_config: crate::modules::config::TurnConfig) -> Self {
+    pub fn builder() -> SignalerBuilder {
+        SignalerBuilder::default()
+    }
+
+    pub fn new(config: ConfigHandle, metrics: Arc<Metrics>) -> Self {
         Self {
             peers: Arc::new(DashMap::new()),
-            sessions: Arc::new(DashMap::new()),
-            turn_credentials: Arc::new(DashMap::new()),
-            turn_config,
+            store: Arc::new(MemoryStore::default()),
+            rate_limiter: Arc::new(RateLimiter::new(config.clone(), metrics.clone())),
+            config,
+            metrics,
+            active_connections: Arc::new(AtomicUsize::new(0)),
+            hooks: Arc::new(NoopHooks),
+            webhooks: None,
+            cdr: None,
+            cluster: None,
+            shutdown: watch::channel(ShutdownPhase::Running).0,
         }
     }
 
-    pub fn generate_turn_credentials(&self, username: &str) -> Result<TurnCredentials> {
-        let timestamp = Utc::now().timestamp();
-        let turn_username = format!("{}:{}", timestamp, username);
-        
-        let mut mac = Hmac::<Sha1>::new_from_slice(SHARED_KEY.as_bytes())?;
-        mac.update(turn_username.as_bytes());
-        let turn_password = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, mac.finalize().into_bytes());
+    pub fn shutdown_phase(&self) -> ShutdownPhase {
+        self.shutdown.borrow().clone()
+    }
 
-        let ttl = 86400; // 24 hours
-        let host = format!("{}:{}", self.turn_config.public_ip, self.turn_config.port);
-        
-        let credentials = TurnCredentials {
-            username: turn_username.clone(),
-            password: turn_password,
-            ttl,
-            uris: vec![format!("turn:{}?transport=udp", host)],
-        };
+    /// Follows the shutdown phase, for transports that tell their clients themselves.
+    pub fn watch_shutdown(&self) -> watch::Receiver<ShutdownPhase> {
+        self.shutdown.subscribe()
+    }
 
-        // Store credentials with expiration
-        let expires_at = Utc::now() + Duration::seconds(ttl);
-        self.turn_credentials.insert(
-            turn_username,
-            ExpiredCredential {
-                credential: credentials.clone(),
-                expires_at,
-            },
-        );
+    /// True while new WebSocket connections fit under `max_connections`.
+    pub fn has_capacity(&self) -> bool {
+        self.active_connections.load(Ordering::SeqCst) < self.config.load().signaling.max_connections
+    }
 
-        Ok(credentials)
+    /// Sends `goaway` to every connection and starts draining.
+    pub fn begin_shutdown(&self, goaway: GoAway) {
+        info!("🛑 Shutting down, sending goaway to {} connections",
+              self.active_connections.load(Ordering::SeqCst));
+        self.shutdown.send_replace(ShutdownPhase::Draining(goaway));
     }
 
-    pub fn validate_turn_credentials(&self, username: &str) -> Option<String> {
-        if let Some(entry) = self.turn_credentials.get(username) {
-            if entry.expires_at > Utc::now() {
-                return Some(entry.credential.password.clone());
-            } else {
-                // Remove expired credentials
-                self.turn_credentials.remove(username);
+    /// Waits for connections to close on their own until `deadline`, then closes
+    /// the rest with code 1001 (going away).
+    pub async fn drain(&self, deadline: tokio::time::Instant) {
+        let poll_interval = std::time::Duration::from_millis(250);
+
+        while self.active_connections.load(Ordering::SeqCst) > 0 {
+            if tokio::time::Instant::now() >= deadline {
+                let remaining = self.active_connections.load(Ordering::SeqCst);
+                warn!("⏱️ Drain deadline reached, closing {} remaining connections", remaining);
+                self.shutdown.send_replace(ShutdownPhase::Closing);
+
+                // Closing only has to flush a close frame per connection
+                let grace = tokio::time::Instant::now() + std::time::Duration::from_secs(2);
+                while self.active_connections.load(Ordering::SeqCst) > 0
+                    && tokio::time::Instant::now() < grace
+                {
+                    tokio::time::sleep(poll_interval).await;
+                }
+                break;
+            }
+            tokio::time::sleep(poll_interval).await;
+        }
+
+        info!("✅ Signaling drained, {} connections left",
+              self.active_connections.load(Ordering::SeqCst));
+    }
+
+    /// Refreshes gauges derived from the peer and session maps, then renders all metrics.
+    pub async fn render_metrics(&self) -> Result<String> {
+        self.metrics.connected_peers.set(self.peers.len() as i64);
+        if let Some(webhooks) = &self.webhooks {
+            self.metrics.webhook_queue_depth.set(webhooks.pending() as i64);
+        }
+
+        let sessions = self.store.sessions().await?;
+        for status in CallStatus::ALL {
+            let count = sessions
+                .iter()
+                .filter(|session| session.status.as_str() == status.as_str())
+                .count();
+            self.metrics
+                .call_sessions
+                .with_label_values(&[status.as_str()])
+                .set(count as i64);
+        }
+
+        self.metrics.render()
+    }
+
+    pub async fn generate_turn_credentials(&self, username: &str) -> Result<TurnCredentials> {
+        let turn = self.config.load().turn.clone();
+        TurnCredentials::issue(&turn, self.store.as_ref(), username).await
+    }
+
+    /// Password of an issued, unexpired and unrevoked TURN credential.
+    pub async fn validate_turn_credentials(&self, username: &str) -> Option<String> {
+        match self.store.credential(username).await {
+            Ok(Some(entry)) if entry.expires_at > Utc::now() => Some(entry.credential.password),
+            Ok(_) => None,
+            Err(e) => {
+                error!("❌ Failed to look up TURN credential {}: {}", username, e);
+                None
             }
         }
-        None
     }
 
+    /// Sends every local peer the list of all peers, including those on other
+    /// cluster nodes.
     pub fn notify_peers_update(&self) {
-        let peer_infos: Vec<PeerInfo> = self.peers.iter().map(|entry| entry.value().info.clone()).collect();
+        let mut peer_infos: Vec<PeerInfo> = self.peers.iter().map(|entry| entry.value().info.clone()).collect();
+        if let Some(cluster) = &self.cluster {
+            // A peer that just reconnected here may still be listed on its old node
+            let remote = cluster
+                .remote_peers()
+                .into_iter()
+                .filter(|peer| !self.peers.contains_key(&peer.info.id));
+            peer_infos.extend(remote.map(|peer| peer.info));
+        }
         let message = Method::Peers(peer_infos);
 
         for peer in self.peers.iter() {
             if let Err(e) = peer.value().sender.send(message.clone()) {
                 error!("Failed to send peers update to {}: {}", peer.key(), e);
+                self.metrics.delivery_failures.with_label_values(&["peers"]).inc();
             }
         }
     }
 
-    pub async fn handle_websocket(&self, socket: WebSocket) {
-        info!("Starting WebSocket handler for new connection");
+    /// Hands `message` to peer `to`, here or on the cluster node it is connected
+    /// to. Delivery to another node is fire-and-forget.
+    fn route(&self, to: &str, message: Method) -> Result<(), RouteError> {
+        if let Some(peer) = self.peers.get(to) {
+            return peer.sender.send(message).map_err(|e| RouteError::Unreachable(e.to_string()));
+        }
+
+        let cluster = self.cluster.as_ref().ok_or(RouteError::NotFound)?;
+        let node_id = cluster.locate(to).ok_or(RouteError::NotFound)?;
+        debug!("🌐 Routing {} for {} via node {}", message.request_type(), to, node_id);
+        cluster.deliver(&node_id, to, message);
+        Ok(())
+    }
+
+    async fn handle_cluster_event(&self, event: ClusterEvent) {
+        match event {
+            ClusterEvent::PeersChanged => self.notify_peers_update(),
+            ClusterEvent::PeerLeft(peer_id) => {
+                info!("🌐 Remote peer {} left the cluster", peer_id);
+                self.end_calls_of(&peer_id).await;
+                self.notify_peers_update();
+            }
+            ClusterEvent::Deliver { to, message } => {
+                let request_type = message.request_type();
+                // Sessions live on the caller's node, which may be this one
+                match &message {
+                    Method::Answer(answer) => {
+                        self.mark_connected(&answer.session_id).await;
+                    }
+                    Method::Candidate(candidate) => self.mark_turn_used(candidate).await,
+                    Method::Bye(bye) => {
+                        self.end_call(&bye.session_id, EndReason::Bye).await;
+                    }
+                    _ => {}
+                }
+
+                let delivered = match self.peers.get(&to) {
+                    Some(peer) => peer.sender.send(message).is_ok(),
+                    None => false,
+                };
+                if delivered {
+                    debug!("✅ Delivered {} from the cluster to {}", request_type, to);
+                } else {
+                    warn!("⚠️ Cluster {} message for {} arrived after it left", request_type, to);
+                    self.metrics.delivery_failures.with_label_values(&[request_type]).inc();
+                }
+            }
+        }
+    }
+
+    /// Marks an answered session as connected and records its setup time.
+    /// Returns false if the session is not tracked on this node.
+    async fn mark_connected(&self, session_id: &str) -> bool {
+        // Answers to renegotiations don't count towards call setup time
+        let answered = matches!(self.store.session(session_id).await, Ok(Some(session)) if session.answered_at.is_some());
+        match self.set_session_status(session_id, CallStatus::Connected).await {
+            Some(session) => {
+                // Late answers to ended calls leave them unanswered
+                if let (false, Some(answered_at)) = (answered, session.answered_at) {
+                    let setup_time = answered_at - session.started_at;
+                    self.metrics
+                        .call_setup_seconds
+                        .observe(setup_time.num_milliseconds() as f64 / 1000.0);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn mark_turn_used(&self, candidate: &CandidatePayload) {
+        if candidate.candidate.candidate.contains(" typ relay") {
+            self.update_session(&candidate.session_id, |session| session.turn_used = true).await;
+        }
+    }
+
+    fn emit(&self, event: impl FnOnce() -> WebhookEvent) {
+        if let Some(webhooks) = &self.webhooks {
+            webhooks.emit(event());
+        }
+    }
+
+    /// Applies `update` to a stored session, treating store errors as an unknown session.
+    async fn update_session(
+        &self,
+        session_id: &str,
+        update: impl FnOnce(&mut CallSession) + Send + 'static,
+    ) -> Option<CallSession> {
+        match self.store.update_session(session_id, Box::new(update)).await {
+            Ok(session) => session,
+            Err(e) => {
+                error!("❌ Failed to update session {}: {}", session_id, e);
+                None
+            }
+        }
+    }
+
+    /// Moves a session forward to `status` and returns its updated copy, or
+    /// `None` if the session is unknown. Sessions never go back, so a late
+    /// answer can't revive an ended call. Actual transitions are timestamped,
+    /// reported to webhooks and, once the call ends, written to the call history.
+    async fn set_session_status(&self, session_id: &str, status: CallStatus) -> Option<CallSession> {
+        let changed = Arc::new(AtomicBool::new(false));
+        let transition = changed.clone();
+        let session = self.update_session(session_id, move |session| {
+            if status > session.status {
+                transition.store(true, Ordering::SeqCst);
+                match status {
+                    CallStatus::Calling => {}
+                    CallStatus::Connected => session.answered_at = Some(Utc::now()),
+                    CallStatus::Ended => session.ended_at = Some(Utc::now()),
+                }
+                session.status = status;
+            }
+        })
+        .await?;
+
+        if changed.load(Ordering::SeqCst) {
+            let event_type = match session.status {
+                CallStatus::Calling => webhooks::CALL_STARTED,
+                CallStatus::Connected => webhooks::CALL_CONNECTED,
+                CallStatus::Ended => webhooks::CALL_ENDED,
+            };
+            self.emit(|| WebhookEvent::call(event_type, &session));
+
+            if let (CallStatus::Ended, Some(cdr), Some(record)) =
+                (&session.status, &self.cdr, CallRecord::from_session(&session))
+            {
+                cdr.record(record);
+            }
+        }
+        Some(session)
+    }
+
+    /// Ends the unfinished calls of a peer that went away without `bye`.
+    async fn end_calls_of(&self, peer_id: &str) {
+        let sessions = match self.store.sessions().await {
+            Ok(sessions) => sessions,
+            Err(e) => {
+                error!("❌ Failed to load sessions to end the calls of {}: {}", peer_id, e);
+                return;
+            }
+        };
+        let open: Vec<String> = sessions
+            .into_iter()
+            .filter(|session| !matches!(session.status, CallStatus::Ended))
+            .filter(|session| session.caller_id == peer_id || session.callee_id == peer_id)
+            .map(|session| session.session_id)
+            .collect();
+
+        for session_id in open {
+            info!("📞 CALL ENDED: {} disconnected during session {}", peer_id, session_id);
+            self.end_call(&session_id, EndReason::Disconnect).await;
+        }
+    }
+
+    /// Ends a session for `reason`, keeping the first reason if it already ended.
+    async fn end_call(&self, session_id: &str, reason: EndReason) -> Option<CallSession> {
+        self.update_session(session_id, move |session| {
+            session.end_reason.get_or_insert(reason);
+        })
+        .await;
+        self.set_session_status(session_id, CallStatus::Ended).await
+    }
+
+    /// Closes the connection of a registered peer. Returns false if the peer is unknown.
+    pub async fn disconnect_peer(&self, peer_id: &str, reason: &str) -> bool {
+        let closer = match self.peers.get(peer_id) {
+            Some(peer) => peer.closer.clone(),
+            None => return false,
+        };
+
+        info!("🔌 Disconnecting peer {}: {}", peer_id, reason);
+        // Close frame reasons are limited to 123 bytes
+        let mut reason = reason.to_string();
+        while reason.len() > 123 {
+            reason.pop();
+        }
+        let frame = CloseFrame {
+            code: close_code::POLICY,
+            reason: reason.into(),
+        };
+
+        match closer {
+            Some(closer) if closer.send(frame).is_ok() => {}
+            _ => {
+                // No live connection to close, just forget the peer
+                if let Some((_, peer)) = self.peers.remove(peer_id) {
+                    if let Some(cluster) = &self.cluster {
+                        cluster.unregister(peer_id);
+                    }
+                    self.emit(|| WebhookEvent::peer(webhooks::PEER_OFFLINE, &peer.info, peer.remote_addr));
+                    self.end_calls_of(peer_id).await;
+                }
+                self.notify_peers_update();
+            }
+        }
+        true
+    }
+
+    /// Ends a call on behalf of the server, sending `bye` to both sides.
+    /// Returns false if the session is unknown.
+    pub async fn end_session(&self, session_id: &str) -> bool {
+        let (caller_id, callee_id) = match self.end_call(session_id, EndReason::Admin).await {
+            Some(session) => (session.caller_id, session.callee_id),
+            None => return false,
+        };
+
+        info!("📞 CALL ENDED by server: session {}", session_id);
+        // Each side sees the bye as coming from the other party
+        for (peer_id, from) in [(&caller_id, &callee_id), (&callee_id, &caller_id)] {
+            let bye_message = Method::Bye(Byebye {
+                session_id: session_id.to_string(),
+                from: from.clone(),
+                reason: None,
+            });
+            if let Err(RouteError::Unreachable(e)) = self.route(peer_id, bye_message) {
+                error!("❌ Failed to notify {} of call end: {}", peer_id, e);
+                self.metrics.delivery_failures.with_label_values(&["bye"]).inc();
+            }
+        }
+        true
+    }
+
+    /// Revokes TURN credentials, either by exact TURN username (`<timestamp>:<user>`)
+    /// or every credential issued to `<user>`. Returns the revoked TURN usernames.
+    pub async fn revoke_turn_credentials(&self, username: &str) -> Result<Vec<String>> {
+        let revoked = self.store.remove_credentials(username).await?;
+        for turn_username in &revoked {
+            info!("🔑 Revoked TURN credential {}", turn_username);
+        }
+        Ok(revoked)
+    }
+
+    /// True if `peer_id` is connected here or, in cluster mode, on another node.
+    pub fn is_reachable(&self, peer_id: &str) -> bool {
+        self.peers.contains_key(peer_id)
+            || self.cluster.as_ref().is_some_and(|cluster| cluster.locate(peer_id).is_some())
+    }
+
+    /// Opens a `Connection` and returns it with the events its transport must
+    /// pass on.
+    pub fn connect(&self, remote_addr: Option<SocketAddr>) -> (Connection, ConnectionEvents) {
+        self.open_connection(remote_addr, self.rate_limiter.connection_limiter())
+    }
+
+    /// Like `connect`, for peers the server runs itself, such as the SFU. They
+    /// speak for many clients at once and are exempt from message rate limits.
+    pub fn connect_server_peer(&self) -> (Connection, ConnectionEvents) {
+        self.open_connection(None, ConnectionLimiter::exempt(self.config.clone()))
+    }
+
+    fn open_connection(
+        &self,
+        remote_addr: Option<SocketAddr>,
+        limiter: ConnectionLimiter,
+    ) -> (Connection, ConnectionEvents) {
+        let (sender, messages) = mpsc::unbounded_channel();
+        let (close_tx, close) = mpsc::unbounded_channel();
+        let state = ConnectionState {
+            peer_id: None,
+            capabilities: Capabilities::default(),
+            limiter,
+            close: None,
+            connected_at: Utc::now(),
+            remote_addr,
+            closer: Some(close_tx),
+        };
+        let connection = Connection {
+            state: Arc::new(tokio::sync::Mutex::new(state)),
+            sender,
+        };
+        (connection, ConnectionEvents { messages, close })
+    }
+
+    /// Handles `message` as if `connection` had sent it. Replies, including
+    /// errors, go to the connection's receiver.
+    pub async fn receive(&self, connection: &Connection, message: Method) -> Result<()> {
+        self.handle_decoded(Ok(message), &connection.sender, &connection.state).await
+    }
+
+    /// Like `receive`, for a JSON message as sent in a `/ws` text frame.
+    pub async fn receive_text(&self, connection: &Connection, text: &str) -> Result<()> {
+        let max_message_size = self.config.load().signaling.max_message_size;
+        let decoded = validation::check_size(text.len(), max_message_size).and_then(|_| codec::decode_text(text));
+        self.handle_decoded(decoded, &connection.sender, &connection.state).await
+    }
+
+    /// Unregisters the peer of `connection` and ends its calls, as when a
+    /// WebSocket closes.
+    pub async fn disconnect(&self, connection: &Connection) {
+        self.disconnected(&connection.state).await;
+    }
+
+    async fn disconnected(&self, state: &tokio::sync::Mutex<ConnectionState>) {
+        let (peer_id, remote_addr) = {
+            let state = state.lock().await;
+            (state.peer_id.clone(), state.remote_addr)
+        };
+        let removed = match &peer_id {
+            Some(id) => {
+                info!("Connection closed, removing peer: {}", id);
+                let removed = self.peers.remove(id).map(|(_, peer)| peer.info);
+                if let (Some(cluster), Some(_)) = (&self.cluster, &removed) {
+                    cluster.unregister(id);
+                }
+                self.notify_peers_update();
+                removed
+            }
+            None => {
+                info!("Connection closed before peer registration");
+                None
+            }
+        };
+        if let Some(peer) = &removed {
+            self.emit(|| WebhookEvent::peer(webhooks::PEER_OFFLINE, peer, remote_addr));
+            self.end_calls_of(&peer.id).await;
+        }
+        let ctx = HookContext { remote_addr, peer_id };
+        self.hooks.on_disconnect(&ctx, removed.as_ref()).await;
+    }
+
+    pub async fn handle_websocket(&self, socket: WebSocket, remote_addr: Option<SocketAddr>) {
+        info!("Starting WebSocket handler for new connection from {}", crate::modules::http::describe(remote_addr));
+        let mut codec = Codec::from_subprotocol(socket.protocol().and_then(|p| p.to_str().ok()));
+        info!("Using {:?} codec for connection", codec);
         let (mut sender, mut receiver) = socket.split();
         let (tx, mut rx) = mpsc::unbounded_channel::<Method>();
         
-        let peer_id = Arc::new(tokio::sync::Mutex::new(None::<String>));
-        let peer_id_clone = peer_id.clone();
-        let peers_clone = self.peers.clone();
+        let (close_tx, mut close_rx) = mpsc::unbounded_channel::<CloseFrame<'static>>();
+        let state = Arc::new(tokio::sync::Mutex::new(ConnectionState {
+            peer_id: None,
+            capabilities: Capabilities::default(),
+            limiter: self.rate_limiter.connection_limiter(),
+            close: None,
+            connected_at: Utc::now(),
+            remote_addr,
+            closer: Some(close_tx),
+        }));
 
         // Spawn task to handle outgoing messages
-        let send_task = tokio::spawn(async move {
+        let mut send_task = tokio::spawn(async move {
             while let Some(message) = rx.recv().await {
-                let msg_json = match serde_json::to_string(&message) {
-                    Ok(json) => json,
+                let frame = match codec.encode(&message) {
+                    Ok(frame) => frame,
                     Err(e) => {
                         error!("Failed to serialize message: {}", e);
                         continue;
                     }
                 };
 
-                if sender.send(Message::Text(msg_json)).await.is_err() {
-                    break;
+                if sender.send(frame).await.is_err() {
+                    return None;
+                }
+
+                // The hello reply is the last frame in the old codec, so the
+                // client knows exactly where binary framing starts
+                if let Method::Hello(hello) = &message {
+                    if hello.features.contains(&Feature::Binary) {
+                        codec = Codec::MessagePack;
+                    }
                 }
             }
+            Some(sender)
         });
 
+        self.active_connections.fetch_add(1, Ordering::SeqCst);
+        let mut shutdown_rx = self.shutdown.subscribe();
+        // Connections opened while draining still hear about it
+        shutdown_rx.mark_changed();
+
         // Handle incoming messages
         let ping_sender = tx.clone();
-        while let Some(msg) = receiver.next().await {
+        loop {
+            let msg = tokio::select! {
+                msg = receiver.next() => match msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+                Some(frame) = close_rx.recv() => {
+                    state.lock().await.close = Some(frame);
+                    break;
+                }
+                Ok(()) = shutdown_rx.changed() => {
+                    let phase = shutdown_rx.borrow_and_update().clone();
+                    match phase {
+                        ShutdownPhase::Running => continue,
+                        ShutdownPhase::Draining(goaway) => {
+                            if let Err(e) = tx.send(Method::GoAway(goaway)) {
+                                error!("Failed to send goaway: {}", e);
+                            }
+                            continue;
+                        }
+                        ShutdownPhase::Closing => {
+                            state.lock().await.close = Some(CloseFrame {
+                                code: close_code::AWAY,
+                                reason: "Server shutting down".into(),
+                            });
+                            break;
+                        }
+                    }
+                }
+            };
+
             match msg {
                 Ok(Message::Text(text)) => {
                     debug!("Received WebSocket text message: {}", text);
-                    if let Err(e) = self.handle_message(text, &tx, &peer_id_clone).await {
+                    let max_message_size = self.config.load().signaling.max_message_size;
+                    let decoded = validation::check_size(text.len(), max_message_size)
+                        .and_then(|_| codec::decode_text(&text));
+                    if let Err(e) = self.handle_decoded(decoded, &tx, &state).await {
+                        error!("Error handling message: {}", e);
+                    }
+                }
+                Ok(Message::Binary(data)) => {
+                    debug!("Received WebSocket binary message ({} bytes)", data.len());
+                    let max_message_size = self.config.load().signaling.max_message_size;
+                    let decoded = validation::check_size(data.len(), max_message_size)
+                        .and_then(|_| codec::decode_binary(&data));
+                    if let Err(e) = self.handle_decoded(decoded, &tx, &state).await {
                         error!("Error handling message: {}", e);
                     }
                 }
@@ -208,7 +1127,7 @@ impl Signaler {
                     info!("WebSocket connection closed gracefully: {:?}", close_frame);
                     break;
                 }
-                Ok(Message::Ping(data)) => {
+                Ok(Message::Ping(_)) => {
                     debug!("Received WebSocket ping, sending pong");
                     if let Err(e) = ping_sender.send(Method::Keepalive) {
                         error!("Failed to send pong response: {}", e);
@@ -222,52 +1141,196 @@ impl Signaler {
                     error!("WebSocket error: {}", e);
                     break;
                 }
-                _ => {
-                    debug!("Received other WebSocket message type");
-                }
             }
-        }
 
-        // Cleanup on disconnect
-        if let Some(id) = peer_id.lock().await.as_ref() {
-            info!("WebSocket disconnected, removing peer: {}", id);
-            peers_clone.remove(id);
-            self.notify_peers_update();
-        } else {
-            info!("WebSocket disconnected before peer registration");
+            if state.lock().await.close.is_some() {
+                break;
+            }
         }
 
+        self.disconnected(&state).await;
+
+        // Let queued replies (such as the error explaining a disconnect) flush before closing
+        drop(tx);
+        drop(ping_sender);
+        let flush_timeout = std::time::Duration::from_secs(1);
+        if let Ok(Ok(Some(mut sink))) = tokio::time::timeout(flush_timeout, &mut send_task).await {
+            let close = state.lock().await.close.take();
+            let _ = sink.send(Message::Close(close)).await;
+        }
         send_task.abort();
+        self.active_connections.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    async fn handle_decoded(
+        &self,
+        decoded: Result<Method, ValidationError>,
+        sender: &mpsc::UnboundedSender<Method>,
+        state: &Arc<tokio::sync::Mutex<ConnectionState>>,
+    ) -> Result<()> {
+        let (request, error) = match decoded {
+            Ok(message) => {
+                let request_type = message.request_type();
+                self.metrics.messages_received.with_label_values(&[request_type]).inc();
+
+                let mut connection = state.lock().await;
+                if !connection.limiter.check(request_type) {
+                    self.rate_limiter.record_breach(request_type);
+                    let exhausted = connection.limiter.exhausted();
+                    if exhausted {
+                        connection.close = Some(CloseFrame {
+                            code: close_code::POLICY,
+                            reason: "Rate limit exceeded".into(),
+                        });
+                    }
+                    drop(connection);
+
+                    warn!("⚠️ Rate limit exceeded for {} messages{}", request_type,
+                          if exhausted { ", disconnecting" } else { "" });
+                    sender.send(Method::Error(SignalingError {
+                        request: request_type.to_string(),
+                        reason: format!("Too many {} messages", request_type),
+                        rule: Some("rate_limit".to_string()),
+                    }))?;
+                    return Ok(());
+                }
+                let registered_id = connection.peer_id.clone();
+                let remote_addr = connection.remote_addr;
+                drop(connection);
+
+                if let Err(e) = validation::validate(&message, registered_id.as_deref()) {
+                    (message.request_type().to_string(), e)
+                } else {
+                    let ctx = HookContext {
+                        remote_addr,
+                        peer_id: registered_id,
+                    };
+                    return match self.run_hooks(&ctx, message).await {
+                        Ok(message) => self.handle_message(message, sender, state).await,
+                        Err((request, reason)) => {
+                            warn!("⚠️ Hook rejected {} message: {}", request, reason);
+                            self.metrics.messages_rejected.with_label_values(&["hook"]).inc();
+                            sender.send(Method::Error(SignalingError {
+                                request: request.to_string(),
+                                reason,
+                                rule: Some("hook".to_string()),
+                            }))?;
+                            Ok(())
+                        }
+                    };
+                }
+            }
+            Err(e) => (e.request().to_string(), e),
+        };
+
+        warn!("⚠️ Rejected {} message ({}): {}", request, error.rule(), error);
+        self.metrics.messages_rejected.with_label_values(&[error.rule()]).inc();
+        sender.send(error.to_reply(&request))?;
+        Ok(())
+    }
+
+    /// Passes a validated message through the matching hook. Returns the message
+    /// to handle, or the request type and reason if a hook rejected it.
+    async fn run_hooks(
+        &self,
+        ctx: &HookContext,
+        message: Method,
+    ) -> std::result::Result<Method, (&'static str, String)> {
+        let request = message.request_type();
+        let decided = match message {
+            Method::New(peer) => apply(self.hooks.on_register(ctx, &peer).await, peer).map(Method::New),
+            Method::Offer(offer) => apply(self.hooks.on_offer(ctx, &offer).await, offer).map(Method::Offer),
+            Method::Answer(answer) => {
+                apply(self.hooks.on_answer(ctx, &answer).await, answer).map(Method::Answer)
+            }
+            Method::Candidate(candidate) => {
+                apply(self.hooks.on_candidate(ctx, &candidate).await, candidate).map(Method::Candidate)
+            }
+            Method::Bye(bye) => apply(self.hooks.on_bye(ctx, &bye).await, bye).map(Method::Bye),
+            other => Ok(other),
+        };
+        decided.map_err(|reason| (request, reason))
     }
 
     async fn handle_message(
         &self,
-        text: String,
+        message: Method,
         sender: &mpsc::UnboundedSender<Method>,
-        peer_id: &Arc<tokio::sync::Mutex<Option<String>>>,
+        state: &Arc<tokio::sync::Mutex<ConnectionState>>,
     ) -> Result<()> {
-        debug!("Received message: {}", text);
+        debug!("Received message: {:?}", message);
         
-        let message: Method = serde_json::from_str(&text)?;
+        let capabilities = state.lock().await.capabilities.clone();
 
         match message {
+            Method::Hello(hello) => {
+                match protocol::negotiate(&hello) {
+                    Ok(negotiated) => {
+                        info!("🤝 Negotiated protocol v{} with features {:?}",
+                              negotiated.version, negotiated.features);
+
+                        let mut state = state.lock().await;
+                        if let Some(id) = state.peer_id.as_ref() {
+                            if let Some(mut peer) = self.peers.get_mut(id) {
+                                peer.capabilities = negotiated.clone();
+                            }
+                        }
+                        state.capabilities = negotiated.clone();
+
+                        if let Err(e) = sender.send(Method::Hello(negotiated.to_hello())) {
+                            error!("Failed to send hello response: {}", e);
+                        }
+                    }
+                    Err(reason) => {
+                        warn!("⚠️ Protocol negotiation failed: {}", reason);
+                        self.metrics.messages_rejected.with_label_values(&["protocol_version"]).inc();
+                        let error_msg = Method::Error(SignalingError {
+                            request: "hello".to_string(),
+                            reason,
+                            rule: Some("protocol_version".to_string()),
+                        });
+                        let _ = sender.send(error_msg);
+                    }
+                }
+            }
             Method::New(peer_info) => {
                 info!("Registering new peer: {} (ID: {}, User-Agent: {})", 
                       peer_info.name, peer_info.id, peer_info.user_agent);
                 
+                let mut state = state.lock().await;
                 let peer = Peer {
                     info: peer_info.clone(),
                     sender: sender.clone(),
+                    capabilities: capabilities.clone(),
+                    connected_at: state.connected_at,
+                    remote_addr: state.remote_addr,
+                    closer: state.closer.clone(),
                 };
                 
                 self.peers.insert(peer_info.id.clone(), peer);
-                *peer_id.lock().await = Some(peer_info.id.clone());
+                self.metrics.registrations.inc();
+                state.peer_id = Some(peer_info.id.clone());
+                let remote_addr = state.remote_addr;
+                drop(state);
+                if let Some(cluster) = &self.cluster {
+                    cluster.register(&peer_info);
+                }
+                self.emit(|| WebhookEvent::peer(webhooks::PEER_ONLINE, &peer_info, remote_addr));
                 
                 info!("Peer {} successfully registered, notifying all peers", peer_info.id);
                 self.notify_peers_update();
             }
             Method::Offer(ref data) => {
-                if let Ok(negotiation) = serde_json::from_value::<Negotiation>(data.clone()) {
+                let negotiation = data.negotiation();
+                // Offers renegotiating a live call, from either side, keep its session
+                let renegotiation = matches!(
+                    self.store.session(&negotiation.session_id).await,
+                    Ok(Some(session)) if !matches!(session.status, CallStatus::Ended)
+                );
+                if renegotiation {
+                    info!("🔄 CALL RENEGOTIATED: {} re-offering to {} (session: {})",
+                          negotiation.from, negotiation.to, negotiation.session_id);
+                } else {
                     info!("📞 CALL INITIATED: {} calling {} (session: {})", 
                           negotiation.from, negotiation.to, negotiation.session_id);
                     
@@ -278,137 +1341,149 @@ impl Signaler {
                         callee_id: negotiation.to.clone(),
                         started_at: Utc::now(),
                         status: CallStatus::Calling,
+                        room: data.extra.get("room").and_then(|room| room.as_str()).map(str::to_string),
+                        answered_at: None,
+                        ended_at: None,
+                        end_reason: None,
+                        turn_used: false,
                     };
-                    self.sessions.insert(negotiation.session_id.clone(), session);
+                    self.emit(|| WebhookEvent::call(webhooks::CALL_STARTED, &session));
+                    if let Err(e) = self.store.put_session(&session).await {
+                        error!("❌ Failed to store session {}: {}", negotiation.session_id, e);
+                    }
                     info!("📝 Call session created: {}", negotiation.session_id);
-                    
-                    if let Some(target_peer) = self.peers.get(&negotiation.to) {
-                        info!("📤 Forwarding offer to recipient: {}", negotiation.to);
-                        let relay_message = Method::Offer(data.clone());
-                        
-                        if let Err(e) = target_peer.sender.send(relay_message) {
-                            error!("❌ Failed to deliver offer to {}: {}", negotiation.to, e);
-                            // Update session status to ended
-                            if let Some(mut session) = self.sessions.get_mut(&negotiation.session_id) {
-                                session.status = CallStatus::Ended;
+                }
+                
+                info!("📤 Forwarding offer to recipient: {}", negotiation.to);
+                match self.route(&negotiation.to, Method::Offer(data.clone())) {
+                    Ok(()) => {
+                        info!("✅ Offer successfully delivered to {}", negotiation.to);
+                        send_ack(&capabilities, sender, "offer", Some(&negotiation.session_id));
+                    }
+                    Err(e) => {
+                        let reason = match e {
+                            RouteError::Unreachable(e) => {
+                                error!("❌ Failed to deliver offer to {}: {}", negotiation.to, e);
+                                format!("Recipient [{}] unreachable", negotiation.to)
                             }
-                            let error_msg = Method::Error(SignalingError {
-                                request: "offer".to_string(),
-                                reason: format!("Recipient [{}] unreachable", negotiation.to),
-                            });
-                            let _ = sender.send(error_msg);
-                        } else {
-                            info!("✅ Offer successfully delivered to {}", negotiation.to);
-                        }
-                    } else {
-                        error!("❌ CALL FAILED: Recipient {} not found", negotiation.to);
-                        // Update session status to ended
-                        if let Some(mut session) = self.sessions.get_mut(&negotiation.session_id) {
-                            session.status = CallStatus::Ended;
-                        }
+                            RouteError::NotFound => {
+                                error!("❌ CALL FAILED: Recipient {} not found", negotiation.to);
+                                format!("Recipient [{}] not available", negotiation.to)
+                            }
+                        };
+                        self.metrics.delivery_failures.with_label_values(&["offer"]).inc();
+                        self.end_call(&negotiation.session_id, EndReason::Unreachable).await;
                         let error_msg = Method::Error(SignalingError {
                             request: "offer".to_string(),
-                            reason: format!("Recipient [{}] not available", negotiation.to),
+                            reason,
+                            rule: None,
                         });
                         let _ = sender.send(error_msg);
                     }
-                } else {
-                    error!("❌ Invalid offer format: {:?}", data);
                 }
             }
             Method::Answer(ref data) => {
-                if let Ok(negotiation) = serde_json::from_value::<Negotiation>(data.clone()) {
-                    info!("📞 CALL ANSWERED: {} answered call from {} (session: {})", 
-                          negotiation.from, negotiation.to, negotiation.session_id);
-                    
-                    // Update session status to connected
-                    if let Some(mut session) = self.sessions.get_mut(&negotiation.session_id) {
-                        session.status = CallStatus::Connected;
-                        info!("🔗 Call session connected: {}", negotiation.session_id);
-                    } else {
-                        warn!("⚠️ No session found for answer: {}", negotiation.session_id);
+                let negotiation = data.negotiation();
+                info!("📞 CALL ANSWERED: {} answered call from {} (session: {})", 
+                      negotiation.from, negotiation.to, negotiation.session_id);
+                
+                if self.mark_connected(&negotiation.session_id).await {
+                    info!("🔗 Call session connected: {}", negotiation.session_id);
+                } else if self.peers.contains_key(&negotiation.to) {
+                    // Otherwise the caller's node tracks the session
+                    warn!("⚠️ No session found for answer: {}", negotiation.session_id);
+                }
+                
+                info!("📤 Forwarding answer to caller: {}", negotiation.to);
+                match self.route(&negotiation.to, Method::Answer(data.clone())) {
+                    Ok(()) => {
+                        info!("✅ Answer successfully delivered to {}", negotiation.to);
+                        send_ack(&capabilities, sender, "answer", Some(&negotiation.session_id));
                     }
-                    
-                    if let Some(target_peer) = self.peers.get(&negotiation.to) {
-                        info!("📤 Forwarding answer to caller: {}", negotiation.to);
-                        let relay_message = Method::Answer(data.clone());
-                        
-                        if let Err(e) = target_peer.sender.send(relay_message) {
-                            error!("❌ Failed to deliver answer to {}: {}", negotiation.to, e);
-                            // Update session status to ended
-                            if let Some(mut session) = self.sessions.get_mut(&negotiation.session_id) {
-                                session.status = CallStatus::Ended;
+                    Err(e) => {
+                        let reason = match e {
+                            RouteError::Unreachable(e) => {
+                                error!("❌ Failed to deliver answer to {}: {}", negotiation.to, e);
+                                format!("Caller [{}] unreachable", negotiation.to)
                             }
-                            let error_msg = Method::Error(SignalingError {
-                                request: "answer".to_string(),
-                                reason: format!("Caller [{}] unreachable", negotiation.to),
-                            });
-                            let _ = sender.send(error_msg);
-                        } else {
-                            info!("✅ Answer successfully delivered to {}", negotiation.to);
-                        }
-                    } else {
-                        error!("❌ ANSWER FAILED: Caller {} not found", negotiation.to);
-                        // Update session status to ended
-                        if let Some(mut session) = self.sessions.get_mut(&negotiation.session_id) {
-                            session.status = CallStatus::Ended;
-                        }
+                            RouteError::NotFound => {
+                                error!("❌ ANSWER FAILED: Caller {} not found", negotiation.to);
+                                format!("Caller [{}] no longer available", negotiation.to)
+                            }
+                        };
+                        self.metrics.delivery_failures.with_label_values(&["answer"]).inc();
+                        self.end_call(&negotiation.session_id, EndReason::Unreachable).await;
                         let error_msg = Method::Error(SignalingError {
                             request: "answer".to_string(),
-                            reason: format!("Caller [{}] no longer available", negotiation.to),
+                            reason,
+                            rule: None,
                         });
                         let _ = sender.send(error_msg);
                     }
-                } else {
-                    error!("❌ Invalid answer format: {:?}", data);
                 }
             }
             Method::Candidate(ref data) => {
-                if let Ok(negotiation) = serde_json::from_value::<Negotiation>(data.clone()) {
-                    debug!("🔗 ICE candidate from {} to {} (session: {})", 
-                          negotiation.from, negotiation.to, negotiation.session_id);
-                    
-                    if let Some(target_peer) = self.peers.get(&negotiation.to) {
-                        let relay_message = Method::Candidate(data.clone());
-                        
-                        if let Err(e) = target_peer.sender.send(relay_message) {
-                            warn!("⚠️ Failed to relay ICE candidate to {}: {}", negotiation.to, e);
-                        } else {
-                            debug!("✅ ICE candidate relayed to {}", negotiation.to);
-                        }
-                    } else {
+                let negotiation = data.negotiation();
+                debug!("🔗 ICE candidate from {} to {} (session: {})", 
+                      negotiation.from, negotiation.to, negotiation.session_id);
+                
+                self.mark_turn_used(data).await;
+
+                match self.route(&negotiation.to, Method::Candidate(data.clone())) {
+                    Ok(()) => {
+                        debug!("✅ ICE candidate relayed to {}", negotiation.to);
+                        send_ack(&capabilities, sender, "candidate", Some(&negotiation.session_id));
+                    }
+                    Err(RouteError::Unreachable(e)) => {
+                        warn!("⚠️ Failed to relay ICE candidate to {}: {}", negotiation.to, e);
+                        self.metrics.delivery_failures.with_label_values(&["candidate"]).inc();
+                    }
+                    Err(RouteError::NotFound) => {
                         warn!("⚠️ ICE candidate target peer {} not found", negotiation.to);
+                        self.metrics.delivery_failures.with_label_values(&["candidate"]).inc();
                     }
-                } else {
-                    error!("❌ Invalid ICE candidate format: {:?}", data);
                 }
             }
             Method::Bye(bye) => {
                 info!("📞 CALL ENDED: {} ended call for session {}", bye.from, bye.session_id);
                 
-                // Update session status to ended
-                if let Some(mut session) = self.sessions.get_mut(&bye.session_id) {
-                    session.status = CallStatus::Ended;
+                if self.end_call(&bye.session_id, EndReason::Bye).await.is_some() {
                     info!("📝 Call session ended: {}", bye.session_id);
                 }
                 
-                let session_parts: Vec<&str> = bye.session_id.split('-').collect();
+                // Peer ids such as `echo-bot` contain '-' themselves, so split
+                // around the sender's id when the session starts or ends with it
+                let caller_prefix = format!("{}-", bye.from);
+                let callee_suffix = format!("-{}", bye.from);
+                let session_parts: Vec<&str> = if let Some(callee) = bye.session_id.strip_prefix(&caller_prefix) {
+                    vec![bye.from.as_str(), callee]
+                } else if let Some(caller) = bye.session_id.strip_suffix(&callee_suffix) {
+                    vec![caller, bye.from.as_str()]
+                } else {
+                    bye.session_id.split('-').collect()
+                };
                 if session_parts.len() == 2 {
                     for &peer_id in &session_parts {
                         if peer_id != bye.from { // Don't send bye back to sender
-                            if let Some(peer) = self.peers.get(peer_id) {
-                                info!("📤 Notifying {} that call ended", peer_id);
-                                let bye_message = Method::Bye(Byebye {
-                                    session_id: bye.session_id.clone(),
-                                    from: bye.from.clone(),
-                                });
-                                if let Err(e) = peer.sender.send(bye_message) {
-                                    error!("❌ Failed to notify {} of call end: {}", peer_id, e);
-                                } else {
+                            info!("📤 Notifying {} that call ended", peer_id);
+                            let bye_message = Method::Bye(Byebye {
+                                session_id: bye.session_id.clone(),
+                                from: bye.from.clone(),
+                                reason: bye.reason.clone(),
+                            });
+                            match self.route(peer_id, bye_message) {
+                                Ok(()) => {
                                     info!("✅ Call end notification sent to {}", peer_id);
+                                    send_ack(&capabilities, sender, "bye", Some(&bye.session_id));
+                                }
+                                Err(RouteError::Unreachable(e)) => {
+                                    error!("❌ Failed to notify {} of call end: {}", peer_id, e);
+                                    self.metrics.delivery_failures.with_label_values(&["bye"]).inc();
+                                }
+                                Err(RouteError::NotFound) => {
+                                    warn!("⚠️ Peer {} not found for call end notification", peer_id);
+                                    self.metrics.delivery_failures.with_label_values(&["bye"]).inc();
                                 }
-                            } else {
-                                warn!("⚠️ Peer {} not found for call end notification", peer_id);
                             }
                         }
                     }
@@ -429,4 +1504,175 @@ impl Signaler {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+// Drops expired TURN credentials and old ended sessions every minute until the
+// signaler is dropped
+async fn purge_state(signaler: Weak<Signaler>) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+        let Some(signaler) = signaler.upgrade() else { break };
+
+        let now = Utc::now();
+        let retention = Duration::seconds(signaler.config.load().store.session_retention as i64);
+        match signaler.store.purge(now, now - retention).await {
+            Ok(0) => {}
+            Ok(purged) => debug!("Purged {} expired credentials and sessions", purged),
+            Err(e) => warn!("⚠️ Failed to purge state store: {}", e),
+        }
+    }
+}
+
+// Applies cluster events until the signaler is dropped
+async fn cluster_events(signaler: Weak<Signaler>, mut events: mpsc::UnboundedReceiver<ClusterEvent>) {
+    while let Some(event) = events.recv().await {
+        let Some(signaler) = signaler.upgrade() else { break };
+        signaler.handle_cluster_event(event).await;
+    }
+}
+
+fn apply<T>(decision: HookDecision<T>, original: T) -> std::result::Result<T, String> {
+    match decision {
+        HookDecision::Allow => Ok(original),
+        HookDecision::Reject(reason) => Err(reason),
+        HookDecision::Rewrite(rewritten) => Ok(rewritten),
+    }
+}
+
+/// Confirms a relayed request to connections that negotiated the `acks` feature.
+fn send_ack(
+    capabilities: &Capabilities,
+    sender: &mpsc::UnboundedSender<Method>,
+    request: &str,
+    session_id: Option<&str>,
+) {
+    if !capabilities.supports(Feature::Acks) {
+        return;
+    }
+
+    let ack = Method::Ack(Ack {
+        request: request.to_string(),
+        session_id: session_id.map(str::to_string),
+    });
+    if let Err(e) = sender.send(ack) {
+        error!("Failed to send {} ack: {}", request, e);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::cdr::CdrQuery;
+    use serde_json::json;
+
+    async fn client(signaler: &Signaler, id: &str) -> (Connection, ConnectionEvents) {
+        let (connection, events) = signaler.connect(None);
+        send(signaler, &connection, json!({"type": "new", "data": {"id": id, "name": id, "user_agent": "test"}})).await;
+        (connection, events)
+    }
+
+    async fn send(signaler: &Signaler, connection: &Connection, message: serde_json::Value) {
+        signaler.receive(connection, serde_json::from_value(message).unwrap()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn late_answers_do_not_revive_ended_calls() {
+        let dir = std::env::temp_dir().join(format!("signaling-{}", uuid::Uuid::new_v4().simple()));
+        let cdr = Arc::new(CdrStore::open(dir.join("cdr.db")).unwrap());
+        let config = Config::builder().build().unwrap();
+        let signaler = Signaler::builder().config(config).cdr(cdr.clone()).build().unwrap();
+        let (alice, _alice_events) = client(&signaler, "alice").await;
+        let (bob, _bob_events) = client(&signaler, "bob").await;
+
+        let description = json!({"type": "offer", "sdp": "v=0\r\n"});
+        let offer = json!({"from": "alice", "to": "bob", "session_id": "alice-bob", "description": description});
+        send(&signaler, &alice, json!({"type": "offer", "data": offer})).await;
+        send(&signaler, &bob, json!({"type": "bye", "data": {"session_id": "alice-bob", "from": "bob"}})).await;
+        let answer = json!({"from": "bob", "to": "alice", "session_id": "alice-bob", "description": {"type": "answer", "sdp": "v=0\r\n"}});
+        send(&signaler, &bob, json!({"type": "answer", "data": answer})).await;
+
+        let session = signaler.store.session("alice-bob").await.unwrap().unwrap();
+        assert!(matches!(session.status, CallStatus::Ended));
+        assert!(session.answered_at.is_none());
+
+        // The writer thread records asynchronously, give a second row time to show up
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        let records = cdr.query(&CdrQuery::default()).unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].answered_at.is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn webhooks_only_see_forward_transitions() {
+        use axum::{extract::State, http::HeaderMap, routing::post, Router};
+
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(|State(received): State<Arc<std::sync::Mutex<Vec<String>>>>, headers: HeaderMap| async move {
+                    received.lock().unwrap().push(headers["X-Webhook-Event"].to_str().unwrap().to_string());
+                }),
+            )
+            .with_state(received.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let config = Config::builder()
+            .set("webhooks", "urls", &url)
+            .set("webhooks", "events", "call.started,call.connected,call.ended")
+            .build()
+            .unwrap();
+        let signaler = Signaler::builder().config(config).build().unwrap();
+        let (alice, _alice_events) = client(&signaler, "alice").await;
+        let (bob, _bob_events) = client(&signaler, "bob").await;
+
+        let description = json!({"type": "offer", "sdp": "v=0\r\n"});
+        let offer = json!({"from": "alice", "to": "bob", "session_id": "alice-bob", "description": description});
+        send(&signaler, &alice, json!({"type": "offer", "data": offer})).await;
+        send(&signaler, &bob, json!({"type": "bye", "data": {"session_id": "alice-bob", "from": "bob"}})).await;
+        let answer = json!({"from": "bob", "to": "alice", "session_id": "alice-bob", "description": {"type": "answer", "sdp": "v=0\r\n"}});
+        send(&signaler, &bob, json!({"type": "answer", "data": answer})).await;
+
+        let webhooks = signaler.webhooks.clone().unwrap();
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+        while webhooks.pending() > 0 && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(*received.lock().unwrap(), ["call.started", "call.ended"]);
+    }
+
+    fn error_rule(message: Method) -> Option<String> {
+        match message {
+            Method::Error(error) => error.rule,
+            other => panic!("expected an error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn unsupported_hellos_name_the_rule() {
+        let signaler = Signaler::builder().config(Config::builder().build().unwrap()).build().unwrap();
+        let (connection, mut events) = signaler.connect(None);
+        send(&signaler, &connection, json!({"type": "hello", "data": {"version": 0, "features": []}})).await;
+        assert_eq!(error_rule(events.messages.try_recv().unwrap()).as_deref(), Some("protocol_version"));
+    }
+
+    #[tokio::test]
+    async fn connections_cannot_register_a_second_id() {
+        let signaler = Signaler::builder().config(Config::builder().build().unwrap()).build().unwrap();
+        let (alice, mut events) = client(&signaler, "alice").await;
+        while events.messages.try_recv().is_ok() {}
+
+        send(&signaler, &alice, json!({"type": "new", "data": {"id": "mallory", "name": "mallory", "user_agent": "test"}})).await;
+        assert_eq!(error_rule(events.messages.try_recv().unwrap()).as_deref(), Some("already_registered"));
+        assert!(signaler.peers.contains_key("alice"));
+        assert!(!signaler.peers.contains_key("mallory"));
+
+        // Offers still go out as alice only
+        let offer = json!({"from": "mallory", "to": "bob", "session_id": "mallory-bob", "description": {"type": "offer", "sdp": "v=0\r\n"}});
+        send(&signaler, &alice, json!({"type": "offer", "data": offer})).await;
+        assert_eq!(error_rule(events.messages.try_recv().unwrap()).as_deref(), Some("sender_mismatch"));
+    }
+}
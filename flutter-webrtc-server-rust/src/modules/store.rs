@@ -0,0 +1,456 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use dashmap::DashMap;
+use log::info;
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::modules::signaling::{CallSession, CallStatus, EndReason, ExpiredCredential};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sessions (
+    session_id TEXT PRIMARY KEY,
+    ended_at TEXT,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS sessions_ended_at ON sessions (ended_at);
+CREATE TABLE IF NOT EXISTS turn_credentials (
+    username TEXT PRIMARY KEY,
+    expires_at TEXT NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS turn_credentials_expires_at ON turn_credentials (expires_at);
+";
+
+/// Where `Signaler` keeps call sessions and the TURN credentials it issued.
+///
+/// Errors are logged by the caller and the operation is treated as a miss.
+#[async_trait]
+pub trait StateStore: Send + Sync {
+    async fn session(&self, session_id: &str) -> Result<Option<CallSession>>;
+    async fn sessions(&self) -> Result<Vec<CallSession>>;
+    /// Inserts or replaces a session.
+    async fn put_session(&self, session: &CallSession) -> Result<()>;
+    /// Applies `update` to a session atomically and returns the updated copy,
+    /// or `None` if the session is unknown.
+    async fn update_session(&self, session_id: &str, update: SessionUpdate) -> Result<Option<CallSession>>;
+
+    async fn credential(&self, turn_username: &str) -> Result<Option<ExpiredCredential>>;
+    async fn put_credential(&self, credential: &ExpiredCredential) -> Result<()>;
+    /// Removes the credential with TURN username `username` (`<timestamp>:<user>`),
+    /// or every credential issued to user `username`. Returns the removed TURN usernames.
+    async fn remove_credentials(&self, username: &str) -> Result<Vec<String>>;
+
+    /// Drops credentials expired at `now` and sessions that ended before
+    /// `ended_before`. Returns how many entries were removed.
+    async fn purge(&self, now: DateTime<Utc>, ended_before: DateTime<Utc>) -> Result<usize>;
+}
+
+/// Change applied by `StateStore::update_session`.
+pub type SessionUpdate = Box<dyn FnOnce(&mut CallSession) + Send>;
+
+/// State in process memory, lost on restart. The default.
+#[derive(Default)]
+pub struct MemoryStore {
+    sessions: DashMap<String, CallSession>,
+    credentials: DashMap<String, ExpiredCredential>,
+}
+
+#[async_trait]
+impl StateStore for MemoryStore {
+    async fn session(&self, session_id: &str) -> Result<Option<CallSession>> {
+        Ok(self.sessions.get(session_id).map(|session| session.clone()))
+    }
+
+    async fn sessions(&self) -> Result<Vec<CallSession>> {
+        Ok(self.sessions.iter().map(|session| session.value().clone()).collect())
+    }
+
+    async fn put_session(&self, session: &CallSession) -> Result<()> {
+        self.sessions.insert(session.session_id.clone(), session.clone());
+        Ok(())
+    }
+
+    async fn update_session(&self, session_id: &str, update: SessionUpdate) -> Result<Option<CallSession>> {
+        Ok(self.sessions.get_mut(session_id).map(|mut session| {
+            update(&mut session);
+            session.clone()
+        }))
+    }
+
+    async fn credential(&self, turn_username: &str) -> Result<Option<ExpiredCredential>> {
+        Ok(self.credentials.get(turn_username).map(|credential| credential.clone()))
+    }
+
+    async fn put_credential(&self, credential: &ExpiredCredential) -> Result<()> {
+        self.credentials
+            .insert(credential.credential.username.clone(), credential.clone());
+        Ok(())
+    }
+
+    async fn remove_credentials(&self, username: &str) -> Result<Vec<String>> {
+        let suffix = format!(":{}", username);
+        let mut removed = Vec::new();
+        self.credentials.retain(|turn_username, _| {
+            let matches = turn_username == username || turn_username.ends_with(&suffix);
+            if matches {
+                removed.push(turn_username.clone());
+            }
+            !matches
+        });
+        Ok(removed)
+    }
+
+    async fn purge(&self, now: DateTime<Utc>, ended_before: DateTime<Utc>) -> Result<usize> {
+        let before = self.credentials.len() + self.sessions.len();
+        self.credentials.retain(|_, credential| credential.expires_at > now);
+        self.sessions
+            .retain(|_, session| session.ended_at.is_none_or(|ended_at| ended_at >= ended_before));
+        Ok(before - self.credentials.len() - self.sessions.len())
+    }
+}
+
+/// State in an SQLite database, kept across restarts. Nodes on one host can
+/// share the file to validate each other's credentials and see each other's
+/// sessions. Queries run on Tokio's blocking threads.
+pub struct SqliteStore {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStore {
+    /// Opens or creates the database at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let connection = Connection::open(path)
+            .map_err(|e| anyhow::anyhow!("Failed to open state store {}: {}", path.display(), e))?;
+        // WAL and a busy timeout let several server processes share the file
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.busy_timeout(Duration::from_secs(5))?;
+        connection.execute_batch(SCHEMA)?;
+
+        info!("Keeping sessions and TURN credentials in {}", path.display());
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    /// Ends the sessions a previous run left open, which no client can resume.
+    /// Returns how many were ended. Blocks on SQLite, meant for startup.
+    pub fn end_open_sessions(&self, now: DateTime<Utc>) -> Result<usize> {
+        let mut connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+        let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let open = {
+            let mut statement = transaction.prepare("SELECT data FROM sessions WHERE ended_at IS NULL")?;
+            let rows = statement.query_map([], |row| row.get::<_, String>(0))?;
+            rows.map(|data| Ok(serde_json::from_str::<CallSession>(&data?)?))
+                .collect::<Result<Vec<_>>>()?
+        };
+        for mut session in open.iter().cloned() {
+            session.status = CallStatus::Ended;
+            session.ended_at = Some(now);
+            session.end_reason.get_or_insert(EndReason::Disconnect);
+            upsert_session(&transaction, &session)?;
+        }
+        transaction.commit()?;
+        Ok(open.len())
+    }
+
+    // Runs `query` with the connection on a blocking thread
+    async fn blocking<T: Send + 'static>(
+        &self,
+        query: impl FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let connection = self.connection.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection.lock().unwrap_or_else(|e| e.into_inner());
+            query(&mut connection)
+        })
+        .await?
+    }
+}
+
+#[async_trait]
+impl StateStore for SqliteStore {
+    async fn session(&self, session_id: &str) -> Result<Option<CallSession>> {
+        let session_id = session_id.to_string();
+        self.blocking(move |connection| {
+            let data: Option<String> = connection
+                .query_row("SELECT data FROM sessions WHERE session_id = ?1", [session_id], |row| row.get(0))
+                .optional()?;
+            Ok(data.map(|data| serde_json::from_str(&data)).transpose()?)
+        })
+        .await
+    }
+
+    async fn sessions(&self) -> Result<Vec<CallSession>> {
+        self.blocking(|connection| {
+            let mut statement = connection.prepare("SELECT data FROM sessions")?;
+            let rows = statement.query_map([], |row| row.get::<_, String>(0))?;
+            rows.map(|data| Ok(serde_json::from_str(&data?)?)).collect()
+        })
+        .await
+    }
+
+    async fn put_session(&self, session: &CallSession) -> Result<()> {
+        let session = session.clone();
+        self.blocking(move |connection| upsert_session(connection, &session)).await
+    }
+
+    async fn update_session(&self, session_id: &str, update: SessionUpdate) -> Result<Option<CallSession>> {
+        let session_id = session_id.to_string();
+        self.blocking(move |connection| {
+            // Immediate, so concurrent updates from other processes cannot interleave
+            let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let data: Option<String> = transaction
+                .query_row("SELECT data FROM sessions WHERE session_id = ?1", [session_id], |row| row.get(0))
+                .optional()?;
+            let Some(data) = data else {
+                return Ok(None);
+            };
+
+            let mut session: CallSession = serde_json::from_str(&data)?;
+            update(&mut session);
+            upsert_session(&transaction, &session)?;
+            transaction.commit()?;
+            Ok(Some(session))
+        })
+        .await
+    }
+
+    async fn credential(&self, turn_username: &str) -> Result<Option<ExpiredCredential>> {
+        let turn_username = turn_username.to_string();
+        self.blocking(move |connection| {
+            let data: Option<String> = connection
+                .query_row(
+                    "SELECT data FROM turn_credentials WHERE username = ?1",
+                    [turn_username],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            Ok(data.map(|data| serde_json::from_str(&data)).transpose()?)
+        })
+        .await
+    }
+
+    async fn put_credential(&self, credential: &ExpiredCredential) -> Result<()> {
+        let credential = credential.clone();
+        self.blocking(move |connection| {
+            connection.execute(
+                "INSERT OR REPLACE INTO turn_credentials (username, expires_at, data) VALUES (?1, ?2, ?3)",
+                params![
+                    credential.credential.username,
+                    timestamp(credential.expires_at),
+                    serde_json::to_string(&credential)?,
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn remove_credentials(&self, username: &str) -> Result<Vec<String>> {
+        let username = username.to_string();
+        self.blocking(move |connection| {
+            let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let suffix = format!(":{}", username);
+            // substr rather than LIKE, so `%` and `_` in user names match literally
+            let removed = {
+                let mut statement = transaction.prepare(
+                    "DELETE FROM turn_credentials
+                     WHERE username = ?1
+                        OR (length(username) > length(?2) AND substr(username, -length(?2)) = ?2)
+                     RETURNING username",
+                )?;
+                let rows = statement.query_map(params![username, suffix], |row| row.get::<_, String>(0))?;
+                rows.collect::<Result<Vec<_>, _>>()?
+            };
+            transaction.commit()?;
+            Ok(removed)
+        })
+        .await
+    }
+
+    async fn purge(&self, now: DateTime<Utc>, ended_before: DateTime<Utc>) -> Result<usize> {
+        self.blocking(move |connection| {
+            let credentials = connection.execute(
+                "DELETE FROM turn_credentials WHERE expires_at <= ?1",
+                [timestamp(now)],
+            )?;
+            let sessions = connection.execute(
+                "DELETE FROM sessions WHERE ended_at IS NOT NULL AND ended_at < ?1",
+                [timestamp(ended_before)],
+            )?;
+            Ok(credentials + sessions)
+        })
+        .await
+    }
+}
+
+// Fixed-width UTC timestamps, so string comparison in SQL orders them correctly
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn upsert_session(connection: &Connection, session: &CallSession) -> Result<()> {
+    connection.execute(
+        "INSERT OR REPLACE INTO sessions (session_id, ended_at, data) VALUES (?1, ?2, ?3)",
+        params![
+            session.session_id,
+            session.ended_at.map(timestamp),
+            serde_json::to_string(session)?,
+        ],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::signaling::TurnCredentials;
+
+    fn temp_path() -> std::path::PathBuf {
+        std::env::temp_dir()
+            .join(format!("store-{}", uuid::Uuid::new_v4().simple()))
+            .join("state.sqlite")
+    }
+
+    fn session(session_id: &str) -> CallSession {
+        let (caller_id, callee_id) = session_id.split_once('-').unwrap();
+        CallSession {
+            session_id: session_id.to_string(),
+            caller_id: caller_id.to_string(),
+            callee_id: callee_id.to_string(),
+            started_at: Utc::now(),
+            status: CallStatus::Calling,
+            room: None,
+            answered_at: None,
+            ended_at: None,
+            end_reason: None,
+            turn_used: false,
+        }
+    }
+
+    fn credential(turn_username: &str, expires_at: DateTime<Utc>) -> ExpiredCredential {
+        ExpiredCredential {
+            credential: TurnCredentials {
+                username: turn_username.to_string(),
+                password: "password".to_string(),
+                ttl: 60,
+                uris: Vec::new(),
+            },
+            expires_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn state_survives_reopening() {
+        let path = temp_path();
+        let expires_at = Utc::now() + chrono::Duration::hours(1);
+        {
+            let store = SqliteStore::open(&path).unwrap();
+            store.put_session(&session("alice-bob")).await.unwrap();
+            store.put_credential(&credential("1:alice", expires_at)).await.unwrap();
+        }
+
+        let store = SqliteStore::open(&path).unwrap();
+        assert_eq!(store.session("alice-bob").await.unwrap().unwrap().caller_id, "alice");
+        assert_eq!(store.sessions().await.unwrap().len(), 1);
+        let restored = store.credential("1:alice").await.unwrap().unwrap();
+        assert_eq!(restored.credential.password, "password");
+        assert_eq!(restored.expires_at.timestamp_millis(), expires_at.timestamp_millis());
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn sessions_left_open_are_ended_on_startup() {
+        let path = temp_path();
+        let store = SqliteStore::open(&path).unwrap();
+        store.put_session(&session("alice-bob")).await.unwrap();
+        let mut ended = session("carol-dave");
+        ended.status = CallStatus::Ended;
+        ended.ended_at = Some(Utc::now() - chrono::Duration::minutes(5));
+        ended.end_reason = Some(EndReason::Bye);
+        store.put_session(&ended).await.unwrap();
+        drop(store);
+
+        let store = SqliteStore::open(&path).unwrap();
+        assert_eq!(store.end_open_sessions(Utc::now()).unwrap(), 1);
+        let restored = store.session("alice-bob").await.unwrap().unwrap();
+        assert!(matches!(restored.status, CallStatus::Ended));
+        assert!(matches!(restored.end_reason, Some(EndReason::Disconnect)));
+        assert!(restored.ended_at.is_some());
+        let untouched = store.session("carol-dave").await.unwrap().unwrap();
+        assert!(matches!(untouched.end_reason, Some(EndReason::Bye)));
+        assert_eq!(store.end_open_sessions(Utc::now()).unwrap(), 0);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn updates_apply_to_known_sessions_only() {
+        let path = temp_path();
+        let store = SqliteStore::open(&path).unwrap();
+        store.put_session(&session("alice-bob")).await.unwrap();
+
+        let updated = store
+            .update_session("alice-bob", Box::new(|session| session.turn_used = true))
+            .await
+            .unwrap();
+        assert!(updated.unwrap().turn_used);
+        assert!(store.session("alice-bob").await.unwrap().unwrap().turn_used);
+        let missing = store.update_session("carol-dave", Box::new(|session| session.turn_used = true)).await;
+        assert!(missing.unwrap().is_none());
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn credentials_are_removed_by_turn_username_or_user() {
+        let path = temp_path();
+        let store = SqliteStore::open(&path).unwrap();
+        let expires_at = Utc::now() + chrono::Duration::hours(1);
+        for turn_username in ["1:alice", "2:alice", "3:malice", "4:a%ice", "5:bob"] {
+            store.put_credential(&credential(turn_username, expires_at)).await.unwrap();
+        }
+
+        let mut removed = store.remove_credentials("alice").await.unwrap();
+        removed.sort();
+        assert_eq!(removed, ["1:alice", "2:alice"]);
+        // `_` is no LIKE wildcard, so it does not match the `%`
+        assert!(store.remove_credentials("a_ice").await.unwrap().is_empty());
+        assert_eq!(store.remove_credentials("5:bob").await.unwrap(), ["5:bob"]);
+        assert!(store.credential("3:malice").await.unwrap().is_some());
+        assert!(store.credential("4:a%ice").await.unwrap().is_some());
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn purge_drops_expired_credentials_and_old_sessions() {
+        let path = temp_path();
+        let store = SqliteStore::open(&path).unwrap();
+        let now = Utc::now();
+        store.put_credential(&credential("1:alice", now - chrono::Duration::seconds(1))).await.unwrap();
+        store.put_credential(&credential("2:alice", now + chrono::Duration::hours(1))).await.unwrap();
+        store.put_session(&session("alice-bob")).await.unwrap();
+        for (session_id, ended_minutes_ago) in [("carol-dave", 120), ("erin-frank", 5)] {
+            let mut ended = session(session_id);
+            ended.status = CallStatus::Ended;
+            ended.ended_at = Some(now - chrono::Duration::minutes(ended_minutes_ago));
+            store.put_session(&ended).await.unwrap();
+        }
+
+        let purged = store.purge(now, now - chrono::Duration::hours(1)).await.unwrap();
+        assert_eq!(purged, 2);
+        assert!(store.credential("1:alice").await.unwrap().is_none());
+        assert!(store.credential("2:alice").await.unwrap().is_some());
+        let mut remaining: Vec<String> = store.sessions().await.unwrap().into_iter().map(|s| s.session_id).collect();
+        remaining.sort();
+        assert_eq!(remaining, ["alice-bob", "erin-frank"]);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -1,36 +1,136 @@
 use anyhow::Result;
-use log::{error, info, warn};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use log::{debug, error, info, warn};
+use serde::Serialize;
+use sha2::Sha256;
 use std::collections::HashMap;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use stun::agent::TransactionId;
+use stun::attributes::{
+    AttrType, ATTR_CHANNEL_NUMBER, ATTR_DATA, ATTR_LIFETIME, ATTR_MESSAGE_INTEGRITY, ATTR_NONCE, ATTR_REALM,
+    ATTR_REQUESTED_TRANSPORT, ATTR_USERNAME, ATTR_XORMAPPED_ADDRESS, ATTR_XOR_PEER_ADDRESS, ATTR_XOR_RELAYED_ADDRESS,
+};
+use stun::error_code::{
+    ErrorCode, ErrorCodeAttribute, CODE_ALLOC_MISMATCH, CODE_BAD_REQUEST, CODE_INSUFFICIENT_CAPACITY,
+    CODE_STALE_NONCE, CODE_UNAUTHORIZED, CODE_UNSUPPORTED_TRANS_PROTO, CODE_WRONG_CREDENTIALS,
+};
+use stun::integrity::MessageIntegrity;
+use stun::message::{
+    is_message, Message, MessageClass, MessageType, Setter, CLASS_ERROR_RESPONSE, CLASS_INDICATION, CLASS_REQUEST,
+    CLASS_SUCCESS_RESPONSE, MAGIC_COOKIE, METHOD_ALLOCATE, METHOD_BINDING, METHOD_CHANNEL_BIND,
+    METHOD_CREATE_PERMISSION, METHOD_DATA, METHOD_REFRESH, METHOD_SEND,
+};
+use stun::textattrs::TextAttribute;
+use stun::xoraddr::XorMappedAddress;
 use tokio::net::UdpSocket;
 use tokio::sync::Mutex;
 
+use crate::modules::util::constant_time_eq;
+use crate::modules::metrics::Metrics;
+use crate::modules::signaling::ShutdownPhase;
+
+/// Allocations of the running relay, keyed by client address.
+pub type AllocationMap = Arc<Mutex<HashMap<SocketAddr, Allocation>>>;
+
+const STUN_MAGIC_COOKIE: u32 = 0x2112A442;
+const STUN_BINDING_REQUEST: u16 = 0x0001;
+const STUN_BINDING_SUCCESS: u16 = 0x0101;
+
+// Lifetimes from RFC 5766
+const DEFAULT_LIFETIME: Duration = Duration::from_secs(600);
+const MAX_LIFETIME: Duration = Duration::from_secs(3600);
+const PERMISSION_LIFETIME: Duration = Duration::from_secs(300);
+const CHANNEL_LIFETIME: Duration = Duration::from_secs(600);
+const NONCE_LIFETIME: Duration = Duration::from_secs(3600);
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+/// Consecutive receive errors after which a relay socket is abandoned
+const RELAY_RECV_FAILURES: u32 = 10;
+const CHANNEL_NUMBERS: RangeInclusive<u16> = 0x4000..=0x7FFE;
+const PROTOCOL_UDP: u8 = 17;
+
+/// Listener state of the TURN server as seen after `start`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TurnStatus {
+    /// Public IP not configured, TURN intentionally not started
+    Disabled,
+    /// Startup failed or has not happened yet
+    NotRunning,
+    Listening(SocketAddr),
+}
+
 pub struct TurnServer {
-    config: crate::modules::config::TurnConfig,
+    config: crate::modules::config::ConfigHandle,
     signaler: Arc<crate::modules::signaling::Signaler>,
     server_handle: Option<tokio::task::JoinHandle<()>>,
+    allocations: AllocationMap,
+    listen_addr: Option<SocketAddr>,
+}
+
+/// Builds a `TurnServer` sharing the signaler's live config unless another is given.
+pub struct TurnServerBuilder {
+    signaler: Arc<crate::modules::signaling::Signaler>,
+    config: Option<crate::modules::config::ConfigHandle>,
+}
+
+impl TurnServerBuilder {
+    pub fn config_handle(mut self, config: crate::modules::config::ConfigHandle) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    pub fn build(self) -> TurnServer {
+        let config = self.config.unwrap_or_else(|| self.signaler.config.clone());
+        TurnServer::new(config, self.signaler)
+    }
 }
 
 impl TurnServer {
+    pub fn builder(signaler: Arc<crate::modules::signaling::Signaler>) -> TurnServerBuilder {
+        TurnServerBuilder { signaler, config: None }
+    }
+
     pub fn new(
-        config: crate::modules::config::TurnConfig,
+        config: crate::modules::config::ConfigHandle,
         signaler: Arc<crate::modules::signaling::Signaler>,
     ) -> Self {
         Self {
             config,
             signaler,
             server_handle: None,
+            allocations: Arc::new(Mutex::new(HashMap::new())),
+            listen_addr: None,
         }
     }
 
+    fn is_enabled(&self) -> bool {
+        self.config.load().turn.is_enabled()
+    }
+
+    pub fn status(&self) -> TurnStatus {
+        match self.listen_addr {
+            Some(addr) => TurnStatus::Listening(addr),
+            None if self.is_enabled() => TurnStatus::NotRunning,
+            None => TurnStatus::Disabled,
+        }
+    }
+
+    /// Shared handle to the relay's allocations, valid before and after `start`.
+    /// Removing an allocation from the map stops relaying for it.
+    pub fn allocations(&self) -> AllocationMap {
+        self.allocations.clone()
+    }
+
     pub async fn start(&mut self) -> Result<()> {
-        if self.config.public_ip.contains("YOUR PUBLIC IP") {
+        if !self.is_enabled() {
             warn!("TURN server public IP not configured, skipping TURN server startup");
             return Ok(());
         }
 
-        let bind_addr: SocketAddr = format!("0.0.0.0:{}", self.config.port).parse()?;
+        let bind_addr: SocketAddr = format!("0.0.0.0:{}", self.config.load().turn.port).parse()?;
         
         info!("Starting TURN server on {}", bind_addr);
 
@@ -38,8 +138,12 @@ impl TurnServer {
         let socket = Arc::new(UdpSocket::bind(bind_addr).await?);
         info!("TURN server UDP socket bound to {}", bind_addr);
 
-        // Create simple TURN relay server
-        let turn_relay = SimpleTurnRelay::new(socket, self.signaler.clone(), self.config.clone());
+        let turn_relay = TurnRelay::new(
+            socket,
+            self.signaler.clone(),
+            self.config.clone(),
+            self.allocations.clone(),
+        );
         
         // Start server in background task
         let handle = tokio::spawn(async move {
@@ -50,180 +154,1002 @@ impl TurnServer {
         });
 
         self.server_handle = Some(handle);
+        self.listen_addr = Some(bind_addr);
         info!("TURN server successfully started on {}", bind_addr);
         
         Ok(())
     }
 
+    /// Lets existing allocations finish until `deadline`, then stops the relay.
+    pub async fn drain(&mut self, deadline: tokio::time::Instant) -> Result<()> {
+        loop {
+            let remaining = self.allocations.lock().await.len();
+            if remaining == 0 {
+                break;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                warn!("TURN drain deadline reached with {} allocations left", remaining);
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+        }
+
+        self.close().await
+    }
+
     pub async fn close(&mut self) -> Result<()> {
         if let Some(handle) = self.server_handle.take() {
             handle.abort();
+            let mut allocations = self.allocations.lock().await;
+            allocations.clear();
+            record_gauges(&self.signaler.metrics, &allocations);
+            self.listen_addr = None;
             info!("TURN server stopped");
         }
         Ok(())
     }
 }
 
-struct SimpleTurnRelay {
+/// Sends a STUN binding request to the relay listening on `addr` and waits for
+/// the matching success response. Returns the round trip time.
+pub async fn probe(addr: SocketAddr, timeout: std::time::Duration) -> Result<std::time::Duration> {
+    // The listener is bound to the wildcard address, probe it over loopback
+    let target = match addr.ip() {
+        ip if ip.is_unspecified() && addr.is_ipv4() => SocketAddr::from(([127, 0, 0, 1], addr.port())),
+        ip if ip.is_unspecified() => SocketAddr::from((std::net::Ipv6Addr::LOCALHOST, addr.port())),
+        _ => addr,
+    };
+    let bind_addr: SocketAddr = if target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }.parse()?;
+    let socket = UdpSocket::bind(bind_addr).await?;
+
+    let transaction_id = *uuid::Uuid::new_v4().as_bytes();
+    let mut request = Vec::with_capacity(20);
+    request.extend_from_slice(&STUN_BINDING_REQUEST.to_be_bytes());
+    request.extend_from_slice(&0u16.to_be_bytes());
+    request.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+    request.extend_from_slice(&transaction_id[..12]);
+
+    let started = std::time::Instant::now();
+    socket.send_to(&request, target).await?;
+
+    let mut buffer = [0u8; 512];
+    let exchange = async {
+        loop {
+            let (len, from) = socket.recv_from(&mut buffer).await?;
+            let response = &buffer[..len];
+            if from.port() == target.port()
+                && len >= 20
+                && u16::from_be_bytes([response[0], response[1]]) == STUN_BINDING_SUCCESS
+                && response[8..20] == transaction_id[..12]
+            {
+                return Ok::<_, std::io::Error>(());
+            }
+        }
+    };
+
+    tokio::time::timeout(timeout, exchange)
+        .await
+        .map_err(|_| anyhow::anyhow!("no STUN response within {}ms", timeout.as_millis()))??;
+    Ok(started.elapsed())
+}
+
+/// An allocation as listed by the admin API.
+#[derive(Debug, Clone, Serialize)]
+pub struct TurnAllocation {
+    pub client_addr: SocketAddr,
+    pub relay_addr: SocketAddr,
+    pub username: String,
+    pub expires_at: DateTime<Utc>,
+    /// Peer addresses the client has permission to exchange data with
+    pub permissions: Vec<IpAddr>,
+    pub channels: usize,
+}
+
+/// A relayed address held for one client. Dropping it, e.g. when its credential
+/// is revoked, stops the relay.
+pub struct Allocation {
+    username: String,
+    relay_addr: SocketAddr,
+    relay: Arc<UdpSocket>,
+    /// Of the Allocate request, to answer its retransmissions
+    transaction_id: TransactionId,
+    expires: Instant,
+    permissions: HashMap<IpAddr, Instant>,
+    channels: HashMap<u16, Channel>,
+    relay_task: tokio::task::JoinHandle<()>,
+}
+
+struct Channel {
+    peer: SocketAddr,
+    expires: Instant,
+}
+
+impl Allocation {
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn summary(&self, client_addr: SocketAddr) -> TurnAllocation {
+        let remaining = self.expires.saturating_duration_since(Instant::now());
+        TurnAllocation {
+            client_addr,
+            relay_addr: self.relay_addr,
+            username: self.username.clone(),
+            expires_at: Utc::now() + chrono::Duration::from_std(remaining).unwrap_or_default(),
+            permissions: self.permissions.keys().copied().collect(),
+            channels: self.channels.len(),
+        }
+    }
+
+    fn permits(&self, peer: IpAddr) -> bool {
+        self.permissions.get(&peer).is_some_and(|expires| *expires > Instant::now())
+    }
+
+    fn channel_to(&self, peer: SocketAddr) -> Option<u16> {
+        self.channels
+            .iter()
+            .find(|(_, channel)| channel.peer == peer)
+            .map(|(number, _)| *number)
+    }
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        self.relay_task.abort();
+    }
+}
+
+/// Sets the allocation, permission and channel gauges from `allocations`.
+pub(crate) fn record_gauges(metrics: &Metrics, allocations: &HashMap<SocketAddr, Allocation>) {
+    let permissions: usize = allocations.values().map(|allocation| allocation.permissions.len()).sum();
+    let channels: usize = allocations.values().map(|allocation| allocation.channels.len()).sum();
+    metrics.turn_allocations.set(allocations.len() as i64);
+    metrics.turn_permissions.set(permissions as i64);
+    metrics.turn_channels.set(channels as i64);
+}
+
+/// UDP TURN relay (RFC 5766): allocations, permissions, channels and Send/Data
+/// indications, authenticated with the long-term mechanism against credentials
+/// issued by `/api/turn`.
+struct TurnRelay {
     socket: Arc<UdpSocket>,
     signaler: Arc<crate::modules::signaling::Signaler>,
-    config: crate::modules::config::TurnConfig,
-    allocations: Arc<Mutex<HashMap<SocketAddr, TurnAllocation>>>,
+    config: crate::modules::config::ConfigHandle,
+    allocations: AllocationMap,
+    /// Signs nonces so they need no server state
+    nonce_key: Vec<u8>,
 }
 
-#[derive(Clone)]
-struct TurnAllocation {
-    client_addr: SocketAddr,
-    relay_addr: SocketAddr,
+/// A credential that authenticated a request.
+struct Credential {
     username: String,
+    integrity: MessageIntegrity,
 }
 
-impl SimpleTurnRelay {
+impl TurnRelay {
     fn new(
         socket: Arc<UdpSocket>,
         signaler: Arc<crate::modules::signaling::Signaler>,
-        config: crate::modules::config::TurnConfig,
-    ) -> Self {
-        Self {
+        config: crate::modules::config::ConfigHandle,
+        allocations: AllocationMap,
+    ) -> Arc<Self> {
+        let nonce_key = [uuid::Uuid::new_v4().into_bytes(), uuid::Uuid::new_v4().into_bytes()].concat();
+        Arc::new(Self {
             socket,
             signaler,
             config,
-            allocations: Arc::new(Mutex::new(HashMap::new())),
+            allocations,
+            nonce_key,
+        })
+    }
+
+    async fn run(self: Arc<Self>) -> Result<()> {
+        let mut buffer = vec![0u8; 65536];
+        let mut sweep = tokio::time::interval(SWEEP_INTERVAL);
+
+        loop {
+            tokio::select! {
+                received = self.socket.recv_from(&mut buffer) => match received {
+                    Ok((len, addr)) => self.handle_packet(&buffer[..len], addr).await,
+                    Err(e) => error!("Error receiving UDP packet: {}", e),
+                },
+                _ = sweep.tick() => self.sweep().await,
+            }
         }
     }
 
-    async fn run(self) -> Result<()> {
-        let mut buffer = [0u8; 65536];
-        
+    async fn handle_packet(self: &Arc<Self>, data: &[u8], addr: SocketAddr) {
+        if data.first().is_some_and(|byte| (0x40..=0x7F).contains(byte)) {
+            self.handle_channel_data(data, addr).await;
+        } else if is_message(data) {
+            if let Err(e) = self.handle_stun_message(data, addr).await {
+                error!("Error handling STUN message from {}: {}", addr, e);
+            }
+        } else {
+            debug!("Dropping {} bytes from {}, neither STUN nor ChannelData", data.len(), addr);
+        }
+    }
+
+    async fn handle_stun_message(self: &Arc<Self>, data: &[u8], addr: SocketAddr) -> Result<()> {
+        self.signaler.metrics.turn_stun_requests.inc();
+        let mut request = Message::new();
+        request.raw = data.to_vec();
+        request.decode()?;
+        debug!("STUN {} from {}", request.typ, addr);
+
+        let response = match (request.typ.method, request.typ.class) {
+            (METHOD_BINDING, CLASS_REQUEST) => respond(
+                &request,
+                CLASS_SUCCESS_RESPONSE,
+                vec![Box::new(XorAddress(ATTR_XORMAPPED_ADDRESS, addr))],
+                None,
+            )?,
+            (METHOD_ALLOCATE, CLASS_REQUEST) => self.allocate(&mut request, addr).await?,
+            (METHOD_REFRESH, CLASS_REQUEST) => self.refresh(&mut request, addr).await?,
+            (METHOD_CREATE_PERMISSION, CLASS_REQUEST) => self.create_permission(&mut request, addr).await?,
+            (METHOD_CHANNEL_BIND, CLASS_REQUEST) => self.channel_bind(&mut request, addr).await?,
+            (METHOD_SEND, CLASS_INDICATION) => {
+                self.send_indication(&request, addr).await;
+                return Ok(());
+            }
+            _ => {
+                debug!("Ignoring unsupported STUN {} from {}", request.typ, addr);
+                return Ok(());
+            }
+        };
+        self.socket.send_to(&response.raw, addr).await?;
+        Ok(())
+    }
+
+    async fn allocate(self: &Arc<Self>, request: &mut Message, client: SocketAddr) -> Result<Message> {
+        let credential = match self.authenticate(request, client).await? {
+            Ok(credential) => credential,
+            Err(challenge) => return Ok(challenge),
+        };
+        let integrity = Some(&credential.integrity);
+
+        let mut allocations = self.allocations.lock().await;
+        if let Some(existing) = allocations.get(&client) {
+            if existing.transaction_id != request.transaction_id {
+                return error_response(request, CODE_ALLOC_MISMATCH, "Allocation Mismatch", integrity);
+            }
+            let remaining = existing.expires.saturating_duration_since(Instant::now());
+            return allocated(request, client, existing.relay_addr, remaining, integrity);
+        }
+        if !matches!(self.signaler.shutdown_phase(), ShutdownPhase::Running) {
+            return error_response(request, CODE_INSUFFICIENT_CAPACITY, "Server shutting down", integrity);
+        }
+        match request.get(ATTR_REQUESTED_TRANSPORT) {
+            Ok(transport) if transport.first() == Some(&PROTOCOL_UDP) => {}
+            Ok(_) => return error_response(request, CODE_UNSUPPORTED_TRANS_PROTO, "Only UDP is relayed", integrity),
+            Err(_) => return error_response(request, CODE_BAD_REQUEST, "Missing REQUESTED-TRANSPORT", integrity),
+        }
+
+        let (relay, relay_addr) = match self.bind_relay().await {
+            Ok(bound) => bound,
+            Err(e) => {
+                error!("Failed to bind TURN relay address for {}: {}", client, e);
+                return error_response(request, CODE_INSUFFICIENT_CAPACITY, "No relay address available", integrity);
+            }
+        };
+        let lifetime = requested_lifetime(request).filter(|lifetime| !lifetime.is_zero()).unwrap_or(DEFAULT_LIFETIME);
+        let relay_task = tokio::spawn(self.clone().relay_from_peers(client, relay.clone()));
+        allocations.insert(
+            client,
+            Allocation {
+                username: credential.username.clone(),
+                relay_addr,
+                relay,
+                transaction_id: request.transaction_id,
+                expires: Instant::now() + lifetime,
+                permissions: HashMap::new(),
+                channels: HashMap::new(),
+                relay_task,
+            },
+        );
+        record_gauges(&self.signaler.metrics, &allocations);
+        info!("🔁 TURN allocation {} for {} (credential {})", relay_addr, client, credential.username);
+
+        allocated(request, client, relay_addr, lifetime, integrity)
+    }
+
+    async fn refresh(&self, request: &mut Message, client: SocketAddr) -> Result<Message> {
+        let credential = match self.authenticate(request, client).await? {
+            Ok(credential) => credential,
+            Err(challenge) => return Ok(challenge),
+        };
+        let integrity = Some(&credential.integrity);
+
+        let mut allocations = self.allocations.lock().await;
+        let Some(allocation) = allocations.get_mut(&client) else {
+            return error_response(request, CODE_ALLOC_MISMATCH, "No allocation", integrity);
+        };
+        if allocation.username != credential.username {
+            return error_response(request, CODE_WRONG_CREDENTIALS, "Wrong Credentials", integrity);
+        }
+
+        let lifetime = requested_lifetime(request).unwrap_or(DEFAULT_LIFETIME);
+        if lifetime.is_zero() {
+            allocations.remove(&client);
+            record_gauges(&self.signaler.metrics, &allocations);
+            info!("🔁 TURN allocation for {} released", client);
+        } else {
+            allocation.expires = Instant::now() + lifetime;
+        }
+        respond(request, CLASS_SUCCESS_RESPONSE, vec![Box::new(Lifetime(lifetime))], integrity)
+    }
+
+    async fn create_permission(&self, request: &mut Message, client: SocketAddr) -> Result<Message> {
+        let credential = match self.authenticate(request, client).await? {
+            Ok(credential) => credential,
+            Err(challenge) => return Ok(challenge),
+        };
+        let integrity = Some(&credential.integrity);
+
+        let mut allocations = self.allocations.lock().await;
+        let Some(allocation) = allocations.get_mut(&client) else {
+            return error_response(request, CODE_ALLOC_MISMATCH, "No allocation", integrity);
+        };
+        if allocation.username != credential.username {
+            return error_response(request, CODE_WRONG_CREDENTIALS, "Wrong Credentials", integrity);
+        }
+        let peers = xor_peer_addresses(request);
+        if peers.is_empty() {
+            return error_response(request, CODE_BAD_REQUEST, "Missing XOR-PEER-ADDRESS", integrity);
+        }
+
+        let expires = Instant::now() + PERMISSION_LIFETIME;
+        for peer in peers {
+            debug!("TURN permission for {} to reach {}", client, peer.ip());
+            allocation.permissions.insert(peer.ip(), expires);
+        }
+        record_gauges(&self.signaler.metrics, &allocations);
+        respond(request, CLASS_SUCCESS_RESPONSE, Vec::new(), integrity)
+    }
+
+    async fn channel_bind(&self, request: &mut Message, client: SocketAddr) -> Result<Message> {
+        let credential = match self.authenticate(request, client).await? {
+            Ok(credential) => credential,
+            Err(challenge) => return Ok(challenge),
+        };
+        let integrity = Some(&credential.integrity);
+
+        let mut allocations = self.allocations.lock().await;
+        let Some(allocation) = allocations.get_mut(&client) else {
+            return error_response(request, CODE_ALLOC_MISMATCH, "No allocation", integrity);
+        };
+        if allocation.username != credential.username {
+            return error_response(request, CODE_WRONG_CREDENTIALS, "Wrong Credentials", integrity);
+        }
+        let number = request
+            .get(ATTR_CHANNEL_NUMBER)
+            .ok()
+            .filter(|value| value.len() >= 2)
+            .map(|value| u16::from_be_bytes([value[0], value[1]]))
+            .filter(|number| CHANNEL_NUMBERS.contains(number));
+        let (Some(number), Some(&peer)) = (number, xor_peer_addresses(request).first()) else {
+            return error_response(request, CODE_BAD_REQUEST, "Invalid channel number or peer", integrity);
+        };
+        // A channel stays bound to one peer, and a peer to one channel
+        let bound_elsewhere = allocation.channels.get(&number).is_some_and(|channel| channel.peer != peer)
+            || allocation.channel_to(peer).is_some_and(|bound| bound != number);
+        if bound_elsewhere {
+            return error_response(request, CODE_BAD_REQUEST, "Channel or peer already bound", integrity);
+        }
+
+        let now = Instant::now();
+        allocation.channels.insert(number, Channel { peer, expires: now + CHANNEL_LIFETIME });
+        allocation.permissions.insert(peer.ip(), now + PERMISSION_LIFETIME);
+        record_gauges(&self.signaler.metrics, &allocations);
+        debug!("TURN channel {:#06x} of {} bound to {}", number, client, peer);
+        respond(request, CLASS_SUCCESS_RESPONSE, Vec::new(), integrity)
+    }
+
+    // Client to peer, wrapped in a Send indication
+    async fn send_indication(&self, request: &Message, client: SocketAddr) {
+        let (Some(&peer), Ok(data)) = (xor_peer_addresses(request).first(), request.get(ATTR_DATA)) else {
+            debug!("Dropping Send indication from {} without peer or data", client);
+            return;
+        };
+        self.relay_to_peer(client, peer, &data).await;
+    }
+
+    // Client to peer, over a bound channel
+    async fn handle_channel_data(&self, data: &[u8], client: SocketAddr) {
+        if data.len() < 4 {
+            return;
+        }
+        let number = u16::from_be_bytes([data[0], data[1]]);
+        let length = u16::from_be_bytes([data[2], data[3]]) as usize;
+        let Some(payload) = data.get(4..4 + length) else {
+            debug!("Dropping truncated ChannelData from {}", client);
+            return;
+        };
+        let peer = {
+            let allocations = self.allocations.lock().await;
+            allocations
+                .get(&client)
+                .and_then(|allocation| allocation.channels.get(&number))
+                .map(|channel| channel.peer)
+        };
+        match peer {
+            Some(peer) => self.relay_to_peer(client, peer, payload).await,
+            None => debug!("Dropping ChannelData from {} on unbound channel {:#06x}", client, number),
+        }
+    }
+
+    async fn relay_to_peer(&self, client: SocketAddr, peer: SocketAddr, data: &[u8]) {
+        let relay = {
+            let allocations = self.allocations.lock().await;
+            match allocations.get(&client) {
+                Some(allocation) if allocation.permits(peer.ip()) => allocation.relay.clone(),
+                Some(_) => {
+                    debug!("Dropping {} bytes from {} to {} without permission", data.len(), client, peer);
+                    return;
+                }
+                None => {
+                    debug!("Dropping {} bytes from {} without allocation", data.len(), client);
+                    return;
+                }
+            }
+        };
+        match relay.send_to(data, peer).await {
+            Ok(sent) => self.count_relayed(sent),
+            Err(e) => debug!("Failed to relay {} bytes from {} to {}: {}", data.len(), client, peer, e),
+        }
+    }
+
+    // Peer to client, over the channel bound to the peer or in a Data indication.
+    // Ends when the allocation is dropped, which aborts it.
+    async fn relay_from_peers(self: Arc<Self>, client: SocketAddr, relay: Arc<UdpSocket>) {
+        let mut buffer = vec![0u8; 65536];
+        let mut failures = 0u32;
         loop {
-            match self.socket.recv_from(&mut buffer).await {
-                Ok((len, addr)) => {
-                    let data = &buffer[..len];
-                    
-                    info!("Received {} bytes from {}", len, addr);
-                    
-                    // Check if this is a STUN/TURN message
-                    if len >= 20 && self.is_stun_message(data) {
-                        info!("Processing STUN/TURN message from {}", addr);
-                        if let Err(e) = self.handle_stun_message(data, addr).await {
-                            error!("Error handling STUN message from {}: {}", addr, e);
-                        }
-                    } else {
-                        info!("Processing data relay from {}", addr);
-                        // Handle data relay
-                        if let Err(e) = self.handle_data_relay(data, addr).await {
-                            error!("Error handling data relay from {}: {}", addr, e);
-                        }
-                    }
+            let (len, peer) = match relay.recv_from(&mut buffer).await {
+                Ok(received) => {
+                    failures = 0;
+                    received
                 }
                 Err(e) => {
-                    error!("Error receiving UDP packet: {}", e);
+                    failures += 1;
+                    if failures >= RELAY_RECV_FAILURES {
+                        warn!("TURN relay socket of {} keeps failing, giving up: {}", client, e);
+                        break;
+                    }
+                    debug!("TURN relay socket of {} failed to receive: {}", client, e);
+                    // Back off so a persistently broken socket does not spin
+                    tokio::time::sleep(Duration::from_millis(10 << failures.min(6))).await;
+                    if !self.allocations.lock().await.contains_key(&client) {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            let channel = {
+                let allocations = self.allocations.lock().await;
+                let Some(allocation) = allocations.get(&client) else {
                     break;
+                };
+                if !allocation.permits(peer.ip()) {
+                    debug!("Dropping {} bytes from {} to {} without permission", len, peer, client);
+                    continue;
                 }
+                allocation.channel_to(peer)
+            };
+
+            let data = &buffer[..len];
+            let packet = match channel {
+                Some(number) => channel_data(number, data),
+                None => match data_indication(peer, data) {
+                    Ok(indication) => indication.raw,
+                    Err(e) => {
+                        debug!("Failed to build Data indication for {}: {}", client, e);
+                        continue;
+                    }
+                },
+            };
+            match self.socket.send_to(&packet, client).await {
+                Ok(_) => self.count_relayed(len),
+                Err(e) => debug!("Failed to relay {} bytes from {} to {}: {}", len, peer, client, e),
             }
         }
-        
-        Ok(())
     }
 
-    fn is_stun_message(&self, data: &[u8]) -> bool {
-        if data.len() < 20 {
-            return false;
+    fn count_relayed(&self, bytes: usize) {
+        self.signaler.metrics.turn_relayed_packets.inc();
+        self.signaler.metrics.turn_relayed_bytes.inc_by(bytes as u64);
+    }
+
+    // Drops expired allocations, permissions and channels
+    async fn sweep(&self) {
+        let now = Instant::now();
+        let mut allocations = self.allocations.lock().await;
+        allocations.retain(|client, allocation| {
+            let alive = allocation.expires > now;
+            if !alive {
+                info!("🔁 TURN allocation for {} expired", client);
+            }
+            alive
+        });
+        for allocation in allocations.values_mut() {
+            allocation.permissions.retain(|_, expires| *expires > now);
+            allocation.channels.retain(|_, channel| channel.expires > now);
         }
-        
-        // STUN message type is in first 2 bytes
-        // STUN messages start with 0x00 or 0x01 in first byte
-        let msg_type = u16::from_be_bytes([data[0], data[1]]);
-        
-        // Check for common STUN/TURN message types
-        matches!(msg_type & 0xFF00, 0x0000 | 0x0100)
+        record_gauges(&self.signaler.metrics, &allocations);
     }
 
-    async fn handle_stun_message(&self, data: &[u8], addr: SocketAddr) -> Result<()> {
-        let msg_type = if data.len() >= 2 {
-            u16::from_be_bytes([data[0], data[1]])
-        } else {
-            0
+    async fn bind_relay(&self) -> Result<(Arc<UdpSocket>, SocketAddr)> {
+        let public_ip = self.config.load().turn.public_ip.parse::<IpAddr>().ok();
+        let bind_addr: SocketAddr = match public_ip {
+            Some(IpAddr::V6(_)) => "[::]:0",
+            _ => "0.0.0.0:0",
+        }
+        .parse()?;
+        let socket = UdpSocket::bind(bind_addr).await?;
+        let local = socket.local_addr()?;
+        let relay_addr = SocketAddr::new(public_ip.unwrap_or(local.ip()), local.port());
+        Ok((Arc::new(socket), relay_addr))
+    }
+
+    // Checks the long-term credential of a request against the issued, unexpired
+    // and unrevoked credentials. Returns the challenge to send back when the
+    // request has no credential or a stale nonce, or fails the check.
+    async fn authenticate(&self, request: &mut Message, client: SocketAddr) -> Result<std::result::Result<Credential, Message>> {
+        let realm = self.config.load().turn.realm.clone();
+        if !request.contains(ATTR_MESSAGE_INTEGRITY) {
+            return Ok(Err(self.challenge(request, client, CODE_UNAUTHORIZED, "Unauthorized", &realm)?));
+        }
+        let nonce = TextAttribute::get_from_as(request, ATTR_NONCE).map(|nonce| nonce.text).unwrap_or_default();
+        if !self.nonce_is_valid(&nonce, client) {
+            return Ok(Err(self.challenge(request, client, CODE_STALE_NONCE, "Stale Nonce", &realm)?));
+        }
+
+        let username = TextAttribute::get_from_as(request, ATTR_USERNAME).map(|username| username.text).unwrap_or_default();
+        let Some(password) = self.signaler.validate_turn_credentials(&username).await else {
+            warn!("TURN request from {} with unknown, expired or revoked credential {}", client, username);
+            self.signaler.metrics.turn_auth_failures.inc();
+            return Ok(Err(self.challenge(request, client, CODE_UNAUTHORIZED, "Unauthorized", &realm)?));
         };
-        
-        info!("Processing STUN/TURN message from {} ({} bytes, type: 0x{:04x})", 
-              addr, data.len(), msg_type);
-        
-        // For now, we'll implement basic STUN binding response
-        // This is a simplified implementation - a full TURN server would need
-        // proper STUN message parsing and TURN protocol implementation
-        
-        let response = self.create_binding_response(addr)?;
-        
-        match self.socket.send_to(&response, addr).await {
-            Ok(bytes_sent) => {
-                info!("Successfully sent STUN binding response to {} ({} bytes)", addr, bytes_sent);
-            },
-            Err(e) => {
-                error!("Failed to send STUN response to {}: {}", addr, e);
-                return Err(e.into());
-            }
+        let integrity = MessageIntegrity::new_long_term_integrity(username.clone(), realm.clone(), password);
+        if integrity.check(request).is_err() {
+            warn!("TURN request from {} failed integrity check for credential {}", client, username);
+            self.signaler.metrics.turn_auth_failures.inc();
+            return Ok(Err(self.challenge(request, client, CODE_UNAUTHORIZED, "Unauthorized", &realm)?));
         }
-        
+        Ok(Ok(Credential { username, integrity }))
+    }
+
+    fn challenge(&self, request: &Message, client: SocketAddr, code: ErrorCode, reason: &str, realm: &str) -> Result<Message> {
+        respond(
+            request,
+            CLASS_ERROR_RESPONSE,
+            vec![
+                Box::new(ErrorCodeAttribute { code, reason: reason.as_bytes().to_vec() }),
+                Box::new(TextAttribute::new(ATTR_REALM, realm.to_string())),
+                Box::new(TextAttribute::new(ATTR_NONCE, self.nonce(client))),
+            ],
+            None,
+        )
+    }
+
+    // `<issued>-<mac>`, bound to the client address and valid for NONCE_LIFETIME
+    fn nonce(&self, client: SocketAddr) -> String {
+        let issued = Utc::now().timestamp().to_string();
+        let mac = self.nonce_mac(&issued, client);
+        format!("{}-{}", issued, mac)
+    }
+
+    fn nonce_is_valid(&self, nonce: &str, client: SocketAddr) -> bool {
+        let Some((issued, mac)) = nonce.split_once('-') else {
+            return false;
+        };
+        let fresh = issued
+            .parse::<i64>()
+            .is_ok_and(|issued| Utc::now().timestamp() - issued < NONCE_LIFETIME.as_secs() as i64);
+        fresh && constant_time_eq(mac.as_bytes(), self.nonce_mac(issued, client).as_bytes())
+    }
+
+    fn nonce_mac(&self, issued: &str, client: SocketAddr) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.nonce_key).expect("HMAC accepts keys of any length");
+        mac.update(issued.as_bytes());
+        mac.update(client.to_string().as_bytes());
+        hex::encode(&mac.finalize().into_bytes()[..16])
+    }
+}
+
+/// An address attribute XORed with the magic cookie and transaction id.
+struct XorAddress(AttrType, SocketAddr);
+
+impl Setter for XorAddress {
+    fn add_to(&self, m: &mut Message) -> Result<(), stun::Error> {
+        XorMappedAddress { ip: self.1.ip(), port: self.1.port() }.add_to_as(m, self.0)
+    }
+}
+
+struct Lifetime(Duration);
+
+impl Setter for Lifetime {
+    fn add_to(&self, m: &mut Message) -> Result<(), stun::Error> {
+        m.add(ATTR_LIFETIME, &(self.0.as_secs() as u32).to_be_bytes());
         Ok(())
     }
+}
 
-    async fn handle_data_relay(&self, data: &[u8], addr: SocketAddr) -> Result<()> {
-        // Simple data relay logic
-        let allocations = self.allocations.lock().await;
-        
-        if let Some(allocation) = allocations.get(&addr) {
-            info!("Relaying {} bytes from {} to {} (user: {})", 
-                  data.len(), addr, allocation.relay_addr, allocation.username);
-            // In a real implementation, we would relay to the target
-        } else {
-            warn!("No allocation found for data relay from {}, {} bytes dropped", addr, data.len());
-        }
-        
+struct Data(Vec<u8>);
+
+impl Setter for Data {
+    fn add_to(&self, m: &mut Message) -> Result<(), stun::Error> {
+        m.add(ATTR_DATA, &self.0);
         Ok(())
     }
+}
 
-    fn create_binding_response(&self, client_addr: SocketAddr) -> Result<Vec<u8>> {
-        // Create a basic STUN Binding Success Response
-        // This is a simplified implementation
-        let mut response = Vec::new();
-        
-        // STUN header: Message Type (Binding Success Response = 0x0101)
-        response.extend_from_slice(&0x0101u16.to_be_bytes());
-        
-        // Message Length (will be updated)
-        let length_pos = response.len();
-        response.extend_from_slice(&0u16.to_be_bytes());
-        
-        // Magic Cookie
-        response.extend_from_slice(&0x2112A442u32.to_be_bytes());
-        
-        // Transaction ID (12 bytes) - simplified random
-        response.extend_from_slice(&[0u8; 12]);
-        
-        // XOR-MAPPED-ADDRESS attribute
-        response.extend_from_slice(&0x0020u16.to_be_bytes()); // Attribute type
-        response.extend_from_slice(&0x0008u16.to_be_bytes()); // Attribute length
-        response.push(0x00); // Reserved
-        response.push(0x01); // Family (IPv4)
-        
-        // Port XOR'd with magic cookie
-        let port = client_addr.port() ^ 0x2112;
-        response.extend_from_slice(&port.to_be_bytes());
-        
-        // IP XOR'd with magic cookie
-        if let SocketAddr::V4(addr_v4) = client_addr {
-            let ip_bytes = addr_v4.ip().octets();
-            let magic_bytes = 0x2112A442u32.to_be_bytes();
-            for (i, &byte) in ip_bytes.iter().enumerate() {
-                response.push(byte ^ magic_bytes[i]);
-            }
+// A response to `request`, signed when the request was authenticated
+fn respond(
+    request: &Message,
+    class: MessageClass,
+    attributes: Vec<Box<dyn Setter>>,
+    integrity: Option<&MessageIntegrity>,
+) -> Result<Message> {
+    let mut setters: Vec<Box<dyn Setter>> = vec![
+        Box::new(request.transaction_id),
+        Box::new(MessageType::new(request.typ.method, class)),
+    ];
+    setters.extend(attributes);
+    if let Some(integrity) = integrity {
+        setters.push(Box::new(integrity.clone()));
+    }
+    let mut response = Message::new();
+    response.build(&setters)?;
+    Ok(response)
+}
+
+fn error_response(request: &Message, code: ErrorCode, reason: &str, integrity: Option<&MessageIntegrity>) -> Result<Message> {
+    let error = ErrorCodeAttribute { code, reason: reason.as_bytes().to_vec() };
+    respond(request, CLASS_ERROR_RESPONSE, vec![Box::new(error)], integrity)
+}
+
+fn allocated(
+    request: &Message,
+    client: SocketAddr,
+    relay_addr: SocketAddr,
+    lifetime: Duration,
+    integrity: Option<&MessageIntegrity>,
+) -> Result<Message> {
+    respond(
+        request,
+        CLASS_SUCCESS_RESPONSE,
+        vec![
+            Box::new(XorAddress(ATTR_XOR_RELAYED_ADDRESS, relay_addr)),
+            Box::new(Lifetime(lifetime)),
+            Box::new(XorAddress(ATTR_XORMAPPED_ADDRESS, client)),
+        ],
+        integrity,
+    )
+}
+
+fn data_indication(peer: SocketAddr, data: &[u8]) -> Result<Message> {
+    let mut indication = Message::new();
+    indication.build(&[
+        Box::new(TransactionId::new()),
+        Box::new(MessageType::new(METHOD_DATA, CLASS_INDICATION)),
+        Box::new(XorAddress(ATTR_XOR_PEER_ADDRESS, peer)),
+        Box::new(Data(data.to_vec())),
+    ])?;
+    Ok(indication)
+}
+
+fn channel_data(number: u16, data: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(4 + data.len());
+    packet.extend_from_slice(&number.to_be_bytes());
+    packet.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    packet.extend_from_slice(data);
+    packet
+}
+
+// Requested lifetime, capped at MAX_LIFETIME
+fn requested_lifetime(request: &Message) -> Option<Duration> {
+    let value = request.get(ATTR_LIFETIME).ok().filter(|value| value.len() >= 4)?;
+    let seconds = u32::from_be_bytes([value[0], value[1], value[2], value[3]]);
+    Some(Duration::from_secs(seconds.into()).min(MAX_LIFETIME))
+}
+
+// Every XOR-PEER-ADDRESS of a request, which may carry several
+fn xor_peer_addresses(request: &Message) -> Vec<SocketAddr> {
+    let mut key = MAGIC_COOKIE.to_be_bytes().to_vec();
+    key.extend_from_slice(&request.transaction_id.0);
+
+    let decode = |value: &[u8]| -> Option<SocketAddr> {
+        let family = u16::from_be_bytes([*value.first()?, *value.get(1)?]);
+        let port = u16::from_be_bytes([*value.get(2)?, *value.get(3)?]) ^ (MAGIC_COOKIE >> 16) as u16;
+        let address = value.get(4..)?;
+        let ip = match (family, address.len()) {
+            (1, 4) => IpAddr::from(<[u8; 4]>::try_from(xor(address, &key)).ok()?),
+            (2, 16) => IpAddr::from(<[u8; 16]>::try_from(xor(address, &key)).ok()?),
+            _ => return None,
+        };
+        Some(SocketAddr::new(ip, port))
+    };
+    request
+        .attributes
+        .0
+        .iter()
+        .filter(|attribute| attribute.typ == ATTR_XOR_PEER_ADDRESS)
+        .filter_map(|attribute| decode(&attribute.value))
+        .collect()
+}
+
+fn xor(value: &[u8], key: &[u8]) -> Vec<u8> {
+    value.iter().zip(key).map(|(a, b)| a ^ b).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::admin::{self, AdminState};
+    use crate::modules::config::Config;
+    use crate::modules::reload::ConfigReloader;
+    use crate::modules::signaling::{Signaler, TurnCredentials};
+    use axum::extract::{Path, State};
+    use axum::http::StatusCode;
+    use stun::message::Getter;
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    struct Raw(AttrType, Vec<u8>);
+
+    impl Setter for Raw {
+        fn add_to(&self, m: &mut Message) -> Result<(), stun::Error> {
+            m.add(self.0, &self.1);
+            Ok(())
         }
-        
-        // Update message length
-        let attr_length = response.len() - 20; // Exclude header
-        response[length_pos..length_pos + 2].copy_from_slice(&(attr_length as u16).to_be_bytes());
-        
-        Ok(response)
     }
-}
\ No newline at end of file
+
+    struct Harness {
+        signaler: Arc<Signaler>,
+        allocations: AllocationMap,
+        server: SocketAddr,
+        client: UdpSocket,
+    }
+
+    async fn harness() -> Harness {
+        harness_with(Config::builder().public_ip("127.0.0.1").build().unwrap()).await
+    }
+
+    async fn harness_with(config: Config) -> Harness {
+        let config = config.into_handle();
+        let signaler = Signaler::builder().config_handle(config.clone()).build().unwrap();
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let server = socket.local_addr().unwrap();
+        let allocations: AllocationMap = Arc::new(Mutex::new(HashMap::new()));
+        let relay = TurnRelay::new(socket, signaler.clone(), config, allocations.clone());
+        tokio::spawn(relay.run());
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        Harness { signaler, allocations, server, client }
+    }
+
+    impl Harness {
+        async fn exchange(&self, request: &Message) -> Message {
+            self.client.send_to(&request.raw, self.server).await.unwrap();
+            let mut response = Message::new();
+            response.raw = self.receive().await;
+            response.decode().unwrap();
+            response
+        }
+
+        async fn receive(&self) -> Vec<u8> {
+            let mut buffer = vec![0u8; 2048];
+            let (len, _) = tokio::time::timeout(TIMEOUT, self.client.recv_from(&mut buffer))
+                .await
+                .expect("no packet from the relay")
+                .unwrap();
+            buffer.truncate(len);
+            buffer
+        }
+
+        // Sends `attributes` with the long-term credential of `username`,
+        // answering the 401 challenge first
+        async fn authenticated(
+            &self,
+            method: stun::message::Method,
+            username: &str,
+            password: &str,
+            attributes: Vec<Box<dyn Setter>>,
+        ) -> Message {
+            let challenge = self.exchange(&build(method, Vec::new())).await;
+            assert_eq!(error_code(&challenge), Some(CODE_UNAUTHORIZED.0));
+            let realm = TextAttribute::get_from_as(&challenge, ATTR_REALM).unwrap().text;
+            let nonce = TextAttribute::get_from_as(&challenge, ATTR_NONCE).unwrap().text;
+
+            let mut setters = attributes;
+            setters.push(Box::new(TextAttribute::new(ATTR_USERNAME, username.to_string())));
+            setters.push(Box::new(TextAttribute::new(ATTR_REALM, realm.clone())));
+            setters.push(Box::new(TextAttribute::new(ATTR_NONCE, nonce)));
+            setters.push(Box::new(MessageIntegrity::new_long_term_integrity(
+                username.to_string(),
+                realm,
+                password.to_string(),
+            )));
+            self.exchange(&build(method, setters)).await
+        }
+
+        async fn allocate(&self, username: &str, password: &str) -> Message {
+            let transport = Box::new(Raw(ATTR_REQUESTED_TRANSPORT, vec![PROTOCOL_UDP, 0, 0, 0]));
+            self.authenticated(METHOD_ALLOCATE, username, password, vec![transport]).await
+        }
+    }
+
+    fn build(method: stun::message::Method, attributes: Vec<Box<dyn Setter>>) -> Message {
+        let mut setters: Vec<Box<dyn Setter>> = vec![
+            Box::new(TransactionId::new()),
+            Box::new(MessageType::new(method, CLASS_REQUEST)),
+        ];
+        setters.extend(attributes);
+        let mut m = Message::new();
+        m.build(&setters).unwrap();
+        m
+    }
+
+    fn error_code(m: &Message) -> Option<u16> {
+        let mut error = ErrorCodeAttribute::default();
+        error.get_from(m).ok().map(|_| error.code.0)
+    }
+
+    fn relayed_address(m: &Message) -> SocketAddr {
+        let mut address = XorMappedAddress::default();
+        address.get_from_as(m, ATTR_XOR_RELAYED_ADDRESS).unwrap();
+        SocketAddr::new(address.ip, address.port)
+    }
+
+    #[tokio::test]
+    async fn allocate_requires_issued_credentials() {
+        let harness = harness().await;
+        let credentials = harness.signaler.generate_turn_credentials("alice").await.unwrap();
+
+        let response = harness.allocate(&credentials.username, "not the password").await;
+        assert_eq!(error_code(&response), Some(CODE_UNAUTHORIZED.0));
+        // Well formed, but never issued into the store
+        let unissued = TurnCredentials::generate(&Config::builder().build().unwrap().turn, "mallory").unwrap();
+        let response = harness.allocate(&unissued.username, &unissued.password).await;
+        assert_eq!(error_code(&response), Some(CODE_UNAUTHORIZED.0));
+
+        assert_eq!(harness.signaler.metrics.turn_auth_failures.get(), 2);
+        assert!(harness.allocations.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn allocate_accepts_credentials_issued_by_another_process() {
+        let dir = std::env::temp_dir().join(format!("turn-{}", uuid::Uuid::new_v4().simple()));
+        let path = dir.join("state.sqlite");
+        let config = Config::builder()
+            .public_ip("127.0.0.1")
+            .set("store", "path", path.to_str().unwrap())
+            .build()
+            .unwrap();
+        let turn = config.turn.clone();
+        let harness = harness_with(config).await;
+
+        // What `gen-credential` does
+        let store = crate::modules::store::SqliteStore::open(&path).unwrap();
+        let credentials = TurnCredentials::issue(&turn, &store, "alice").await.unwrap();
+        let response = harness.allocate(&credentials.username, &credentials.password).await;
+        assert_eq!(error_code(&response), None);
+        assert_eq!(harness.allocations.lock().await.len(), 1);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn allocation_relays_to_permitted_peers() {
+        let harness = harness().await;
+        let credentials = harness.signaler.generate_turn_credentials("alice").await.unwrap();
+        let response = harness.allocate(&credentials.username, &credentials.password).await;
+        assert_eq!(response.typ.class, CLASS_SUCCESS_RESPONSE);
+        let relay_addr = relayed_address(&response);
+        assert_eq!(harness.signaler.metrics.turn_allocations.get(), 1);
+
+        let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let peer_addr = peer.local_addr().unwrap();
+        let permission = Box::new(XorAddress(ATTR_XOR_PEER_ADDRESS, peer_addr));
+        let response = harness
+            .authenticated(METHOD_CREATE_PERMISSION, &credentials.username, &credentials.password, vec![permission])
+            .await;
+        assert_eq!(response.typ.class, CLASS_SUCCESS_RESPONSE);
+        assert_eq!(harness.signaler.metrics.turn_permissions.get(), 1);
+
+        // Client to peer in a Send indication
+        let mut send = Message::new();
+        send.build(&[
+            Box::new(TransactionId::new()),
+            Box::new(MessageType::new(METHOD_SEND, CLASS_INDICATION)),
+            Box::new(XorAddress(ATTR_XOR_PEER_ADDRESS, peer_addr)),
+            Box::new(Data(b"ping".to_vec())),
+        ])
+        .unwrap();
+        harness.client.send_to(&send.raw, harness.server).await.unwrap();
+        let mut buffer = [0u8; 64];
+        let (len, from) = tokio::time::timeout(TIMEOUT, peer.recv_from(&mut buffer)).await.unwrap().unwrap();
+        assert_eq!(&buffer[..len], b"ping");
+        assert_eq!(from.port(), relay_addr.port());
+
+        // Peer to client in a Data indication
+        peer.send_to(b"pong", relay_addr).await.unwrap();
+        let mut data = Message::new();
+        data.raw = harness.receive().await;
+        data.decode().unwrap();
+        assert_eq!(data.typ, MessageType::new(METHOD_DATA, CLASS_INDICATION));
+        assert_eq!(data.get(ATTR_DATA).unwrap(), b"pong");
+        assert_eq!(xor_peer_addresses(&data), vec![peer_addr]);
+    }
+
+    #[tokio::test]
+    async fn channel_binding_relays_channel_data() {
+        let harness = harness().await;
+        let credentials = harness.signaler.generate_turn_credentials("alice").await.unwrap();
+        let relay_addr = relayed_address(&harness.allocate(&credentials.username, &credentials.password).await);
+
+        let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let peer_addr = peer.local_addr().unwrap();
+        let bind: Vec<Box<dyn Setter>> = vec![
+            Box::new(Raw(ATTR_CHANNEL_NUMBER, vec![0x40, 0x00, 0, 0])),
+            Box::new(XorAddress(ATTR_XOR_PEER_ADDRESS, peer_addr)),
+        ];
+        let response = harness
+            .authenticated(METHOD_CHANNEL_BIND, &credentials.username, &credentials.password, bind)
+            .await;
+        assert_eq!(response.typ.class, CLASS_SUCCESS_RESPONSE);
+        assert_eq!(harness.signaler.metrics.turn_channels.get(), 1);
+
+        harness.client.send_to(&channel_data(0x4000, b"ping"), harness.server).await.unwrap();
+        let mut buffer = [0u8; 64];
+        let (len, _) = tokio::time::timeout(TIMEOUT, peer.recv_from(&mut buffer)).await.unwrap().unwrap();
+        assert_eq!(&buffer[..len], b"ping");
+
+        peer.send_to(b"pong", relay_addr).await.unwrap();
+        assert_eq!(harness.receive().await, channel_data(0x4000, b"pong"));
+    }
+
+    #[tokio::test]
+    async fn revoked_credentials_lose_their_allocation() {
+        let harness = harness().await;
+        let credentials = harness.signaler.generate_turn_credentials("alice").await.unwrap();
+        let relay_addr = relayed_address(&harness.allocate(&credentials.username, &credentials.password).await);
+        let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let permission = Box::new(XorAddress(ATTR_XOR_PEER_ADDRESS, peer.local_addr().unwrap()));
+        harness
+            .authenticated(METHOD_CREATE_PERMISSION, &credentials.username, &credentials.password, vec![permission])
+            .await;
+
+        let config = harness.signaler.config.clone();
+        let state = AdminState {
+            signaler: harness.signaler.clone(),
+            allocations: harness.allocations.clone(),
+            reloader: Arc::new(ConfigReloader::new(config, || Ok(Config::builder().build()?))),
+        };
+        let response = admin::revoke_credentials(State(state), Path("alice".to_string())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        peer.send_to(b"pong", relay_addr).await.unwrap();
+        let mut buffer = [0u8; 64];
+        let relayed = tokio::time::timeout(Duration::from_millis(300), harness.client.recv_from(&mut buffer)).await;
+        assert!(relayed.is_err(), "relay kept forwarding after revocation");
+
+        let response = harness.allocate(&credentials.username, &credentials.password).await;
+        assert_eq!(error_code(&response), Some(CODE_UNAUTHORIZED.0));
+    }
+
+    #[tokio::test]
+    async fn refresh_with_zero_lifetime_releases_the_allocation() {
+        let harness = harness().await;
+        let credentials = harness.signaler.generate_turn_credentials("alice").await.unwrap();
+        harness.allocate(&credentials.username, &credentials.password).await;
+        assert_eq!(harness.allocations.lock().await.len(), 1);
+
+        let lifetime = Box::new(Lifetime(Duration::ZERO));
+        let response = harness
+            .authenticated(METHOD_REFRESH, &credentials.username, &credentials.password, vec![lifetime])
+            .await;
+        assert_eq!(response.typ.class, CLASS_SUCCESS_RESPONSE);
+        assert!(harness.allocations.lock().await.is_empty());
+        assert_eq!(harness.signaler.metrics.turn_allocations.get(), 0);
+    }
+}
@@ -0,0 +1,256 @@
+use std::fmt;
+
+use crate::modules::signaling::{IceCandidate, Method, SessionDescription, SignalingError};
+
+/// A rule broken by an inbound signaling message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    MessageTooLarge { size: usize, limit: usize },
+    Malformed { request: String, detail: String },
+    MissingField(&'static str),
+    InvalidSdpType { expected: &'static str, actual: String },
+    InvalidSdp,
+    MissingCandidateTarget,
+    SenderMismatch { claimed: String, registered: String },
+    /// `new` with another id from a connection that already registered
+    AlreadyRegistered { registered: String },
+    /// Session message from a connection that has not sent `new` yet
+    NotRegistered,
+}
+
+impl ValidationError {
+    /// Stable identifier of the violated rule, sent to clients in `error` replies.
+    pub fn rule(&self) -> &'static str {
+        match self {
+            ValidationError::MessageTooLarge { .. } => "max_message_size",
+            ValidationError::Malformed { .. } => "schema",
+            ValidationError::MissingField(_) => "required_field",
+            ValidationError::InvalidSdpType { .. } => "sdp_type",
+            ValidationError::InvalidSdp => "sdp_format",
+            ValidationError::MissingCandidateTarget => "candidate_target",
+            ValidationError::SenderMismatch { .. } => "sender_mismatch",
+            ValidationError::AlreadyRegistered { .. } => "already_registered",
+            ValidationError::NotRegistered => "not_registered",
+        }
+    }
+
+    /// Message type the error relates to, when it could be determined.
+    pub fn request(&self) -> &str {
+        match self {
+            ValidationError::Malformed { request, .. } => request,
+            _ => "unknown",
+        }
+    }
+
+    pub fn to_reply(&self, request: &str) -> Method {
+        Method::Error(SignalingError {
+            request: request.to_string(),
+            reason: self.to_string(),
+            rule: Some(self.rule().to_string()),
+        })
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::MessageTooLarge { size, limit } => {
+                write!(f, "Message of {} bytes exceeds the {} byte limit", size, limit)
+            }
+            ValidationError::Malformed { detail, .. } => write!(f, "Malformed message: {}", detail),
+            ValidationError::MissingField(field) => write!(f, "Field '{}' must not be empty", field),
+            ValidationError::InvalidSdpType { expected, actual } => {
+                write!(f, "Description type must be '{}', got '{}'", expected, actual)
+            }
+            ValidationError::InvalidSdp => write!(f, "Description sdp must start with 'v='"),
+            ValidationError::MissingCandidateTarget => {
+                write!(f, "Candidate requires sdpMid or sdpMLineIndex")
+            }
+            ValidationError::SenderMismatch { claimed, registered } => write!(
+                f,
+                "Field 'from' is '{}' but this connection is registered as '{}'",
+                claimed, registered
+            ),
+            ValidationError::NotRegistered => write!(f, "Connection must register with 'new' first"),
+            ValidationError::AlreadyRegistered { registered } => {
+                write!(f, "Connection is already registered as '{}'", registered)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+pub fn check_size(size: usize, limit: usize) -> Result<(), ValidationError> {
+    if size > limit {
+        return Err(ValidationError::MessageTooLarge { size, limit });
+    }
+    Ok(())
+}
+
+/// Checks the semantic rules that the typed payloads alone cannot express.
+pub fn validate(message: &Method, registered_id: Option<&str>) -> Result<(), ValidationError> {
+    match message {
+        Method::New(info) => {
+            require("id", &info.id)?;
+            // Re-registering updates the peer, another id would orphan it
+            match registered_id {
+                Some(registered) if registered != info.id => Err(ValidationError::AlreadyRegistered {
+                    registered: registered.to_string(),
+                }),
+                _ => Ok(()),
+            }
+        }
+        Method::Offer(payload) => {
+            require_routing(&payload.from, &payload.to, &payload.session_id, registered_id)?;
+            validate_description(&payload.description, "offer")
+        }
+        Method::Answer(payload) => {
+            require_routing(&payload.from, &payload.to, &payload.session_id, registered_id)?;
+            validate_description(&payload.description, "answer")
+        }
+        Method::Candidate(payload) => {
+            require_routing(&payload.from, &payload.to, &payload.session_id, registered_id)?;
+            validate_candidate(&payload.candidate)
+        }
+        Method::Bye(bye) => {
+            require("session_id", &bye.session_id)?;
+            require("from", &bye.from)?;
+            check_sender(&bye.from, registered_id)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn require(field: &'static str, value: &str) -> Result<(), ValidationError> {
+    if value.trim().is_empty() {
+        return Err(ValidationError::MissingField(field));
+    }
+    Ok(())
+}
+
+fn require_routing(
+    from: &str,
+    to: &str,
+    session_id: &str,
+    registered_id: Option<&str>,
+) -> Result<(), ValidationError> {
+    require("from", from)?;
+    require("to", to)?;
+    require("session_id", session_id)?;
+    check_sender(from, registered_id)
+}
+
+fn check_sender(from: &str, registered_id: Option<&str>) -> Result<(), ValidationError> {
+    match registered_id {
+        None => Err(ValidationError::NotRegistered),
+        Some(registered) if registered != from => Err(ValidationError::SenderMismatch {
+            claimed: from.to_string(),
+            registered: registered.to_string(),
+        }),
+        Some(_) => Ok(()),
+    }
+}
+
+fn validate_description(
+    description: &SessionDescription,
+    expected: &'static str,
+) -> Result<(), ValidationError> {
+    if description.sdp_type != expected {
+        return Err(ValidationError::InvalidSdpType {
+            expected,
+            actual: description.sdp_type.clone(),
+        });
+    }
+    if !description.sdp.starts_with("v=") {
+        return Err(ValidationError::InvalidSdp);
+    }
+    Ok(())
+}
+
+fn validate_candidate(candidate: &IceCandidate) -> Result<(), ValidationError> {
+    // An empty candidate string signals end-of-candidates and carries no target
+    if !candidate.candidate.is_empty()
+        && candidate.sdp_mid.is_none()
+        && candidate.sdp_mline_index.is_none()
+    {
+        return Err(ValidationError::MissingCandidateTarget);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn message(value: serde_json::Value) -> Method {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn offer(from: &str, sdp_type: &str, sdp: &str) -> Method {
+        message(json!({"type": "offer", "data": {
+            "from": from, "to": "bob", "session_id": "alice-bob",
+            "description": {"type": sdp_type, "sdp": sdp},
+        }}))
+    }
+
+    fn rule(message: &Method, registered_id: Option<&str>) -> Option<&'static str> {
+        validate(message, registered_id).err().map(|error| error.rule())
+    }
+
+    #[test]
+    fn session_messages_need_a_registered_sender() {
+        assert_eq!(rule(&offer("alice", "offer", "v=0"), None), Some("not_registered"));
+        assert_eq!(rule(&offer("mallory", "offer", "v=0"), Some("alice")), Some("sender_mismatch"));
+        assert_eq!(rule(&offer("alice", "offer", "v=0"), Some("alice")), None);
+
+        let bye = message(json!({"type": "bye", "data": {"session_id": "alice-bob", "from": "alice"}}));
+        assert_eq!(rule(&bye, None), Some("not_registered"));
+    }
+
+    #[test]
+    fn registration_needs_an_id() {
+        let new = message(json!({"type": "new", "data": {"id": "alice", "name": "", "user_agent": ""}}));
+        assert_eq!(rule(&new, None), None);
+        let new = message(json!({"type": "new", "data": {"id": " ", "name": "", "user_agent": ""}}));
+        assert_eq!(rule(&new, None), Some("required_field"));
+    }
+
+    #[test]
+    fn connections_register_one_id() {
+        let new = message(json!({"type": "new", "data": {"id": "alice", "name": "Alice", "user_agent": ""}}));
+        assert_eq!(rule(&new, Some("alice")), None);
+        assert_eq!(rule(&new, Some("mallory")), Some("already_registered"));
+    }
+
+    #[test]
+    fn descriptions_are_checked() {
+        assert_eq!(rule(&offer("alice", "offer", "o=-"), Some("alice")), Some("sdp_format"));
+        assert_eq!(rule(&offer("alice", "answer", "v=0"), Some("alice")), Some("sdp_type"));
+        assert_eq!(rule(&offer("", "offer", "v=0"), Some("alice")), Some("required_field"));
+    }
+
+    #[test]
+    fn candidates_need_a_target() {
+        let candidate = |candidate: &str, target: serde_json::Value| {
+            let mut payload = json!({"candidate": candidate});
+            payload.as_object_mut().unwrap().extend(target.as_object().unwrap().clone());
+            message(json!({"type": "candidate", "data": {
+                "from": "alice", "to": "bob", "session_id": "alice-bob", "candidate": payload,
+            }}))
+        };
+        let host = "candidate:1 1 udp 1 192.0.2.1 5000 typ host";
+        assert_eq!(rule(&candidate(host, json!({})), Some("alice")), Some("candidate_target"));
+        assert_eq!(rule(&candidate(host, json!({"sdpMid": "0"})), Some("alice")), None);
+        assert_eq!(rule(&candidate(host, json!({"sdpMLineIndex": 0})), Some("alice")), None);
+        // End of candidates
+        assert_eq!(rule(&candidate("", json!({})), Some("alice")), None);
+    }
+
+    #[test]
+    fn size_limit() {
+        assert!(check_size(10, 10).is_ok());
+        assert_eq!(check_size(11, 10).unwrap_err().rule(), "max_message_size");
+    }
+}
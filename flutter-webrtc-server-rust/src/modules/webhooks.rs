@@ -0,0 +1,508 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+use crate::modules::config::ConfigHandle;
+use crate::modules::metrics::Metrics;
+use crate::modules::signaling::{CallSession, PeerInfo};
+
+pub const PEER_ONLINE: &str = "peer.online";
+pub const PEER_OFFLINE: &str = "peer.offline";
+pub const CALL_STARTED: &str = "call.started";
+pub const CALL_CONNECTED: &str = "call.connected";
+pub const CALL_ENDED: &str = "call.ended";
+
+/// Every event type, as accepted by `[webhooks] events`.
+pub const EVENT_TYPES: &[&str] = &[PEER_ONLINE, PEER_OFFLINE, CALL_STARTED, CALL_CONNECTED, CALL_ENDED];
+
+/// Header carrying `sha256=<hex HMAC-SHA256 of "<timestamp>.<body>">` when a secret is set.
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+/// Header carrying the signed Unix timestamp of the request, so receivers can
+/// reject replays of old requests.
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+
+/// JSON body POSTed to webhook endpoints.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookEvent {
+    /// Unique per event, stays the same across retries so receivers can deduplicate
+    pub id: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub timestamp: DateTime<Utc>,
+    pub data: serde_json::Value,
+}
+
+impl WebhookEvent {
+    fn new(event_type: &str, data: serde_json::Value) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            event_type: event_type.to_string(),
+            timestamp: Utc::now(),
+            data,
+        }
+    }
+
+    pub fn peer(event_type: &str, peer: &PeerInfo, remote_addr: Option<SocketAddr>) -> Self {
+        Self::new(
+            event_type,
+            serde_json::json!({
+                "id": peer.id,
+                "name": peer.name,
+                "user_agent": peer.user_agent,
+                "remote_addr": remote_addr.map(|addr| addr.to_string()),
+            }),
+        )
+    }
+
+    pub fn call(event_type: &str, session: &CallSession) -> Self {
+        Self::new(event_type, serde_json::json!(session))
+    }
+}
+
+/// HMAC-SHA256 of `<timestamp>.<body>` in the `sha256=<hex>` form sent in
+/// `X-Webhook-Signature`.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Delivery {
+    seq: u64,
+    event: WebhookEvent,
+}
+
+enum SpoolOp {
+    Write(PathBuf, Vec<u8>),
+    Remove(PathBuf),
+}
+
+/// Writes and removes the files of a persistent queue in order on a background
+/// thread, so queueing an event never waits on the disk. Dropping it waits for
+/// the pending file operations.
+struct Spool {
+    ops: Option<std::sync::mpsc::Sender<SpoolOp>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Spool {
+    fn start() -> Result<Self> {
+        let (ops, received) = std::sync::mpsc::channel::<SpoolOp>();
+        let thread = std::thread::Builder::new()
+            .name("webhook-spool".to_string())
+            .spawn(move || {
+                for op in received {
+                    match op {
+                        SpoolOp::Write(path, data) => {
+                            if let Err(e) = std::fs::write(&path, data) {
+                                error!("Failed to persist webhook event to {}: {}", path.display(), e);
+                            }
+                        }
+                        SpoolOp::Remove(path) => {
+                            if let Err(e) = std::fs::remove_file(&path) {
+                                if e.kind() != std::io::ErrorKind::NotFound {
+                                    warn!("Failed to remove webhook event {}: {}", path.display(), e);
+                                }
+                            }
+                        }
+                    }
+                }
+            })?;
+        Ok(Self {
+            ops: Some(ops),
+            thread: Some(thread),
+        })
+    }
+
+    fn send(&self, op: SpoolOp) {
+        if let Some(ops) = &self.ops {
+            if ops.send(op).is_err() {
+                error!("❌ Webhook spool writer has stopped");
+            }
+        }
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        self.ops.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Undelivered events for one endpoint, mirrored to one file per event when
+/// the queue is persistent.
+struct DeliveryQueue {
+    url: String,
+    dir: Option<PathBuf>,
+    spool: Option<Spool>,
+    pending: Mutex<VecDeque<Delivery>>,
+    next_seq: AtomicU64,
+    notify: Notify,
+}
+
+impl DeliveryQueue {
+    fn open(url: &str, queue_dir: Option<&str>) -> Result<Self> {
+        // One directory per endpoint, named after the URL so reordering `urls` is harmless
+        let dir = queue_dir.map(|root| {
+            let digest = hex::encode(Sha256::digest(url.as_bytes()));
+            Path::new(root).join(&digest[..16])
+        });
+
+        let mut pending = Vec::new();
+        if let Some(dir) = &dir {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", dir.display(), e))?;
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                match std::fs::read(&path).map_err(anyhow::Error::from).and_then(|data| {
+                    Ok(serde_json::from_slice::<Delivery>(&data)?)
+                }) {
+                    Ok(delivery) => pending.push(delivery),
+                    Err(e) => {
+                        warn!("Discarding unreadable webhook event {}: {}", path.display(), e);
+                        let _ = std::fs::remove_file(&path);
+                    }
+                }
+            }
+            pending.sort_by_key(|delivery| delivery.seq);
+            if !pending.is_empty() {
+                info!("Resuming {} undelivered webhook events for {}", pending.len(), url);
+            }
+        }
+
+        let next_seq = pending.last().map_or(0, |delivery| delivery.seq + 1);
+        let spool = dir.as_ref().map(|_| Spool::start()).transpose()?;
+        Ok(Self {
+            url: url.to_string(),
+            dir,
+            spool,
+            pending: Mutex::new(pending.into()),
+            next_seq: AtomicU64::new(next_seq),
+            notify: Notify::new(),
+        })
+    }
+
+    fn path(&self, seq: u64) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| dir.join(format!("{:020}.json", seq)))
+    }
+
+    /// Queues `event`, dropping the oldest events past `limit`. Returns how many were dropped.
+    fn push(&self, event: WebhookEvent, limit: usize) -> usize {
+        let delivery = Delivery {
+            seq: self.next_seq.fetch_add(1, Ordering::SeqCst),
+            event,
+        };
+        if let (Some(spool), Some(path)) = (&self.spool, self.path(delivery.seq)) {
+            match serde_json::to_vec(&delivery) {
+                Ok(data) => spool.send(SpoolOp::Write(path, data)),
+                Err(e) => error!("Failed to persist webhook event to {}: {}", path.display(), e),
+            }
+        }
+
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.push_back(delivery);
+        let mut dropped = 0;
+        while pending.len() > limit {
+            if let Some(oldest) = pending.pop_front() {
+                self.remove_file(oldest.seq);
+                dropped += 1;
+            }
+        }
+        drop(pending);
+
+        self.notify.notify_one();
+        dropped
+    }
+
+    fn front(&self) -> Option<Delivery> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).front().cloned()
+    }
+
+    /// Removes a delivered or abandoned event, unless it was already dropped for space.
+    fn remove(&self, seq: u64) {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        if pending.front().is_some_and(|delivery| delivery.seq == seq) {
+            pending.pop_front();
+            self.remove_file(seq);
+        }
+    }
+
+    fn remove_file(&self, seq: u64) {
+        if let (Some(spool), Some(path)) = (&self.spool, self.path(seq)) {
+            spool.send(SpoolOp::Remove(path));
+        }
+    }
+}
+
+/// Sends signaling events to the `[webhooks]` endpoints. Each endpoint has its
+/// own queue and worker, so a slow endpoint only delays its own events, which
+/// are delivered in order.
+pub struct Webhooks {
+    config: ConfigHandle,
+    metrics: Arc<Metrics>,
+    queues: Vec<Arc<DeliveryQueue>>,
+}
+
+impl Webhooks {
+    /// Opens the queues for the configured endpoints and spawns their workers.
+    /// Must run inside a Tokio runtime.
+    pub fn start(config: ConfigHandle, metrics: Arc<Metrics>) -> Result<Arc<Self>> {
+        let runtime = tokio::runtime::Handle::try_current()
+            .map_err(|_| anyhow::anyhow!("Webhooks need a Tokio runtime"))?;
+        let snapshot = config.load_full();
+        let client = reqwest::Client::new();
+
+        let mut queues = Vec::new();
+        for url in &snapshot.webhooks.urls {
+            let queue = Arc::new(DeliveryQueue::open(url, snapshot.webhooks.queue_dir.as_deref())?);
+            runtime.spawn(deliver(queue.clone(), client.clone(), config.clone(), metrics.clone()));
+            queues.push(queue);
+        }
+        info!("Sending webhooks to {} endpoint(s)", queues.len());
+
+        Ok(Arc::new(Self { config, metrics, queues }))
+    }
+
+    /// Queues `event` for every endpoint, unless `[webhooks] events` filters it out.
+    pub fn emit(&self, event: WebhookEvent) {
+        let config = self.config.load();
+        let settings = &config.webhooks;
+        if !settings.events.is_empty() && !settings.events.contains(&event.event_type) {
+            return;
+        }
+
+        debug!("Queueing webhook {} ({})", event.event_type, event.id);
+        for queue in &self.queues {
+            let dropped = queue.push(event.clone(), settings.queue_size);
+            if dropped > 0 {
+                warn!("⚠️ Webhook queue for {} is full, dropped {} oldest event(s)", queue.url, dropped);
+                self.metrics
+                    .webhook_deliveries
+                    .with_label_values(&["dropped"])
+                    .inc_by(dropped as u64);
+            }
+        }
+    }
+
+    /// Undelivered events across all endpoints.
+    pub fn pending(&self) -> usize {
+        self.queues
+            .iter()
+            .map(|queue| queue.pending.lock().unwrap_or_else(|e| e.into_inner()).len())
+            .sum()
+    }
+}
+
+// Delivers the queue head until it succeeds or runs out of attempts, backing off
+// exponentially in between
+async fn deliver(queue: Arc<DeliveryQueue>, client: reqwest::Client, config: ConfigHandle, metrics: Arc<Metrics>) {
+    let mut attempt: u32 = 0;
+
+    loop {
+        let Some(delivery) = queue.front() else {
+            queue.notify.notified().await;
+            continue;
+        };
+
+        let settings = config.load().webhooks.clone();
+        match post(&client, &queue.url, &delivery.event, &settings).await {
+            Ok(()) => {
+                debug!("✅ Webhook {} delivered to {}", delivery.event.id, queue.url);
+                metrics.webhook_deliveries.with_label_values(&["delivered"]).inc();
+                queue.remove(delivery.seq);
+                attempt = 0;
+            }
+            Err(e) => {
+                attempt += 1;
+                if attempt >= settings.max_attempts {
+                    error!("❌ Giving up on webhook {} ({}) to {} after {} attempts: {}",
+                           delivery.event.id, delivery.event.event_type, queue.url, attempt, e);
+                    metrics.webhook_deliveries.with_label_values(&["failed"]).inc();
+                    queue.remove(delivery.seq);
+                    attempt = 0;
+                    continue;
+                }
+
+                let backoff = backoff(&settings, attempt);
+                warn!("⚠️ Webhook {} to {} failed (attempt {}/{}), retrying in {}ms: {}",
+                      delivery.event.id, queue.url, attempt, settings.max_attempts, backoff, e);
+                metrics.webhook_deliveries.with_label_values(&["retried"]).inc();
+                tokio::time::sleep(Duration::from_millis(backoff)).await;
+            }
+        }
+    }
+}
+
+// Delay before retrying after `attempt` failed attempts
+fn backoff(settings: &crate::modules::config::WebhookConfig, attempt: u32) -> u64 {
+    settings
+        .retry_base_ms
+        .saturating_mul(1u64 << attempt.saturating_sub(1).min(32))
+        .min(settings.retry_max_ms)
+}
+
+async fn post(
+    client: &reqwest::Client,
+    url: &str,
+    event: &WebhookEvent,
+    settings: &crate::modules::config::WebhookConfig,
+) -> Result<()> {
+    let body = serde_json::to_vec(event)?;
+
+    let mut request = client
+        .post(url)
+        .timeout(Duration::from_millis(settings.timeout_ms))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Webhook-Event", &event.event_type)
+        .header("X-Webhook-Id", &event.id);
+    if let Some(secret) = &settings.secret {
+        // Signed per attempt, so a retry carries a fresh timestamp
+        let timestamp = Utc::now().timestamp();
+        request = request
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(SIGNATURE_HEADER, sign(secret, timestamp, &body));
+    }
+
+    let response = request.body(body).send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        anyhow::bail!("endpoint answered {}", status);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::config::{Config, WebhookConfig};
+    use axum::{extract::State, http::HeaderMap, http::StatusCode, routing::post, Router};
+    use std::sync::atomic::AtomicUsize;
+
+    fn event(id: &str) -> WebhookEvent {
+        WebhookEvent {
+            id: id.to_string(),
+            ..WebhookEvent::new(PEER_ONLINE, serde_json::json!({}))
+        }
+    }
+
+    #[test]
+    fn signature_covers_timestamp_and_body() {
+        let signature = sign("secret", 1_700_000_000, br#"{"id":1}"#);
+        assert_eq!(signature, "sha256=3dd1b9aef568d75f6790a84bd2e5dfa1f44409eef3cbdbd3f10b837376100c11");
+        assert_ne!(signature, sign("secret", 1_700_000_001, br#"{"id":1}"#));
+        assert_ne!(signature, sign("other", 1_700_000_000, br#"{"id":1}"#));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let settings = WebhookConfig { retry_base_ms: 100, retry_max_ms: 1000, ..Default::default() };
+        let delays: Vec<u64> = (1..=6).map(|attempt| backoff(&settings, attempt)).collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+        assert_eq!(backoff(&settings, u32::MAX), 1000);
+    }
+
+    #[test]
+    fn full_queue_drops_the_oldest_events() {
+        let queue = DeliveryQueue::open("http://example.com/hook", None).unwrap();
+        assert_eq!(queue.push(event("a"), 2), 0);
+        assert_eq!(queue.push(event("b"), 2), 0);
+        assert_eq!(queue.push(event("c"), 2), 1);
+        assert_eq!(queue.front().unwrap().event.id, "b");
+    }
+
+    #[test]
+    fn persistent_queue_resumes_in_order() {
+        let dir = std::env::temp_dir().join(format!("webhooks-{}", uuid::Uuid::new_v4().simple()));
+        let dir_name = dir.to_str().unwrap();
+        {
+            let queue = DeliveryQueue::open("http://example.com/hook", Some(dir_name)).unwrap();
+            queue.push(event("a"), 10);
+            queue.push(event("b"), 10);
+            queue.push(event("c"), 10);
+            let delivered = queue.front().unwrap();
+            queue.remove(delivered.seq);
+        }
+
+        let queue = DeliveryQueue::open("http://example.com/hook", Some(dir_name)).unwrap();
+        assert_eq!(queue.front().unwrap().event.id, "b");
+        assert_eq!(queue.push(event("d"), 10), 0);
+        let pending = queue.pending.lock().unwrap();
+        let ids: Vec<&str> = pending.iter().map(|delivery| delivery.event.id.as_str()).collect();
+        assert_eq!(ids, ["b", "c", "d"]);
+        drop(pending);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    // Headers and body of a request
+    type Request = (HeaderMap, Vec<u8>);
+
+    #[derive(Clone, Default)]
+    struct Endpoint {
+        attempts: Arc<AtomicUsize>,
+        received: Arc<Mutex<Vec<Request>>>,
+    }
+
+    // Fails the first attempt, accepts the rest
+    async fn receive(State(endpoint): State<Endpoint>, headers: HeaderMap, body: axum::body::Bytes) -> StatusCode {
+        if endpoint.attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+            return StatusCode::SERVICE_UNAVAILABLE;
+        }
+        endpoint.received.lock().unwrap().push((headers, body.to_vec()));
+        StatusCode::NO_CONTENT
+    }
+
+    #[tokio::test]
+    async fn events_are_retried_and_signed() {
+        let endpoint = Endpoint::default();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let app = Router::new().route("/hook", post(receive)).with_state(endpoint.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let config = Config::builder()
+            .set("webhooks", "urls", &url)
+            .set("webhooks", "secret", "secret")
+            .set("webhooks", "retry_base_ms", 10)
+            .set("webhooks", "events", PEER_ONLINE)
+            .build()
+            .unwrap()
+            .into_handle();
+        let metrics = Arc::new(Metrics::new().unwrap());
+        let webhooks = Webhooks::start(config, metrics.clone()).unwrap();
+        webhooks.emit(WebhookEvent::new(CALL_ENDED, serde_json::json!({})));
+        webhooks.emit(event("online"));
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while webhooks.pending() > 0 && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let received = endpoint.received.lock().unwrap();
+        assert_eq!(received.len(), 1, "filtered events must not be sent");
+        let (headers, body) = &received[0];
+        assert_eq!(headers["X-Webhook-Id"], "online");
+        let timestamp: i64 = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+        assert!((Utc::now().timestamp() - timestamp).abs() < 60);
+        assert_eq!(headers[SIGNATURE_HEADER], sign("secret", timestamp, body).as_str());
+        assert_eq!(metrics.webhook_deliveries.with_label_values(&["retried"]).get(), 1);
+        assert_eq!(metrics.webhook_deliveries.with_label_values(&["delivered"]).get(), 1);
+    }
+}
@@ -0,0 +1,305 @@
+use anyhow::{Context, Result};
+use axum::extract::ws::{close_code, CloseFrame};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use log::{debug, error, info, warn};
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tokio::sync::mpsc;
+use wtransport::endpoint::endpoint_side::Server;
+use wtransport::endpoint::IncomingSession;
+use wtransport::tls::Sha256DigestFmt;
+use wtransport::{Endpoint, Identity, ServerConfig, VarInt};
+
+use crate::modules::http::{admit, describe};
+use crate::modules::signaling::{Method, ShutdownPhase, Signaler};
+
+// Time an accepted session has to open its stream, while it holds a connection slot
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Signaling over WebTransport (HTTP/3 over QUIC), for clients on lossy mobile
+/// networks: QUIC has no head-of-line blocking across packets of different
+/// streams and keeps sessions alive when the client's address changes. Clients
+/// open one bidirectional stream on the session and exchange the JSON messages
+/// of `/ws` text frames on it, one per line.
+pub struct WebTransportServer {
+    endpoint: Endpoint<Server>,
+    signaler: Arc<Signaler>,
+}
+
+impl WebTransportServer {
+    /// Binds the UDP socket with the PEM certificate chain and private key at `cert`
+    /// and `key`.
+    pub async fn bind(signaler: Arc<Signaler>, addr: SocketAddr, cert: &str, key: &str) -> Result<Self> {
+        let identity = Identity::load_pemfiles(cert, key)
+            .await
+            .with_context(|| format!("Failed to load certificate {} and key {}", cert, key))?;
+        if let Some(certificate) = identity.certificate_chain().as_slice().first() {
+            // Browsers accept self-signed certificates pinned with `serverCertificateHashes`
+            info!(
+                "WebTransport certificate SHA-256: {}",
+                certificate.hash().fmt(Sha256DigestFmt::BytesArray)
+            );
+        }
+
+        let config = ServerConfig::builder()
+            .with_bind_address(addr)
+            .with_identity(identity)
+            .keep_alive_interval(Some(Duration::from_secs(3)))
+            // Notices clients that vanished without closing, e.g. after losing coverage
+            .max_idle_timeout(Some(Duration::from_secs(10)))?
+            .build();
+        let endpoint = Endpoint::server(config).with_context(|| format!("Failed to bind {}", addr))?;
+        Ok(Self { endpoint, signaler })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.endpoint.local_addr()?)
+    }
+
+    /// Accepts sessions until the signaler starts draining. Open sessions are
+    /// drained along with the WebSocket connections.
+    pub async fn run(self) {
+        let mut shutdown = self.signaler.watch_shutdown();
+        loop {
+            tokio::select! {
+                incoming = self.endpoint.accept() => {
+                    tokio::spawn(handle_session(self.signaler.clone(), incoming));
+                }
+                Ok(()) = shutdown.changed() => {
+                    if !matches!(*shutdown.borrow_and_update(), ShutdownPhase::Running) {
+                        break;
+                    }
+                }
+            }
+        }
+        info!("WebTransport endpoint stopped accepting sessions");
+    }
+}
+
+async fn handle_session(signaler: Arc<Signaler>, incoming: IncomingSession) {
+    let request = match incoming.await {
+        Ok(request) => request,
+        Err(e) => {
+            debug!("WebTransport handshake failed: {}", e);
+            return;
+        }
+    };
+    info!("New WebTransport session attempt from {}", request.remote_address());
+
+    if request.path() != signaler.config.load().webtransport.path {
+        request.not_found().await;
+        return;
+    }
+
+    let (remote_addr, _guard) = match admit(&signaler, Some(request.remote_address()), headers(request.headers())).await {
+        Ok(admitted) => admitted,
+        Err((status, _)) => {
+            // WebTransport has no 503, both mean "try again later"
+            match status {
+                StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => request.too_many_requests().await,
+                _ => request.forbidden().await,
+            }
+            return;
+        }
+    };
+
+    let session = match request.accept().await {
+        Ok(session) => session,
+        Err(e) => {
+            warn!("Failed to accept WebTransport session from {}: {}", describe(remote_addr), e);
+            return;
+        }
+    };
+    let (mut send, recv) = match tokio::time::timeout(HANDSHAKE_TIMEOUT, session.accept_bi()).await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => {
+            debug!("WebTransport session from {} closed before opening a stream: {}", describe(remote_addr), e);
+            return;
+        }
+        Err(_) => {
+            info!("WebTransport session from {} opened no stream in {}s, closing",
+                  describe(remote_addr), HANDSHAKE_TIMEOUT.as_secs());
+            session.close(VarInt::from_u32(close_code::POLICY.into()), b"No signaling stream opened");
+            return;
+        }
+    };
+
+    let (connection, mut events) = signaler.connect(remote_addr);
+    signaler.active_connections.fetch_add(1, Ordering::SeqCst);
+    let mut shutdown = signaler.watch_shutdown();
+    // Sessions opened while draining still hear about it
+    shutdown.mark_changed();
+
+    let (lines_tx, mut lines) = mpsc::channel(16);
+    let reader = tokio::spawn(read_lines(signaler.clone(), recv, lines_tx));
+
+    let mut close = None;
+    loop {
+        let message = tokio::select! {
+            line = lines.recv() => {
+                match line {
+                    Some(Ok(text)) => {
+                        debug!("Received WebTransport message: {}", text);
+                        if let Err(e) = signaler.receive_text(&connection, &text).await {
+                            error!("Error handling message: {}", e);
+                        }
+                    }
+                    Some(Err(frame)) => {
+                        close = Some(frame);
+                        break;
+                    }
+                    None => break,
+                }
+                continue;
+            }
+            message = events.messages.recv() => match message {
+                Some(message) => message,
+                None => break,
+            },
+            Some(frame) = events.close.recv() => {
+                close = Some(frame);
+                break;
+            }
+            Ok(()) = shutdown.changed() => {
+                let phase = shutdown.borrow_and_update().clone();
+                match phase {
+                    ShutdownPhase::Running => continue,
+                    ShutdownPhase::Draining(goaway) => Method::GoAway(goaway),
+                    ShutdownPhase::Closing => {
+                        close = Some(CloseFrame {
+                            code: close_code::AWAY,
+                            reason: "Server shutting down".into(),
+                        });
+                        break;
+                    }
+                }
+            }
+            e = session.closed() => {
+                info!("WebTransport session from {} closed: {}", describe(remote_addr), e);
+                break;
+            }
+        };
+
+        let mut line = match serde_json::to_string(&message) {
+            Ok(line) => line,
+            Err(e) => {
+                error!("Failed to serialize message: {}", e);
+                continue;
+            }
+        };
+        line.push('\n');
+        if let Err(e) = send.write_all(line.as_bytes()).await {
+            debug!("Failed to write to WebTransport session from {}: {}", describe(remote_addr), e);
+            break;
+        }
+    }
+
+    reader.abort();
+    signaler.disconnect(&connection).await;
+    signaler.active_connections.fetch_sub(1, Ordering::SeqCst);
+    let close = close.unwrap_or(CloseFrame {
+        code: close_code::NORMAL,
+        reason: "".into(),
+    });
+    session.close(VarInt::from_u32(close.code.into()), close.reason.as_bytes());
+}
+
+// Splits the stream into lines of at most `max_message_size` bytes. A longer
+// line can't be skipped reliably, so it ends the session.
+async fn read_lines(
+    signaler: Arc<Signaler>,
+    recv: impl AsyncRead + Unpin,
+    lines: mpsc::Sender<Result<String, CloseFrame<'static>>>,
+) {
+    let mut reader = BufReader::new(recv);
+    loop {
+        let limit = signaler.config.load().signaling.max_message_size as u64 + 1;
+        let mut line = Vec::new();
+        let line = match (&mut reader).take(limit).read_until(b'\n', &mut line).await {
+            Ok(0) => return,
+            Ok(_) if line.last() == Some(&b'\n') => {
+                line.pop();
+                Ok(String::from_utf8_lossy(&line).into_owned())
+            }
+            Ok(read) if read as u64 == limit => Err(CloseFrame {
+                code: close_code::SIZE,
+                reason: "Message too large".into(),
+            }),
+            // The stream ended after a last line without newline
+            Ok(_) => Ok(String::from_utf8_lossy(&line).into_owned()),
+            Err(e) => {
+                debug!("Failed to read from WebTransport stream: {}", e);
+                return;
+            }
+        };
+        if line.as_ref().is_ok_and(|line| line.trim().is_empty()) {
+            continue;
+        }
+        let last = line.is_err();
+        if lines.send(line).await.is_err() || last {
+            return;
+        }
+    }
+}
+
+fn headers(headers: &std::collections::HashMap<String, String>) -> HeaderMap {
+    headers
+        .iter()
+        .filter_map(|(name, value)| {
+            let name = HeaderName::try_from(name.as_str()).ok()?;
+            let value = HeaderValue::try_from(value.as_str()).ok()?;
+            Some((name, value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::config::Config;
+    use tokio::io::AsyncWriteExt;
+
+    // Lines read from `input` with a `max_message_size` of 16 bytes
+    async fn lines(input: &[u8]) -> Vec<Result<String, CloseFrame<'static>>> {
+        let config = Config::builder().set("signaling", "max_message_size", 16).build().unwrap();
+        let signaler = Signaler::builder().config(config).build().unwrap();
+        let (mut writer, reader) = tokio::io::duplex(1024);
+        writer.write_all(input).await.unwrap();
+        drop(writer);
+
+        let (lines_tx, mut lines_rx) = mpsc::channel(16);
+        read_lines(signaler, reader, lines_tx).await;
+        let mut lines = Vec::new();
+        while let Some(line) = lines_rx.recv().await {
+            lines.push(line);
+        }
+        lines
+    }
+
+    #[tokio::test]
+    async fn splits_messages_on_newlines() {
+        let lines = lines(b"{\"a\":1}\n\n  \n{\"b\":2}\n{\"c\":3}").await;
+        let lines: Vec<String> = lines.into_iter().map(Result::unwrap).collect();
+        // Blank lines are skipped, a last line may omit the newline
+        assert_eq!(lines, [r#"{"a":1}"#, r#"{"b":2}"#, r#"{"c":3}"#]);
+    }
+
+    #[tokio::test]
+    async fn line_at_the_limit_is_accepted() {
+        let lines = lines(b"0123456789abcdef\nnext\n").await;
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].as_deref().unwrap(), "0123456789abcdef");
+    }
+
+    #[tokio::test]
+    async fn overlong_line_ends_the_stream() {
+        let lines = lines(b"ok\n0123456789abcdefX\nnever read\n").await;
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].as_deref().unwrap(), "ok");
+        let close = lines[1].as_ref().unwrap_err();
+        assert_eq!(close.code, close_code::SIZE);
+    }
+}
@@ -0,0 +1,675 @@
+use axum::{
+    body::Bytes,
+    extract::{ConnectInfo, OriginalUri, Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::{patch, post},
+    Router,
+};
+use dashmap::DashMap;
+use log::{debug, error, info, warn};
+use std::{net::SocketAddr, sync::Arc};
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant};
+
+use crate::modules::util::constant_time_eq;
+use crate::modules::http::{admit, describe};
+use crate::modules::rate_limit::ConnectionGuard;
+use crate::modules::signaling::{
+    Byebye, CandidatePayload, Connection, ConnectionEvents, DescriptionPayload, IceCandidate, Method, PeerInfo,
+    SessionDescription, Signaler,
+};
+
+const SDP: &str = "application/sdp";
+const SDP_FRAGMENT: &str = "application/trickle-ice-sdpfrag";
+
+/// Which way media flows between the HTTP client and the called peer.
+#[derive(Debug, Clone, Copy)]
+enum Direction {
+    /// WHIP: the client publishes to the peer
+    Ingest,
+    /// WHEP: the client plays what the peer sends
+    Egress,
+}
+
+impl Direction {
+    fn protocol(self) -> &'static str {
+        match self {
+            Direction::Ingest => "whip",
+            Direction::Egress => "whep",
+        }
+    }
+
+    fn peer_name(self) -> &'static str {
+        match self {
+            Direction::Ingest => "WHIP publisher",
+            Direction::Egress => "WHEP viewer",
+        }
+    }
+}
+
+/// A WHIP or WHEP session: the client's virtual peer and its call.
+struct Resource {
+    connection: Connection,
+    target: String,
+    session_id: String,
+    /// `a=mid` of each m-section of the offer, to index trickled candidates
+    mids: Vec<String>,
+    /// The client's per-IP connection slot, held until the session ends
+    _guard: Option<ConnectionGuard>,
+}
+
+#[derive(Clone)]
+struct WhipState {
+    signaler: Arc<Signaler>,
+    /// Keyed by the client's peer id, which is also the resource id
+    resources: Arc<DashMap<String, Arc<Resource>>>,
+}
+
+/// Why a WHIP or WHEP offer did not get an answer.
+enum CallFailure {
+    Declined,
+    Rejected { rule: Option<String>, reason: String },
+    TimedOut,
+}
+
+/// WHIP (`/whip/:peer_id`) and WHEP (`/whep/:peer_id`) endpoints. Each request
+/// registers the client as a virtual peer that calls `peer_id` with the posted
+/// offer, and answers with the SDP the peer answers with.
+pub fn router<S>(signaler: Arc<Signaler>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/whip/:peer_id", post(whip))
+        .route("/whep/:peer_id", post(whep))
+        .route("/whip/:peer_id/:resource_id", patch(trickle).delete(end))
+        .route("/whep/:peer_id/:resource_id", patch(trickle).delete(end))
+        .with_state(WhipState {
+            signaler,
+            resources: Arc::new(DashMap::new()),
+        })
+}
+
+async fn whip(
+    State(state): State<WhipState>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    Path(peer_id): Path<String>,
+    headers: HeaderMap,
+    OriginalUri(uri): OriginalUri,
+    body: Bytes,
+) -> Response {
+    offer(state, Direction::Ingest, peer_id, remote_addr, headers, uri, body).await
+}
+
+async fn whep(
+    State(state): State<WhipState>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    Path(peer_id): Path<String>,
+    headers: HeaderMap,
+    OriginalUri(uri): OriginalUri,
+    body: Bytes,
+) -> Response {
+    offer(state, Direction::Egress, peer_id, remote_addr, headers, uri, body).await
+}
+
+async fn offer(
+    state: WhipState,
+    direction: Direction,
+    target: String,
+    remote_addr: SocketAddr,
+    headers: HeaderMap,
+    uri: Uri,
+    body: Bytes,
+) -> Response {
+    let signaler = &state.signaler;
+    if !authorized(signaler, &headers) {
+        return unauthorized();
+    }
+    if !has_content_type(&headers, SDP) {
+        return (StatusCode::UNSUPPORTED_MEDIA_TYPE, "Expected application/sdp").into_response();
+    }
+    let Ok(sdp) = String::from_utf8(body.to_vec()) else {
+        return (StatusCode::BAD_REQUEST, "Offer is not valid UTF-8").into_response();
+    };
+    if !signaler.is_reachable(&target) {
+        return (StatusCode::NOT_FOUND, format!("Peer [{}] not available", target)).into_response();
+    }
+
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or(direction.protocol())
+        .to_string();
+    // Sessions count against the same limits as WebSocket connections
+    let (remote_addr, guard) = match admit(signaler, Some(remote_addr), headers).await {
+        Ok(admitted) => admitted,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    let client_id = format!("{}_{}", direction.protocol(), uuid::Uuid::new_v4().simple());
+    let session_id = format!("{}-{}", client_id, target);
+    info!("📡 {} offer from {} for {} (session: {})",
+          direction.protocol().to_uppercase(), describe(remote_addr), target, session_id);
+
+    let (connection, mut events) = signaler.connect(remote_addr);
+    let mut extra = serde_json::Map::new();
+    extra.insert("media".to_string(), media_kind(&sdp).into());
+    extra.insert("transport".to_string(), direction.protocol().into());
+    let mids = media_ids(&sdp);
+    let requests = [
+        Method::New(PeerInfo {
+            id: client_id.clone(),
+            name: direction.peer_name().to_string(),
+            user_agent,
+        }),
+        Method::Offer(DescriptionPayload {
+            from: client_id.clone(),
+            to: target.clone(),
+            session_id: session_id.clone(),
+            description: SessionDescription {
+                sdp,
+                sdp_type: "offer".to_string(),
+            },
+            extra,
+        }),
+    ];
+    for request in requests {
+        if let Err(e) = signaler.receive(&connection, request).await {
+            error!("❌ Failed to hand {} offer to signaling: {}", direction.protocol(), e);
+        }
+    }
+
+    let config = signaler.config.load();
+    let answer_timeout = Duration::from_millis(config.whip.answer_timeout_ms);
+    let candidate_wait = Duration::from_millis(config.whip.candidate_wait_ms);
+    let answer = match wait_for_answer(&mut events.messages, &session_id, answer_timeout, candidate_wait).await {
+        Ok(answer) => answer,
+        Err(failure) => {
+            if matches!(failure, CallFailure::TimedOut) {
+                let bye = Method::Bye(Byebye {
+                    session_id: session_id.clone(),
+                    from: client_id.clone(),
+                    reason: None,
+                });
+                let _ = signaler.receive(&connection, bye).await;
+            }
+            signaler.disconnect(&connection).await;
+            return failure_response(&target, failure);
+        }
+    };
+    info!("✅ {} session {} answered by {}", direction.protocol().to_uppercase(), session_id, target);
+
+    let resource = Arc::new(Resource {
+        connection,
+        target,
+        session_id,
+        mids,
+        _guard: guard,
+    });
+    state.resources.insert(client_id.clone(), resource);
+    tokio::spawn(watch(state.clone(), client_id.clone(), events));
+
+    let location = format!("{}/{}", uri.path().trim_end_matches('/'), client_id);
+    let mut response = (StatusCode::CREATED, answer).into_response();
+    let response_headers = response.headers_mut();
+    response_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(SDP));
+    if let Ok(location) = HeaderValue::from_str(&location) {
+        response_headers.insert(header::LOCATION, location);
+    }
+    for link in ice_server_links(signaler, &client_id).await {
+        response_headers.append(header::LINK, link);
+    }
+    response
+}
+
+async fn trickle(
+    State(state): State<WhipState>,
+    Path((peer_id, resource_id)): Path<(String, String)>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if !authorized(&state.signaler, &headers) {
+        return unauthorized();
+    }
+    let Some(resource) = find(&state, &peer_id, &resource_id) else {
+        return (StatusCode::NOT_FOUND, "Unknown session").into_response();
+    };
+    if !has_content_type(&headers, SDP_FRAGMENT) {
+        return (StatusCode::UNSUPPORTED_MEDIA_TYPE, "Expected application/trickle-ice-sdpfrag").into_response();
+    }
+
+    let fragment = String::from_utf8_lossy(&body);
+    for candidate in parse_candidates(&fragment, &resource.mids) {
+        debug!("🔗 Trickled ICE candidate from {} to {}", resource_id, resource.target);
+        let message = Method::Candidate(CandidatePayload {
+            from: resource_id.clone(),
+            to: resource.target.clone(),
+            session_id: resource.session_id.clone(),
+            candidate,
+            extra: serde_json::Map::new(),
+        });
+        if let Err(e) = state.signaler.receive(&resource.connection, message).await {
+            error!("❌ Failed to relay trickled candidate for {}: {}", resource_id, e);
+        }
+    }
+    StatusCode::NO_CONTENT.into_response()
+}
+
+async fn end(
+    State(state): State<WhipState>,
+    Path((peer_id, resource_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    if !authorized(&state.signaler, &headers) {
+        return unauthorized();
+    }
+    let removed = state.resources.remove_if(&resource_id, |_, resource| resource.target == peer_id);
+    let Some((_, resource)) = removed else {
+        return (StatusCode::NOT_FOUND, "Unknown session").into_response();
+    };
+
+    info!("📞 CALL ENDED: {} deleted session {}", resource_id, resource.session_id);
+    let bye = Method::Bye(Byebye {
+        session_id: resource.session_id.clone(),
+        from: resource_id,
+        reason: None,
+    });
+    if let Err(e) = state.signaler.receive(&resource.connection, bye).await {
+        error!("❌ Failed to send bye for {}: {}", resource.session_id, e);
+    }
+    state.signaler.disconnect(&resource.connection).await;
+    StatusCode::OK.into_response()
+}
+
+// Tears the resource down once the called peer hangs up or goes away, or the
+// client's peer is disconnected through the admin API. Also ends when the
+// resource is deleted, as that closes the last sender of `messages`.
+async fn watch(state: WhipState, client_id: String, mut events: ConnectionEvents) {
+    loop {
+        let message = tokio::select! {
+            message = events.messages.recv() => match message {
+                Some(message) => message,
+                None => break,
+            },
+            Some(frame) = events.close.recv() => {
+                info!("🔌 Closing session of {}: {}", client_id, frame.reason);
+                break;
+            }
+        };
+        let Some(resource) = state.resources.get(&client_id).map(|resource| resource.clone()) else {
+            break;
+        };
+        let ended = match message {
+            Method::Bye(bye) if bye.session_id == resource.session_id => {
+                info!("📞 CALL ENDED: {} hung up on {}", resource.target, client_id);
+                true
+            }
+            Method::Peers(peers) => !peers.iter().any(|peer| peer.id == resource.target),
+            Method::Candidate(_) => {
+                debug!("Dropping late ICE candidate for {}, WHIP answers carry all server candidates", client_id);
+                false
+            }
+            Method::Error(e) => {
+                warn!("⚠️ Signaling rejected {} from {}: {}", e.request, client_id, e.reason);
+                false
+            }
+            _ => false,
+        };
+        if ended {
+            break;
+        }
+    }
+
+    if let Some((_, resource)) = state.resources.remove(&client_id) {
+        state.signaler.disconnect(&resource.connection).await;
+    }
+}
+
+// Waits for the answer to `session_id`, then collects the callee's candidates
+// for `candidate_wait` and merges them into the answer SDP
+async fn wait_for_answer(
+    messages: &mut mpsc::UnboundedReceiver<Method>,
+    session_id: &str,
+    answer_timeout: Duration,
+    candidate_wait: Duration,
+) -> Result<String, CallFailure> {
+    let mut candidates = Vec::new();
+    let mut deadline = Instant::now() + answer_timeout;
+    let mut answer = None;
+
+    loop {
+        let message = match tokio::time::timeout_at(deadline, messages.recv()).await {
+            Ok(Some(message)) => message,
+            Ok(None) => return Err(CallFailure::TimedOut),
+            Err(_) => break,
+        };
+        match message {
+            Method::Answer(payload) if payload.session_id == session_id && answer.is_none() => {
+                answer = Some(payload.description.sdp);
+                deadline = Instant::now() + candidate_wait;
+            }
+            Method::Candidate(payload) if payload.session_id == session_id => {
+                candidates.push(payload.candidate);
+            }
+            Method::Bye(bye) if bye.session_id == session_id => return Err(CallFailure::Declined),
+            Method::Error(e) if answer.is_none() => {
+                return Err(CallFailure::Rejected {
+                    rule: e.rule,
+                    reason: e.reason,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    match answer {
+        Some(sdp) => Ok(add_candidates(&sdp, &candidates)),
+        None => Err(CallFailure::TimedOut),
+    }
+}
+
+fn failure_response(target: &str, failure: CallFailure) -> Response {
+    match failure {
+        CallFailure::Declined => {
+            info!("📞 {} declined the call", target);
+            (StatusCode::FORBIDDEN, format!("Peer [{}] declined the call", target)).into_response()
+        }
+        CallFailure::Rejected { rule, reason } => {
+            warn!("⚠️ Call to {} rejected: {}", target, reason);
+            let status = match rule.as_deref() {
+                Some("hook") => StatusCode::FORBIDDEN,
+                Some("rate_limit") => StatusCode::TOO_MANY_REQUESTS,
+                Some(_) => StatusCode::BAD_REQUEST,
+                None => StatusCode::SERVICE_UNAVAILABLE,
+            };
+            (status, reason).into_response()
+        }
+        CallFailure::TimedOut => {
+            warn!("⏱️ {} did not answer in time", target);
+            (StatusCode::GATEWAY_TIMEOUT, format!("Peer [{}] did not answer", target)).into_response()
+        }
+    }
+}
+
+fn authorized(signaler: &Signaler, headers: &HeaderMap) -> bool {
+    // Read per request so a reloaded token takes effect immediately
+    let config = signaler.config.load();
+    let Some(expected) = config.whip.token.as_deref() else {
+        return true;
+    };
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    provided.is_some_and(|token| constant_time_eq(token.as_bytes(), expected.as_bytes()))
+}
+
+fn unauthorized() -> Response {
+    warn!("Rejected unauthenticated WHIP/WHEP request");
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        "Invalid or missing token",
+    )
+        .into_response()
+}
+
+fn find(state: &WhipState, peer_id: &str, resource_id: &str) -> Option<Arc<Resource>> {
+    state
+        .resources
+        .get(resource_id)
+        .filter(|resource| resource.target == peer_id)
+        .map(|resource| resource.clone())
+}
+
+fn has_content_type(headers: &HeaderMap, expected: &str) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|value| value.trim().eq_ignore_ascii_case(expected))
+}
+
+// STUN and TURN servers as `Link` headers, with TURN credentials for the client
+async fn ice_server_links(signaler: &Signaler, client_id: &str) -> Vec<HeaderValue> {
+    let turn = signaler.config.load().turn.clone();
+    let mut links = vec![format!("<stun:{}:{}>; rel=\"ice-server\"", turn.public_ip, turn.port)];
+    match signaler.generate_turn_credentials(client_id).await {
+        Ok(credentials) => links.extend(credentials.uris.iter().map(|uri| {
+            format!(
+                "<{}>; rel=\"ice-server\"; username=\"{}\"; credential=\"{}\"; credential-type=\"password\"",
+                uri, credentials.username, credentials.password
+            )
+        })),
+        Err(e) => error!("Failed to generate TURN credentials for {}: {}", client_id, e),
+    }
+    links.into_iter().filter_map(|link| HeaderValue::from_str(&link).ok()).collect()
+}
+
+// `video` if the offer has a video section, else `audio`, else `data`
+fn media_kind(sdp: &str) -> &'static str {
+    let kinds: Vec<&str> = sdp
+        .lines()
+        .filter_map(|line| line.strip_prefix("m="))
+        .filter_map(|media| media.split_whitespace().next())
+        .collect();
+    if kinds.contains(&"video") {
+        "video"
+    } else if kinds.contains(&"audio") {
+        "audio"
+    } else {
+        "data"
+    }
+}
+
+// `a=mid` of every m-section, or its index for sections without one
+fn media_ids(sdp: &str) -> Vec<String> {
+    let mut mids: Vec<String> = Vec::new();
+    for line in sdp.lines() {
+        if line.starts_with("m=") {
+            mids.push(mids.len().to_string());
+        } else if let (Some(mid), Some(last)) = (line.strip_prefix("a=mid:"), mids.last_mut()) {
+            *last = mid.trim().to_string();
+        }
+    }
+    mids
+}
+
+// Candidates of a trickle-ice-sdpfrag body. Candidates before any `a=mid`
+// belong to the first m-section.
+fn parse_candidates(fragment: &str, mids: &[String]) -> Vec<IceCandidate> {
+    let mut mid = mids.first().cloned();
+    let mut candidates = Vec::new();
+    for line in fragment.lines() {
+        if let Some(value) = line.strip_prefix("a=mid:") {
+            mid = Some(value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("a=") {
+            if !value.starts_with("candidate:") {
+                continue;
+            }
+            let index = mid.as_ref().and_then(|mid| mids.iter().position(|m| m == mid));
+            candidates.push(IceCandidate {
+                candidate: value.trim().to_string(),
+                sdp_mid: mid.clone(),
+                sdp_mline_index: Some(index.unwrap_or(0) as u16),
+            });
+        }
+    }
+    candidates
+}
+
+// Appends `candidates` to the m-sections they belong to, skipping ones already present
+fn add_candidates(sdp: &str, candidates: &[IceCandidate]) -> String {
+    let mut sections: Vec<Vec<String>> = vec![Vec::new()];
+    for line in sdp.lines() {
+        if line.starts_with("m=") {
+            sections.push(Vec::new());
+        }
+        if let Some(section) = sections.last_mut() {
+            section.push(line.to_string());
+        }
+    }
+    let mids = media_ids(sdp);
+
+    for candidate in candidates.iter().filter(|candidate| !candidate.candidate.is_empty()) {
+        let index = candidate
+            .sdp_mid
+            .as_ref()
+            .and_then(|mid| mids.iter().position(|m| m == mid))
+            .or(candidate.sdp_mline_index.map(usize::from));
+        let Some(section) = index.and_then(|index| sections.get_mut(index + 1)) else {
+            continue;
+        };
+        let line = format!("a={}", candidate.candidate.trim_start_matches("a="));
+        if !section.contains(&line) {
+            section.push(line);
+        }
+    }
+
+    let mut merged = sections.concat().join("\r\n");
+    merged.push_str("\r\n");
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::config::Config;
+
+    const OFFER: &str = "v=0\r\nm=video 9 UDP/TLS/RTP/SAVPF 96\r\na=mid:0\r\n";
+    const ANSWER: &str = "v=0\r\nm=video 9 UDP/TLS/RTP/SAVPF 96\r\na=mid:0\r\n";
+    const CANDIDATE: &str = "candidate:1 1 udp 2122260223 192.0.2.1 50000 typ host";
+
+    // Registers `id` as a peer that answers every offer, trickles one candidate
+    // after its answer, and reports whatever else it receives
+    async fn fake_peer(signaler: Arc<Signaler>, id: &str) -> mpsc::UnboundedReceiver<Method> {
+        let (connection, mut events) = signaler.connect(None);
+        let new = Method::New(PeerInfo {
+            id: id.to_string(),
+            name: id.to_string(),
+            user_agent: "test".to_string(),
+        });
+        signaler.receive(&connection, new).await.unwrap();
+
+        let (received_tx, received_rx) = mpsc::unbounded_channel();
+        let id = id.to_string();
+        tokio::spawn(async move {
+            while let Some(message) = events.messages.recv().await {
+                if let Method::Offer(offer) = &message {
+                    let answer = Method::Answer(DescriptionPayload {
+                        from: id.clone(),
+                        to: offer.from.clone(),
+                        session_id: offer.session_id.clone(),
+                        description: SessionDescription {
+                            sdp: ANSWER.to_string(),
+                            sdp_type: "answer".to_string(),
+                        },
+                        extra: serde_json::Map::new(),
+                    });
+                    let candidate = Method::Candidate(CandidatePayload {
+                        from: id.clone(),
+                        to: offer.from.clone(),
+                        session_id: offer.session_id.clone(),
+                        candidate: IceCandidate {
+                            candidate: CANDIDATE.to_string(),
+                            sdp_mid: Some("0".to_string()),
+                            sdp_mline_index: Some(0),
+                        },
+                        extra: serde_json::Map::new(),
+                    });
+                    signaler.receive(&connection, answer).await.unwrap();
+                    signaler.receive(&connection, candidate).await.unwrap();
+                }
+                let _ = received_tx.send(message);
+            }
+        });
+        received_rx
+    }
+
+    async fn serve(signaler: Arc<Signaler>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let app = router::<()>(signaler).into_make_service_with_connect_info::<SocketAddr>();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        base
+    }
+
+    async fn next<T>(received: &mut mpsc::UnboundedReceiver<Method>, select: impl Fn(Method) -> Option<T>) -> T {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(found) = select(received.recv().await.unwrap()) {
+                    break found;
+                }
+            }
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn whip_sessions_offer_trickle_and_end() {
+        let config = Config::builder().set("whip", "candidate_wait_ms", "200").build().unwrap();
+        let signaler = Signaler::builder().config(config).build().unwrap();
+        let mut received = fake_peer(signaler.clone(), "camera").await;
+        let base = serve(signaler.clone()).await;
+        let client = reqwest::Client::new();
+
+        let response = client
+            .post(format!("{}/whip/camera", base))
+            .header("content-type", SDP)
+            .body(OFFER)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 201);
+        assert_eq!(response.headers()["content-type"], SDP);
+        let location = response.headers()["location"].to_str().unwrap().to_string();
+        assert!(location.starts_with("/whip/camera/whip_"));
+        // The candidate trickled after the answer is merged into it
+        let answer = response.text().await.unwrap();
+        assert!(answer.starts_with(ANSWER));
+        assert!(answer.contains(&format!("a={}\r\n", CANDIDATE)));
+
+        let offer = next(&mut received, |message| match message {
+            Method::Offer(offer) => Some(offer),
+            _ => None,
+        })
+        .await;
+        assert_eq!(offer.description.sdp, OFFER);
+        assert_eq!(offer.extra["media"], "video");
+        assert_eq!(offer.extra["transport"], "whip");
+        assert_eq!(format!("/whip/camera/{}", offer.from), location);
+
+        let fragment = format!("a=ice-ufrag:abcd\r\na=mid:0\r\na={}\r\n", CANDIDATE);
+        let response = client
+            .patch(format!("{}{}", base, location))
+            .header("content-type", SDP_FRAGMENT)
+            .body(fragment)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 204);
+        let candidate = next(&mut received, |message| match message {
+            Method::Candidate(candidate) => Some(candidate),
+            _ => None,
+        })
+        .await;
+        assert_eq!(candidate.session_id, offer.session_id);
+        assert_eq!(candidate.candidate.candidate, CANDIDATE);
+        assert_eq!(candidate.candidate.sdp_mid.as_deref(), Some("0"));
+
+        let response = client.delete(format!("{}{}", base, location)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        let bye = next(&mut received, |message| match message {
+            Method::Bye(bye) => Some(bye),
+            _ => None,
+        })
+        .await;
+        assert_eq!(bye.session_id, offer.session_id);
+        assert_eq!(bye.from, offer.from);
+
+        let response = client.delete(format!("{}{}", base, location)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+    }
+}